@@ -1,31 +0,0 @@
-// This file is part of cryptosuite2.                                    //
-//                                                                       //
-// cryptosuite2 is free software: you can redistribute it and/or modify  //
-// it under the terms of the GNU General Public License as published by  //
-// the Free Software Foundation, either version 3 of the License, or     //
-// (at your option) any later version.                                   //
-//                                                                       //
-// cryptosuite2 is distributed in the hope that it will be useful,       //
-// but WITHOUT ANY WARRANTY; without even the implied warranty of        //
-// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         //
-// GNU General Public License for more details.                          //
-//                                                                       //
-// You should have received a copy of the GNU General Public License     //
-// along with cryptosuite2.  If not, see <http://www.gnu.org/licenses/>. //
-//                                                                       //
-
-// This file is the module config header
-// when the arduino interface is NOT used.
-//
-// If you want to use the library with Arduino,
-// edit sha/config.h. If you use a proper build system, 
-// use this config file for sha1 and sha/sha256/default.h
-// for sha256.
-#ifndef SHA1_DEFAULT_H_
-#define SHA1_DEFAULT_H_
-
-#define SHA1_ENABLE_HMAC
-
-
-#endif
-
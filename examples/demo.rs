@@ -1,26 +1,74 @@
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use embedded_hal::spi::MODE_0;
-use hyper::StatusCode;
-use smart_leds::{SmartLedsWrite, RGB};
+use embedded_storage::ReadStorage;
 use ws2812_esp32_rmt_driver::Ws2812Esp32Rmt;
 
 use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::gpio::{PinDriver, Pull};
 use esp_idf_svc::hal::ledc::config::TimerConfig;
 use esp_idf_svc::hal::ledc::{LedcDriver, LedcTimerDriver, Resolution};
 use esp_idf_svc::hal::prelude::{FromValueType, Peripherals};
+use esp_idf_svc::hal::reset;
 use esp_idf_svc::hal::spi::config::BitOrder;
 use esp_idf_svc::hal::spi::{config, SpiDeviceDriver, SpiDriver, SpiDriverConfig, SPI2};
 use esp_idf_svc::hal::timer::TimerDriver;
 use esp_idf_svc::log::EspLogger;
-use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use esp_idf_svc::mqtt::client::MqttClientConfiguration;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+use esp_idf_svc::wifi::EspWifi;
 use esp_idf_svc::{eventloop::EspSystemEventLoop, nvs::EspDefaultNvsPartition};
 
-use lynx_embedded::ykhmac::{AuthStatus, YubiKeyResult};
-use lynx_embedded::{reqwesp, wifi as espWifi, ykhmac, Pn532};
-
-type Led<'d> = Ws2812Esp32Rmt<'d>;
+use lynx_embedded::access::{self, AccessCache, AllowListStore};
+use lynx_embedded::api::{ApiError, Authorization, LynxApi};
+use lynx_embedded::audit::{self, AuditEntry, AuditLog, Decision, Reason};
+use lynx_embedded::command::{self, CommandChannel, CommandVerifier, RemoteCommand};
+use lynx_embedded::config::{self as device_config, ConfigStore, DeviceConfig, NvsStorage};
+use lynx_embedded::lock::{
+    self, DoorCommand, DoorController, DoorEvent, DoorState, Indicator, ServoConfig, ServoLock,
+    Signal, SignalTimer, StripIndicator, SystemClock,
+};
+use lynx_embedded::mqtt::{Event, MqttLink};
+use lynx_embedded::partition::Partition;
+use lynx_embedded::provision::{self, Portal};
+use lynx_embedded::reqwesp::sign::{HmacAlgorithm, HmacSigner};
+use lynx_embedded::reqwesp::{self, RetryPolicy};
+use lynx_embedded::wifi::{WifiManager, WifiState};
+use lynx_embedded::ykhmac::{
+    self, EnrollmentMode, Keyring, PartitionSecretStore, Slot, YkError, YubiKey, YubiKeyReader,
+    YubiKeyResult,
+};
+use lynx_embedded::Pn532;
+
+/// Number of LEDs on the status ring.
+const LED_COUNT: usize = 25;
+/// How long the LEDs stay red after a denied access attempt.
+const DENIED: Duration = Duration::from_secs(3);
+/// How long each poll of the NFC reader waits for a credential.
+const NFC_POLL: Duration = Duration::from_millis(100);
+/// How often the offline allow-list is downloaded again.
+const ALLOW_LIST_SYNC: Duration = Duration::from_secs(15 * 60);
+/// How often unsent audit records are uploaded, unless Wi-Fi just came back.
+const AUDIT_UPLOAD: Duration = Duration::from_secs(60);
+/// Maximum number of audit records per upload request.
+const AUDIT_BATCH: usize = 16;
+/// Failed Wi-Fi attempts in a row after which the provisioning portal is started.
+const PROVISION_AFTER_FAILURES: u32 = 10;
+/// How long an authorized enrollment waits for a YubiKey to be presented.
+const ENROLL_WINDOW: Duration = Duration::from_secs(60);
+/// How long the provisioning portal waits for credentials before retrying the known networks.
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[toml_cfg::toml_config]
+struct Config {
+    #[default("")]
+    api_key_id: &'static str,
+    #[default("")]
+    api_key: &'static str,
+    #[default("")]
+    mqtt_password: &'static str,
+}
 
 fn main() -> ! {
     demo().expect("Error in demo");
@@ -55,27 +103,55 @@ fn demo() -> Result<()> {
         &esp_idf_svc::hal::timer::TimerConfig::new(),
     )?;
 
-    if let Err(e) = ykhmac::initialize_pn532(Pn532::new(device, timer)) {
+    let mut yubikey: YubiKeyReader = YubiKey::new(Pn532::new(device, timer));
+    if let Err(e) = ykhmac::initialize_pn532(yubikey.transport()) {
         log::error!("Failed to initialize PN532: {e:?}");
         return Ok(());
     }
 
-    let secret_key_str = "deadbeef";
-    if let Err(e) = ykhmac::enroll_key(secret_key_str) {
-        log::error!("Failed to enroll key! {e:?}");
-        return Ok(());
+    let mut keyring = Keyring::new(PartitionSecretStore::find(ykhmac::PARTITION_LABEL)?)?;
+    for key in keyring.keys() {
+        log::info!("Enrolled YubiKey {} in {:?}", key.serial, key.slot);
     }
 
-    let mut wifi = BlockingWifi::wrap(
-        EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs))?,
-        sys_loop,
-    )?;
+    let mut store = ConfigStore::new(
+        NvsStorage::new(nvs.clone(), device_config::NVS_NAMESPACE)?,
+        DeviceConfig::default(),
+    );
+    let settings = store.load().unwrap_or_else(|e| {
+        log::warn!("Ignoring stored configuration: {e:?}");
+        store.defaults().clone()
+    });
+
+    let mut verifier = CommandVerifier::new(
+        NvsStorage::new(nvs.clone(), command::NVS_NAMESPACE)?,
+        CONFIG.api_key.as_bytes(),
+        settings.door_id,
+    );
+    if let Err(e) = &verifier {
+        log::warn!("Remote commands disabled: {e}");
+    }
+    let enrollment_storage = NvsStorage::new(nvs.clone(), ykhmac::NVS_NAMESPACE)?;
 
-    espWifi::connect(&mut wifi)?;
-    log::info!("Wifi connected!");
+    let esp_wifi = EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs))?;
+    if settings.networks.is_empty() {
+        log::warn!("No Wifi network configured");
+        return provision(esp_wifi, &mut store, settings);
+    }
+    let mut wifi = WifiManager::new(esp_wifi, &sys_loop, settings.networks.clone())?;
+    let wifi_status = wifi.subscribe();
+    wifi.start()?;
+
+    // Requests are signed with the current time, which the backend only
+    // accepts once it has been synchronized.
+    let sntp = EspSntp::new_default()?;
+    let mut time_synced = false;
 
-    let mut led = Led::new(peripherals.rmt.channel0, peripherals.pins.gpio3)?;
-    led.write(std::iter::repeat(RGB::new(0x00, 0x00, 0x10)).take(25))?;
+    let mut led = StripIndicator::new(
+        Ws2812Esp32Rmt::new(peripherals.rmt.channel0, peripherals.pins.gpio3)?,
+        LED_COUNT,
+    );
+    led.show(Signal::Offline)?;
 
     // Configure and Initialize LEDC Timer Driver
     let timer_driver = LedcTimerDriver::new(
@@ -92,145 +168,469 @@ fn demo() -> Result<()> {
         peripherals.pins.gpio10,
     )?;
 
-    let start_position = DoorPosition::Neutral;
-    let servo_delay = 12;
-
-    let mut servo = ServoHandler::new(servo_driver, start_position, servo_delay);
+    let servo_config = match settings.servo.validate() {
+        Ok(()) => settings.servo,
+        Err(e) => {
+            log::warn!("Ignoring stored servo settings: {e}");
+            ServoConfig::default()
+        }
+    };
+    let mut servo = ServoLock::new(servo_driver, servo_config)?;
     FreeRtos::delay_ms(100);
 
-    let mut client = reqwesp::Client::new()?;
-    // Endpoint for testing REST requests
-    let url = "https://app.lynx-locks.com/api/doors/unlocked/1";
+    let mut builder = reqwesp::Client::builder()
+        .user_agent(concat!("lynx-embedded/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(10))
+        .retry(RetryPolicy::default());
+    if !CONFIG.api_key.is_empty() {
+        builder = builder.signer(HmacSigner::new(
+            CONFIG.api_key_id,
+            CONFIG.api_key.as_bytes(),
+            HmacAlgorithm::Sha256,
+        )?);
+    }
+    let mut api = LynxApi::new(builder.build()?, &settings.backend_url, settings.door_id)?;
+
+    let mut link = MqttLink::new(
+        &settings.mqtt_url,
+        settings.door_id,
+        &MqttClientConfiguration {
+            client_id: Some(CONFIG.api_key_id),
+            username: Some(CONFIG.api_key_id),
+            password: (!CONFIG.mqtt_password.is_empty()).then_some(CONFIG.mqtt_password),
+            crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+            ..Default::default()
+        },
+    )?;
+    report(
+        &mut link,
+        Event::Boot {
+            firmware: env!("CARGO_PKG_VERSION").to_string(),
+        },
+    );
+    let mut wifi_connected = false;
+
+    let allow_list = Partition::find(access::PARTITION_LABEL)?;
+    let size = allow_list.capacity();
+    let mut cache = AccessCache::new(
+        AllowListStore::new(allow_list, 0, size)?,
+        CONFIG.api_key.as_bytes(),
+        settings.door_id,
+        settings.offline_policy,
+    );
+    if let Err(e) = &cache {
+        log::warn!("Offline access disabled: {e}");
+    }
+    let mut last_sync: Option<Instant> = None;
+
+    let audit_log = Partition::find(audit::PARTITION_LABEL)?;
+    let size = audit_log.capacity();
+    let mut audit = AuditLog::new(audit_log, 0, size)?;
+    let mut last_upload: Option<Instant> = None;
+
+    let mut door = DoorController::new(
+        SystemClock::new(),
+        Duration::from_millis(settings.hold_open_ms.into()),
+    );
+    let mut denied = SignalTimer::new(SystemClock::new(), DENIED);
+
+    // Held for `ykhmac::BUTTON_HOLD`, the button lets the next YubiKey be enrolled.
+    // It is the BOOT button on GPIO9, which pulls the pin low when pressed.
+    let mut button = PinDriver::input(peripherals.pins.gpio9)?;
+    button.set_pull(Pull::Up)?;
+    let mut enrollment =
+        EnrollmentMode::new(SystemClock::new(), ENROLL_WINDOW, enrollment_storage)?;
+
+    // A card left on the reader is only handled once, until it leaves the field.
+    let mut last_uid: Option<Vec<u8>> = None;
 
     log::info!("Waiting for authorized credentials...");
     loop {
-        let mut req = client.get(url);
-        let res = req.send()?;
+        if let Some(command) = door.poll() {
+            run(&mut door, command, &mut servo, &mut led, &mut link);
+        }
+        if denied.poll() {
+            led.show(door_signal(&door, wifi_connected))?;
+        }
+        enrollment.button(button.is_low());
 
-        if let StatusCode::OK = res.status() {
-            log::info!("Door unlocked!");
-            unlock(&mut led, &mut servo)?;
+        if let Err(e) = wifi.poll() {
+            log::warn!("Wifi error: {e:?}");
+        }
+        if !time_synced && sntp.get_sync_status() == SyncStatus::Completed {
+            log::info!("Time synchronized");
+            time_synced = true;
+        }
+        if wifi.failures() >= PROVISION_AFTER_FAILURES {
+            log::warn!("Cannot join any Wifi network");
+            // The door must not stay open while the lock only serves the portal.
+            run(
+                &mut door,
+                DoorCommand::Lock,
+                &mut servo,
+                &mut led,
+                &mut link,
+            );
+            return provision(wifi.into_wifi(), &mut store, settings);
+        }
+        while let Ok(status) = wifi_status.try_recv() {
+            let connected = status.state == WifiState::Connected;
+            if connected == wifi_connected
+                || !matches!(status.state, WifiState::Connected | WifiState::Disconnected)
+            {
+                continue;
+            }
+            log::info!("Wifi connected: {connected}");
+            wifi_connected = connected;
+            report(
+                &mut link,
+                Event::Wifi {
+                    connected,
+                    rssi: status.rssi,
+                },
+            );
+            if connected {
+                // Catch up on what was missed while offline.
+                last_sync = None;
+                last_upload = None;
+            }
+            if door.state() == DoorState::Locked && !denied.is_active() {
+                led.show(idle_signal(wifi_connected))?;
+            }
+        }
+
+        if let Ok(cache) = &mut cache {
+            if wifi_connected
+                && time_synced
+                && last_sync.map_or(true, |time| time.elapsed() >= ALLOW_LIST_SYNC)
+            {
+                last_sync = Some(Instant::now());
+                match api.allow_list() {
+                    Ok(list) => {
+                        if let Err(e) = cache.update(&list) {
+                            log::warn!("Rejected allow-list: {e:?}");
+                        }
+                    }
+                    Err(e) => log::warn!("Cannot sync allow-list: {e}"),
+                }
+            }
+        }
+
+        if wifi_connected
+            && time_synced
+            && audit.has_unsent()
+            && last_upload.map_or(true, |time| time.elapsed() >= AUDIT_UPLOAD)
+        {
+            last_upload = Some(Instant::now());
+            match audit.drain(AUDIT_BATCH, |records| Ok(api.upload_audit(records)?)) {
+                Ok(sent) => log::info!("Uploaded {sent} audit records"),
+                Err(e) => log::warn!("Cannot upload audit records: {e}"),
+            }
+        }
+
+        loop {
+            let signed = match link.try_recv() {
+                Ok(Some(signed)) => signed,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Cannot receive remote commands: {e}");
+                    break;
+                }
+            };
+            let Ok(verifier) = &mut verifier else {
+                continue;
+            };
+            let now = time_synced.then(unix_time);
+            match verifier.verify(&signed, now) {
+                Ok(RemoteCommand::Enroll(request)) => {
+                    log::info!("Remote command: enroll");
+                    if let Err(e) = enrollment.authorize(
+                        &request,
+                        CONFIG.api_key.as_bytes(),
+                        settings.door_id,
+                        now,
+                    ) {
+                        log::warn!("Refused enrollment request: {e}");
+                    }
+                }
+                Ok(RemoteCommand::Revoke { serial }) => {
+                    log::info!("Remote command: revoke {serial}");
+                    match keyring.revoke(serial) {
+                        Ok(true) => report(&mut link, Event::KeyRevoked { serial }),
+                        Ok(false) => log::warn!("YubiKey {serial} is not enrolled"),
+                        Err(e) => log::warn!("Cannot revoke YubiKey {serial}: {e:?}"),
+                    }
+                }
+                Ok(command) => {
+                    log::info!("Remote command: {command:?}");
+                    if command == RemoteCommand::Unlock {
+                        report(&mut link, Event::AccessGranted { serial: None });
+                        record(&mut audit, now, None, Decision::Granted, Reason::Remote);
+                    }
+                    if let Some(event) = command.event() {
+                        dispatch(&mut door, event, &mut servo, &mut led, &mut link);
+                    }
+                }
+                Err(e) => log::warn!("Refused remote command: {e}"),
+            }
         }
 
-        match ykhmac::wait_for_yubikey(Duration::from_millis(1000)) {
+        let target = yubikey.wait_for_target(NFC_POLL);
+        if let YubiKeyResult::Error(_) = target {
+            // Nothing in the field: the next card is new, even the same one.
+            last_uid = None;
+        } else {
+            let uid = yubikey.transport().uid();
+            if last_uid.as_deref() == Some(uid) {
+                continue;
+            }
+            last_uid = Some(uid.to_vec());
+        }
+        match target {
             YubiKeyResult::IsYubiKey => {
                 log::info!("YubiKey detected!");
-                log::info!("Firmware version: {}", ykhmac::get_version());
-                let serial = ykhmac::get_serial();
+                if let Ok(version) = yubikey.version() {
+                    log::info!("Firmware version: {version}");
+                }
+                let serial = match yubikey.serial() {
+                    Ok(serial) => serial,
+                    Err(e) => {
+                        log::warn!("Cannot read serial number: {e:?}");
+                        last_uid = None;
+                        continue;
+                    }
+                };
                 log::info!("Serial number: {serial}");
-                match ykhmac::authenticate() {
-                    AuthStatus::AccessGranted => {
-                        let url =
-                            format!("https://app.lynx-locks.com/api/auth/authorize/1/{serial}");
-                        let mut req = client.get(url.as_str());
-                        let res = req.send()?;
-
-                        if let StatusCode::OK = res.status() {
-                            log::info!("Door unlocked!");
-                            unlock(&mut led, &mut servo)?;
-                        } else {
-                            log::info!("Access Denied");
-                            set_red(&mut led, 3000)?
+                report(&mut link, Event::CredentialPresented { serial });
+                if let Some(permit) = enrollment.take() {
+                    match keyring.enroll(&mut yubikey, Slot::Two, permit) {
+                        Ok(serial) => report(&mut link, Event::KeyEnrolled { serial }),
+                        Err(e) => log::warn!("Cannot enroll YubiKey {serial}: {e:?}"),
+                    }
+                    continue;
+                }
+                let denied = Event::AccessDenied {
+                    serial: Some(serial),
+                };
+                match keyring.authenticate(&mut yubikey) {
+                    Ok(()) => {
+                        let mut reason = Reason::Offline;
+                        let online = || {
+                            if !time_synced {
+                                return Err(ApiError::Http(anyhow!("Time is not synchronized")));
+                            }
+                            let result = api.authorize(serial);
+                            if result.is_ok() {
+                                reason = Reason::Backend;
+                            }
+                            result
+                        };
+                        let authorization = match &cache {
+                            Ok(cache) => {
+                                cache.authorize(serial, time_synced.then(unix_time), online)
+                            }
+                            Err(_) => online().unwrap_or_else(|e| {
+                                log::warn!("Cannot authorize credential: {e}");
+                                Authorization::Denied
+                            }),
+                        };
+                        match authorization {
+                            Authorization::Granted => {
+                                log::info!("Door unlocked!");
+                                record(
+                                    &mut audit,
+                                    time_synced.then(unix_time),
+                                    Some(serial),
+                                    Decision::Granted,
+                                    reason,
+                                );
+                                report(
+                                    &mut link,
+                                    Event::AccessGranted {
+                                        serial: Some(serial),
+                                    },
+                                );
+                                dispatch(
+                                    &mut door,
+                                    DoorEvent::CredentialAccepted,
+                                    &mut servo,
+                                    &mut led,
+                                    &mut link,
+                                );
+                            }
+                            Authorization::Denied => {
+                                log::info!("Access Denied");
+                                record(
+                                    &mut audit,
+                                    time_synced.then(unix_time),
+                                    Some(serial),
+                                    Decision::Denied,
+                                    reason,
+                                );
+                                report(&mut link, denied);
+                                denied.show(&mut led, Signal::Denied)?
+                            }
                         }
                     }
-                    AuthStatus::AccessDenied => set_red(&mut led, 3000)?,
-                    AuthStatus::Error(e) => log::warn!("Auth error: {e:?}"),
+                    Err(
+                        e @ (YkError::ResponseMismatch
+                        | YkError::NotEnrolled(_)
+                        | YkError::SlotNotConfigured),
+                    ) => {
+                        log::info!("Rejected YubiKey: {e}");
+                        record(
+                            &mut audit,
+                            time_synced.then(unix_time),
+                            Some(serial),
+                            Decision::Denied,
+                            Reason::BadCredential,
+                        );
+                        report(&mut link, denied);
+                        denied.show(&mut led, Signal::Denied)?
+                    }
+                    Err(e) => {
+                        // Nothing was decided, so the YubiKey may be tried again.
+                        log::warn!("Auth error: {e}");
+                        last_uid = None;
+                    }
                 }
             }
-            YubiKeyResult::NotYubiKey => set_red(&mut led, 3000)?, // Set LED to red for 3 seconds.
+            YubiKeyResult::NotYubiKey => {
+                record(
+                    &mut audit,
+                    time_synced.then(unix_time),
+                    None,
+                    Decision::Denied,
+                    Reason::UnknownCredential,
+                );
+                report(&mut link, Event::AccessDenied { serial: None });
+                denied.show(&mut led, Signal::Denied)?
+            }
             YubiKeyResult::Error(_) => {}
         }
     }
 }
 
-fn unlock(led: &mut Led, servo: &mut ServoHandler) -> Result<()> {
-    led.write(std::iter::repeat(RGB::new(0x00, 0x10, 0x00)).take(25))?;
-    servo.set_position(DoorPosition::Unlocked);
+/// Serves the provisioning portal until credentials are submitted or it times
+/// out, then restarts to use them.
+fn provision(
+    mut wifi: EspWifi<'static>,
+    store: &mut ConfigStore<NvsStorage>,
+    mut settings: DeviceConfig,
+) -> Result<()> {
+    let _ = wifi.stop();
+    let mac = wifi.ap_netif().get_mac()?;
+    let portal = Portal::start(
+        &mut wifi,
+        &format!("Lynx-Setup-{:02X}{:02X}", mac[4], mac[5]),
+        &provision::setup_password(CONFIG.api_key.as_bytes(), &mac)?,
+        settings
+            .provisioned
+            .then_some(settings.backend_url.as_str()),
+    )?;
 
-    FreeRtos::delay_ms(7000);
-    led.write(std::iter::repeat(RGB::new(0x00, 0x00, 0x10)).take(25))?;
-    servo.set_position(DoorPosition::Locked);
-    servo.set_position(DoorPosition::Neutral);
-    Ok(())
+    let started = Instant::now();
+    while started.elapsed() < PORTAL_TIMEOUT {
+        if let Some(provisioned) = portal.try_recv()? {
+            settings.provision(&provisioned)?;
+            store.save(&settings)?;
+            log::info!("Provisioned for {}, restarting", provisioned.ssid);
+            // Let the confirmation page reach the browser.
+            FreeRtos::delay_ms(1000);
+            break;
+        }
+        FreeRtos::delay_ms(100);
+    }
+    reset::restart();
 }
 
-fn set_red(led: &mut Led, wait_ms: u32) -> Result<()> {
-    led.write(std::iter::repeat(RGB::new(0x10, 0x00, 0x00)).take(25))?;
-
-    FreeRtos::delay_ms(wait_ms);
-    led.write(std::iter::repeat(RGB::new(0x00, 0x00, 0x10)).take(25))?;
-    Ok(())
+/// Feeds `event` to the door controller and carries out the resulting command, if any.
+fn dispatch(
+    door: &mut DoorController<SystemClock>,
+    event: DoorEvent,
+    servo: &mut ServoLock,
+    led: &mut impl Indicator,
+    link: &mut MqttLink,
+) {
+    if let Some(command) = door.handle(event) {
+        run(door, command, servo, led, link);
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-enum DoorPosition {
-    Neutral = 90,
-    Unlocked = 37,
-    Locked = 135,
+/// Carries out `command` and reports the result back to the door controller.
+fn run(
+    door: &mut DoorController<SystemClock>,
+    command: DoorCommand,
+    servo: &mut ServoLock,
+    led: &mut impl Indicator,
+    link: &mut MqttLink,
+) {
+    let result = lock::execute(command, servo, led);
+    if result == DoorEvent::MotionFailed {
+        report(link, Event::ServoFault);
+    }
+    door.handle(result);
 }
 
-struct ServoHandler<'a> {
-    servo: LedcDriver<'a>,
-    current_position: u32,
-    max_duty: u32,
-    servo_delay: u32,
+/// Publishes `event`, only logging failures since telemetry must never block the door.
+fn report(link: &mut MqttLink, event: Event) {
+    if let Err(e) = link.publish(event) {
+        log::warn!("Cannot publish event: {e}");
+    }
 }
 
-impl<'a> ServoHandler<'a> {
-    pub fn new(mut servo: LedcDriver<'a>, start_position: DoorPosition, servo_delay: u32) -> Self {
-        let max_duty = servo.get_max_duty();
-        let min_limit = max_duty * 25 / 1000;
-        let max_limit = max_duty * 125 / 1000;
-        servo
-            .set_duty(Self::map(
-                start_position as u32,
-                0,
-                180,
-                min_limit,
-                max_limit,
-            ))
-            .unwrap();
-        Self {
-            servo,
-            current_position: start_position as u32,
-            max_duty,
-            servo_delay,
-        }
+/// Signal of the current door state, shown again once a denied access attempt
+/// has been shown for long enough.
+fn door_signal(door: &DoorController<SystemClock>, online: bool) -> Signal {
+    match door.state() {
+        DoorState::Unlocking | DoorState::Unlocked => Signal::Granted,
+        _ => idle_signal(online),
     }
+}
 
-    pub fn set_position(&mut self, position: DoorPosition) {
-        log::info!("Moving to {position:?} position...");
-        for mut angle in Self::angle_range(self.current_position, position as u32) {
-            if angle > 180 {
-                angle = 180;
-            }
-            // Set the desired duty cycle
-            self.set_duty(angle);
-            // Give servo some time to update
-            FreeRtos::delay_ms(self.servo_delay);
-        }
-        self.current_position = position as u32;
-        log::info!("Finished moving to {position:?} position!");
+/// Signal shown while waiting for a credential.
+fn idle_signal(online: bool) -> Signal {
+    if online {
+        Signal::Idle
+    } else {
+        Signal::Offline
     }
+}
 
-    fn set_duty(&mut self, position: u32) {
-        log::info!("position: {position}");
-        let min_limit = self.max_duty * 25 / 1000;
-        let max_limit = self.max_duty * 125 / 1000;
-        self.servo
-            .set_duty(Self::map(position, 0, 180, min_limit, max_limit))
-            .unwrap();
+/// Appends an access attempt at Unix time `now` to the audit log, only logging
+/// failures so a full or worn flash never keeps the door closed.
+///
+/// `now` is `None` until the clock is synchronized, in which case the attempt
+/// is stamped with the uptime instead.
+fn record(
+    audit: &mut AuditLog<Partition>,
+    now: Option<u64>,
+    serial: Option<u32>,
+    decision: Decision,
+    reason: Reason,
+) {
+    let entry = AuditEntry {
+        timestamp: now.unwrap_or_else(uptime),
+        unsynced: now.is_none(),
+        serial,
+        decision,
+        reason,
+    };
+    if let Err(e) = audit.append(entry) {
+        log::warn!("Cannot record access attempt: {e:?}");
     }
+}
 
-    fn map(x: u32, in_min: u32, in_max: u32, out_min: u32, out_max: u32) -> u32 {
-        (x - in_min) * (out_max - out_min) / (in_max - in_min) + out_min
-    }
+/// Seconds since boot.
+fn uptime() -> u64 {
+    let micros = unsafe { esp_idf_svc::sys::esp_timer_get_time() };
+    micros as u64 / 1_000_000
+}
 
-    fn angle_range(a: u32, b: u32) -> Box<dyn Iterator<Item = u32>> {
-        if b > a {
-            Box::new(a..=b)
-        } else {
-            Box::new((b..=a).rev())
-        }
-    }
+/// Current Unix time in seconds, or 0 if the clock is before the epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
 }
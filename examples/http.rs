@@ -51,7 +51,7 @@ fn main() -> Result<()> {
         // Send `GET` request
         log::info!("`GET` request");
 
-        let mut req = client.get(url);
+        let req = client.get(url);
         let res = req.send()?;
 
         log::info!(
@@ -65,11 +65,27 @@ fn main() -> Result<()> {
         log::info!("Full Response: {res_text}");
     }
 
+    {
+        // Send `GET` request with a query string
+        log::info!("`GET` request with query string");
+
+        let req = client
+            .get(url)
+            .path_segments(["door", "1"])
+            .query(&[("serial", "12345678"), ("note", "front door")]);
+        let res = req.send()?;
+
+        log::info!("Status Code: {}, URL: {}", res.status(), res.url());
+
+        let res_text = res.text()?;
+        log::info!("Full Response: {res_text}");
+    }
+
     {
         // Send `POST` request with json body
         log::info!("`POST` request with json body");
 
-        let mut req = client.post(url).json(&Account {
+        let req = client.post(url).json(&Account {
             username: "crab".to_string(),
             password: "ferris".to_string(),
         });
@@ -99,7 +115,7 @@ fn main() -> Result<()> {
         params.insert("lang", "rust");
         params.insert("pancakes", "🥞");
 
-        let mut req = client.post(url).form(&params);
+        let req = client.post(url).form(&params);
         let res = req.send()?;
 
         log::info!(
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::gpio::{PinDriver, Pull};
 use esp_idf_svc::hal::peripherals::Peripherals;
 use esp_idf_svc::hal::prelude::FromValueType;
 use esp_idf_svc::hal::spi::config::BitOrder;
@@ -11,7 +12,12 @@ use esp_idf_svc::hal::timer::{TimerConfig, TimerDriver};
 use esp_idf_svc::log::EspLogger;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 
-use lynx_embedded::ykhmac::{AuthStatus, YubiKeyResult};
+use lynx_embedded::config::NvsStorage;
+use lynx_embedded::lock::SystemClock;
+use lynx_embedded::ykhmac::{
+    EnrollmentMode, Keyring, PartitionSecretStore, Slot, YkError, YubiKey, YubiKeyReader,
+    YubiKeyResult,
+};
 use lynx_embedded::{ykhmac, Led};
 use lynx_embedded::{LedError, Pn532};
 
@@ -19,7 +25,7 @@ fn main() -> anyhow::Result<()> {
     // Bind the log crate to the ESP Logging facilities
     EspLogger::initialize_default();
     let _sys_loop = EspSystemEventLoop::take()?;
-    let _nvs = EspDefaultNvsPartition::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
     let peripherals = Peripherals::take()?;
 
     let spi = peripherals.spi2;
@@ -38,17 +44,27 @@ fn main() -> anyhow::Result<()> {
 
     let timer = TimerDriver::new(peripherals.timer10, &TimerConfig::new())?;
 
-    if let Err(e) = ykhmac::initialize_pn532(Pn532::new(device, timer)) {
+    let mut yubikey: YubiKeyReader = YubiKey::new(Pn532::new(device, timer));
+    if let Err(e) = ykhmac::initialize_pn532(yubikey.transport()) {
         log::error!("Failed to initialize PN532: {e:?}");
         return Ok(());
     }
 
-    let secret_key_str = "deadbeef";
-    if let Err(e) = ykhmac::enroll_key(secret_key_str) {
-        log::error!("Failed to enroll key! {e:?}");
-        return Ok(());
+    let mut keyring = Keyring::new(PartitionSecretStore::find(ykhmac::PARTITION_LABEL)?)?;
+    for key in keyring.keys() {
+        log::info!("Enrolled YubiKey {} in {:?}", key.serial, key.slot);
     }
 
+    // Hold the BOOT button on GPIO-9, active low, to enroll the next YubiKey
+    // within a minute.
+    let mut button = PinDriver::input(peripherals.pins.gpio9)?;
+    button.set_pull(Pull::Up)?;
+    let mut enrollment = EnrollmentMode::new(
+        SystemClock::new(),
+        Duration::from_secs(60),
+        NvsStorage::new(nvs, ykhmac::NVS_NAMESPACE)?,
+    )?;
+
     let mut led = Led::new(
         esp_idf_svc::sys::rmt_channel_t_RMT_CHANNEL_0,
         esp_idf_svc::sys::gpio_num_t_GPIO_NUM_8,
@@ -56,19 +72,38 @@ fn main() -> anyhow::Result<()> {
 
     log::info!("Waiting for NFC target...");
     loop {
-        match ykhmac::wait_for_yubikey(Duration::from_millis(30000)) {
+        if enrollment.button(button.is_low()) {
+            log::info!("Present the YubiKey to enroll");
+        }
+        match yubikey.wait_for_target(Duration::from_millis(1000)) {
             YubiKeyResult::IsYubiKey => {
                 log::info!("YubiKey detected!");
-                log::info!("Firmware version: {}", ykhmac::get_version().as_string());
-                log::info!("Serial number: {}", ykhmac::get_serial());
-                match ykhmac::authenticate() {
-                    AuthStatus::AccessGranted => {
+                if let Ok(version) = yubikey.version() {
+                    log::info!("Firmware version: {}", version.as_string());
+                }
+                if let Ok(serial) = yubikey.serial() {
+                    log::info!("Serial number: {serial}");
+                }
+                if let Some(permit) = enrollment.take() {
+                    match keyring.enroll(&mut yubikey, Slot::Two, permit) {
+                        Ok(_) => set_green(&mut led, 3000)?,
+                        Err(e) => log::warn!("Cannot enroll the YubiKey: {e:?}"),
+                    }
+                    continue;
+                }
+                match keyring.authenticate(&mut yubikey) {
+                    Ok(()) => {
                         set_green(&mut led, 3000)? // Set LED to green for 3 seconds.
                     }
-                    AuthStatus::AccessDenied => {
+                    Err(
+                        e @ (YkError::ResponseMismatch
+                        | YkError::NotEnrolled(_)
+                        | YkError::SlotNotConfigured),
+                    ) => {
+                        log::info!("Rejected YubiKey: {e}");
                         set_red(&mut led, 3000)? // Set LED to red for 3 seconds.
                     }
-                    AuthStatus::Error(e) => log::warn!("Auth error: {e:?}"),
+                    Err(e) => log::warn!("Auth error: {e}"),
                 }
             }
             YubiKeyResult::NotYubiKey => set_red(&mut led, 3000)?, // Set LED to red for 3 seconds.
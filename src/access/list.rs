@@ -0,0 +1,75 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::hmac::{constant_time_eq, derive_key, hex, hmac_sha256};
+
+/// Domain of the key signing allow-lists, derived from the key shared with the backend.
+const KEY_DOMAIN: &str = "allow-list";
+
+/// YubiKeys allowed to open the door while the backend is unreachable.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowList {
+    /// The only door the list is valid for.
+    pub door_id: u32,
+    /// Increases every time the list changes, so an older list is never restored.
+    pub version: u64,
+    /// Unix time in seconds after which the list must no longer be trusted.
+    pub expires_at: u64,
+    /// Serial numbers of the allowed YubiKeys.
+    pub serials: Vec<u32>,
+}
+
+impl AllowList {
+    /// Whether the list can still be trusted at Unix time `now`.
+    ///
+    /// `now` is `None` while the clock is not synchronized, in which case the
+    /// list is treated as expired.
+    pub fn is_valid(&self, now: Option<u64>) -> bool {
+        now.is_some_and(|now| now < self.expires_at)
+    }
+
+    pub fn contains(&self, serial: u32) -> bool {
+        self.serials.contains(&serial)
+    }
+}
+
+/// An `AllowList` as sent by the backend and stored in flash.
+///
+/// `list` is the JSON of the `AllowList`, kept as a string so the signature is
+/// checked over the exact bytes the backend signed. `signature` is the hex
+/// HMAC-SHA256 of `list` with the allow-list key derived from the key the lock
+/// shares with the backend by `derive_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAllowList {
+    pub list: String,
+    pub signature: String,
+}
+
+impl SignedAllowList {
+    /// Signs `list` with `key`, like the backend does.
+    pub fn sign(list: &AllowList, key: &[u8]) -> Result<Self> {
+        let list = serde_json::to_string(list)?;
+        let signature = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            list.as_bytes(),
+        )?);
+        Ok(Self { list, signature })
+    }
+
+    /// Checks the signature with `key`, and decodes the list if it is meant
+    /// for `door_id`.
+    pub fn verify(&self, key: &[u8], door_id: u32) -> Result<AllowList> {
+        let expected = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            self.list.as_bytes(),
+        )?);
+        if !constant_time_eq(expected.as_bytes(), self.signature.as_bytes()) {
+            return Err(anyhow!("Allow-list signature mismatch"));
+        }
+        let list: AllowList = serde_json::from_str(&self.list)?;
+        if list.door_id != door_id {
+            return Err(anyhow!("Allow-list is for door {}", list.door_id));
+        }
+        Ok(list)
+    }
+}
@@ -0,0 +1,295 @@
+//! Access decisions while the backend is unreachable.
+//!
+//! The backend signs an `AllowList` of YubiKey serials for each door. The lock
+//! syncs it regularly, keeps it in flash with an `AllowListStore`, and the
+//! `AccessCache` falls back to it when the online authorize call fails, as
+//! configured by its `OfflinePolicy`.
+
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use embedded_storage::Storage;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{ApiError, Authorization};
+
+mod list;
+pub use list::{AllowList, SignedAllowList};
+
+mod store;
+pub use store::AllowListStore;
+
+/// Label of the data partition holding the allow-list in `partitions.csv`.
+pub const PARTITION_LABEL: &str = "allowlist";
+
+/// What to do with a credential when the backend cannot be asked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OfflinePolicy {
+    /// Ask the backend, and fall back to the cached list when it is unreachable.
+    /// Without a valid list, everyone is denied.
+    #[default]
+    FailSecure,
+    /// Ask the backend, and fall back to the cached list when it is unreachable.
+    /// Without a valid list, every authenticated YubiKey is granted.
+    FailOpen,
+    /// Never ask the backend, only the cached list decides.
+    CacheOnly,
+}
+
+impl FromStr for OfflinePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fail-secure" => Ok(OfflinePolicy::FailSecure),
+            "fail-open" => Ok(OfflinePolicy::FailOpen),
+            "cache-only" => Ok(OfflinePolicy::CacheOnly),
+            _ => Err(anyhow!("Unknown offline policy: {s}")),
+        }
+    }
+}
+
+/// Local copy of the allow-list, deciding access when the backend cannot.
+pub struct AccessCache<S> {
+    store: AllowListStore<S>,
+    key: Vec<u8>,
+    door_id: u32,
+    policy: OfflinePolicy,
+    list: Option<AllowList>,
+}
+
+impl<S> AccessCache<S>
+where
+    S: Storage,
+    S::Error: Debug,
+{
+    /// Loads the list of `door_id` kept in `store`, checking its signature
+    /// with `key`.
+    ///
+    /// A missing or invalid list is not an error: the cache starts empty and is
+    /// filled by the next `update()`. An empty `key` is, as anyone could sign a
+    /// list then.
+    pub fn new(
+        mut store: AllowListStore<S>,
+        key: &[u8],
+        door_id: u32,
+        policy: OfflinePolicy,
+    ) -> Result<Self> {
+        if key.is_empty() {
+            return Err(anyhow!("No key to verify the allow-list with"));
+        }
+        let list = match store
+            .load()
+            .and_then(|list| list.map(|l| l.verify(key, door_id)).transpose())
+        {
+            Ok(list) => list,
+            Err(e) => {
+                log::warn!("Ignoring stored allow-list: {e:?}");
+                None
+            }
+        };
+        if let Some(list) = &list {
+            log::info!(
+                "Loaded allow-list v{} with {} serials",
+                list.version,
+                list.serials.len()
+            );
+        }
+
+        Ok(Self {
+            store,
+            key: key.to_vec(),
+            door_id,
+            policy,
+            list,
+        })
+    }
+
+    pub fn policy(&self) -> OfflinePolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: OfflinePolicy) {
+        self.policy = policy;
+    }
+
+    /// Get the cached list, even if it expired.
+    pub fn list(&self) -> Option<&AllowList> {
+        self.list.as_ref()
+    }
+
+    /// Replaces the cached list with `signed`, and stores it in flash.
+    ///
+    /// Fails if the signature does not match, if `signed` is meant for another
+    /// door, or if it is older than the cached list.
+    pub fn update(&mut self, signed: &SignedAllowList) -> Result<()> {
+        let list = signed.verify(&self.key, self.door_id)?;
+        if let Some(current) = &self.list {
+            if list.version < current.version {
+                return Err(anyhow!(
+                    "Allow-list v{} is older than the cached v{}",
+                    list.version,
+                    current.version
+                ));
+            }
+            if list == *current {
+                return Ok(());
+            }
+        }
+
+        self.store.save(signed)?;
+        log::info!(
+            "Updated allow-list to v{} with {} serials",
+            list.version,
+            list.serials.len()
+        );
+        self.list = Some(list);
+        Ok(())
+    }
+
+    /// Decides on the YubiKey with `serial` at Unix time `now`, `None` while
+    /// the clock is not synchronized.
+    ///
+    /// `online` asks the backend. It is not called with `OfflinePolicy::CacheOnly`,
+    /// and its answer is used as is unless it fails with a transient error.
+    pub fn authorize<F>(&self, serial: u32, now: Option<u64>, online: F) -> Authorization
+    where
+        F: FnOnce() -> Result<Authorization, ApiError>,
+    {
+        if self.policy != OfflinePolicy::CacheOnly {
+            match online() {
+                Ok(authorization) => return authorization,
+                Err(e) if e.is_transient() => {
+                    log::warn!("Backend unreachable, using the allow-list: {e}");
+                }
+                Err(e) => {
+                    log::warn!("Cannot authorize credential: {e}");
+                    return Authorization::Denied;
+                }
+            }
+        }
+        self.check(serial, now)
+    }
+
+    /// Decides on the YubiKey with `serial` from the cached list only.
+    ///
+    /// The list is treated as expired while the clock is not synchronized.
+    pub fn check(&self, serial: u32, now: Option<u64>) -> Authorization {
+        match &self.list {
+            Some(list) if list.is_valid(now) => {
+                if list.contains(serial) {
+                    Authorization::Granted
+                } else {
+                    Authorization::Denied
+                }
+            }
+            _ if self.policy == OfflinePolicy::FailOpen => {
+                log::warn!("No valid allow-list, failing open");
+                Authorization::Granted
+            }
+            _ => {
+                log::warn!("No valid allow-list, failing secure");
+                Authorization::Denied
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::mock::RamStorage;
+
+    const KEY: &[u8] = b"secret";
+    const SIZE: usize = 0x1000;
+
+    fn signed(door_id: u32, version: u64, serials: &[u32]) -> SignedAllowList {
+        let list = AllowList {
+            door_id,
+            version,
+            expires_at: 1_000,
+            serials: serials.to_vec(),
+        };
+        SignedAllowList::sign(&list, KEY).unwrap()
+    }
+
+    fn cache(storage: RamStorage, policy: OfflinePolicy) -> AccessCache<RamStorage> {
+        let store = AllowListStore::new(storage, 0, SIZE).unwrap();
+        AccessCache::new(store, KEY, 1, policy).unwrap()
+    }
+
+    fn unreachable() -> Result<Authorization, ApiError> {
+        Err(ApiError::Http(anyhow!("Connection refused")))
+    }
+
+    #[test]
+    fn falls_back_to_the_list_when_the_backend_is_unreachable() {
+        let mut cache = cache(RamStorage::new(SIZE), OfflinePolicy::FailSecure);
+        cache.update(&signed(1, 1, &[42])).unwrap();
+
+        assert_eq!(
+            cache.authorize(42, Some(999), unreachable),
+            Authorization::Granted
+        );
+        assert_eq!(
+            cache.authorize(7, Some(999), unreachable),
+            Authorization::Denied
+        );
+        assert_eq!(
+            cache.authorize(42, Some(999), || Err(ApiError::Forbidden)),
+            Authorization::Denied
+        );
+    }
+
+    #[test]
+    fn keeps_the_list_across_restarts() {
+        let mut store = AllowListStore::new(RamStorage::new(SIZE), 0, SIZE).unwrap();
+        store.save(&signed(1, 3, &[42])).unwrap();
+        let cache = AccessCache::new(store, KEY, 1, OfflinePolicy::CacheOnly).unwrap();
+        assert_eq!(cache.list().map(|list| list.version), Some(3));
+        assert_eq!(cache.check(42, Some(0)), Authorization::Granted);
+    }
+
+    #[test]
+    fn treats_an_expired_list_or_an_unsynchronized_clock_as_no_list() {
+        let mut cache = cache(RamStorage::new(SIZE), OfflinePolicy::FailSecure);
+        cache.update(&signed(1, 1, &[42])).unwrap();
+        assert_eq!(cache.check(42, Some(1_000)), Authorization::Denied);
+        assert_eq!(cache.check(42, None), Authorization::Denied);
+
+        cache.set_policy(OfflinePolicy::FailOpen);
+        assert_eq!(cache.check(7, None), Authorization::Granted);
+    }
+
+    #[test]
+    fn refuses_a_forged_list_or_a_list_for_another_door() {
+        let mut cache = cache(RamStorage::new(SIZE), OfflinePolicy::CacheOnly);
+        let mut forged = signed(1, 1, &[42]);
+        forged.list = forged.list.replace("42", "7");
+        assert!(cache.update(&forged).is_err());
+        assert!(cache.update(&signed(2, 1, &[42])).is_err());
+        assert!(cache.list().is_none());
+
+        let mut store = AllowListStore::new(RamStorage::new(SIZE), 0, SIZE).unwrap();
+        store.save(&signed(2, 1, &[42])).unwrap();
+        let cache = AccessCache::new(store, KEY, 1, OfflinePolicy::CacheOnly).unwrap();
+        assert!(cache.list().is_none());
+    }
+
+    #[test]
+    fn refuses_an_older_list() {
+        let mut cache = cache(RamStorage::new(SIZE), OfflinePolicy::CacheOnly);
+        cache.update(&signed(1, 2, &[42])).unwrap();
+        assert!(cache.update(&signed(1, 1, &[7])).is_err());
+        assert_eq!(cache.check(42, Some(0)), Authorization::Granted);
+    }
+
+    #[test]
+    fn refuses_an_empty_key_or_a_region_without_room_for_a_header() {
+        let store = AllowListStore::new(RamStorage::new(SIZE), 0, SIZE).unwrap();
+        assert!(AccessCache::new(store, b"", 1, OfflinePolicy::FailSecure).is_err());
+        assert!(AllowListStore::new(RamStorage::new(SIZE), 0, 7).is_err());
+    }
+}
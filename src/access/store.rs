@@ -0,0 +1,89 @@
+use std::fmt::Debug;
+
+use anyhow::{anyhow, Result};
+use embedded_storage::Storage;
+
+use super::SignedAllowList;
+
+/// Marks the start of a stored allow-list.
+const MAGIC: [u8; 4] = *b"LXAL";
+
+/// Magic followed by the little-endian length of the payload.
+const HEADER_LEN: usize = 8;
+
+/// Keeps a `SignedAllowList` in a region of flash.
+///
+/// The list is stored as JSON after a small header, and its signature is
+/// checked again every time it is loaded, so a corrupted or tampered region
+/// is never trusted.
+pub struct AllowListStore<S> {
+    storage: S,
+    offset: u32,
+    size: usize,
+}
+
+impl<S> AllowListStore<S>
+where
+    S: Storage,
+    S::Error: Debug,
+{
+    /// Uses the `size` bytes of `storage` starting at `offset`.
+    pub fn new(storage: S, offset: u32, size: usize) -> Result<Self> {
+        if size < HEADER_LEN {
+            return Err(anyhow!("Allow-list region of {size} bytes is too small"));
+        }
+        Ok(Self {
+            storage,
+            offset,
+            size,
+        })
+    }
+
+    /// Reads the stored list, returning `None` if the region is empty.
+    pub fn load(&mut self) -> Result<Option<SignedAllowList>> {
+        let mut header = [0u8; HEADER_LEN];
+        self.storage
+            .read(self.offset, &mut header)
+            .map_err(|e| anyhow!("Cannot read allow-list header: {e:?}"))?;
+        if header[..4] != MAGIC {
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes(header[4..].try_into()?) as usize;
+        if len > self.size - HEADER_LEN {
+            return Err(anyhow!("Stored allow-list is too long ({len} bytes)"));
+        }
+        let mut payload = vec![0u8; len];
+        self.storage
+            .read(self.offset + HEADER_LEN as u32, &mut payload)
+            .map_err(|e| anyhow!("Cannot read allow-list: {e:?}"))?;
+        Ok(Some(serde_json::from_slice(&payload)?))
+    }
+
+    /// Replaces the stored list with `list`.
+    pub fn save(&mut self, list: &SignedAllowList) -> Result<()> {
+        let payload = serde_json::to_vec(list)?;
+        if payload.len() > self.size - HEADER_LEN {
+            return Err(anyhow!(
+                "Allow-list of {} bytes does not fit in {} bytes",
+                payload.len(),
+                self.size - HEADER_LEN
+            ));
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        self.storage
+            .write(self.offset, &bytes)
+            .map_err(|e| anyhow!("Cannot write allow-list: {e:?}"))
+    }
+
+    /// Erases the stored list.
+    pub fn clear(&mut self) -> Result<()> {
+        self.storage
+            .write(self.offset, &[0xFF; HEADER_LEN])
+            .map_err(|e| anyhow!("Cannot clear allow-list: {e:?}"))
+    }
+}
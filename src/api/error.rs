@@ -0,0 +1,128 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use hyper::StatusCode;
+
+/// Error returned by `LynxApi` calls.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The backend did not accept the credentials of the lock (`401`).
+    Unauthorized,
+    /// The lock is not allowed to perform the call (`403`).
+    Forbidden,
+    /// The door or resource does not exist (`404`).
+    NotFound,
+    /// The lock sent too many requests (`429`).
+    RateLimited,
+    /// The backend rejected the request with another `4xx` status.
+    Rejected(StatusCode),
+    /// The backend failed with a `5xx` status.
+    Server(StatusCode),
+    /// The backend answered with a status the call does not expect.
+    Unexpected(StatusCode),
+    /// The request could not be sent, or the response could not be read or decoded.
+    Http(anyhow::Error),
+}
+
+impl ApiError {
+    /// Maps a response status the call did not expect to an `ApiError`.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+            StatusCode::FORBIDDEN => ApiError::Forbidden,
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited,
+            status if status.is_client_error() => ApiError::Rejected(status),
+            status if status.is_server_error() => ApiError::Server(status),
+            status => ApiError::Unexpected(status),
+        }
+    }
+
+    /// Returns the status code, if the error was generated from a response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Unauthorized => Some(StatusCode::UNAUTHORIZED),
+            ApiError::Forbidden => Some(StatusCode::FORBIDDEN),
+            ApiError::NotFound => Some(StatusCode::NOT_FOUND),
+            ApiError::RateLimited => Some(StatusCode::TOO_MANY_REQUESTS),
+            ApiError::Rejected(status)
+            | ApiError::Server(status)
+            | ApiError::Unexpected(status) => Some(*status),
+            ApiError::Http(_) => None,
+        }
+    }
+
+    /// Returns true if the same call may succeed later without any change.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ApiError::RateLimited | ApiError::Server(_) | ApiError::Http(_)
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized => f.write_str("lock is not authenticated"),
+            ApiError::Forbidden => f.write_str("lock is not allowed to make this call"),
+            ApiError::NotFound => f.write_str("door or resource not found"),
+            ApiError::RateLimited => f.write_str("too many requests"),
+            ApiError::Rejected(status) => write!(f, "request rejected ({status})"),
+            ApiError::Server(status) => write!(f, "backend error ({status})"),
+            ApiError::Unexpected(status) => write!(f, "unexpected response ({status})"),
+            ApiError::Http(e) => write!(f, "HTTP error: {e}"),
+        }
+    }
+}
+
+impl StdError for ApiError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ApiError::Http(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Http(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::StatusCode;
+
+    use super::ApiError;
+
+    #[test]
+    fn maps_statuses_to_errors() {
+        let cases = [
+            (401, "Unauthorized"),
+            (403, "Forbidden"),
+            (404, "NotFound"),
+            (429, "RateLimited"),
+            (400, "Rejected(400)"),
+            (500, "Server(500)"),
+            (503, "Server(503)"),
+            (302, "Unexpected(302)"),
+        ];
+        for (status, expected) in cases {
+            let status = StatusCode::from_u16(status).unwrap();
+            let error = ApiError::from_status(status);
+            assert_eq!(format!("{error:?}"), expected);
+            assert_eq!(error.status(), Some(status));
+        }
+    }
+
+    #[test]
+    fn only_retries_transient_errors() {
+        assert!(ApiError::from_status(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(ApiError::from_status(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(ApiError::Http(anyhow::anyhow!("timeout")).is_transient());
+        assert!(!ApiError::from_status(StatusCode::UNAUTHORIZED).is_transient());
+        assert!(!ApiError::from_status(StatusCode::BAD_REQUEST).is_transient());
+    }
+}
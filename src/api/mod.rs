@@ -0,0 +1,256 @@
+//! Typed client for the Lynx backend API.
+//!
+//! All calls are made for a single door, relative to a base URL:
+//!
+//! - `GET doors/unlocked/{door}`: `200` if an unlock is pending, `204` or `404` otherwise.
+//! - `GET auth/authorize/{door}/{serial}`: `200` if the credential is granted,
+//!   `403` if it is denied.
+//! - `GET doors/{door}/allowlist`: the `SignedAllowList` used when the backend is unreachable.
+//! - `POST doors/{door}/events` with an `EventReport`.
+//! - `POST doors/{door}/audit` with a batch of `AuditRecord`s.
+//! - `POST doors/{door}/heartbeat` with a `Heartbeat`, answered with an optional
+//!   `HeartbeatResponse`.
+//!
+//! Any other status is mapped to an `ApiError`.
+
+use hyper::StatusCode;
+
+use embedded_svc::http::client::Connection;
+
+use crate::access::SignedAllowList;
+use crate::audit::AuditRecord;
+use crate::reqwesp::error::HttpError;
+use crate::reqwesp::transport::{DefaultConnection, ResponseHeaders};
+use crate::{Client, IntoUrl, RequestBuilder, Url};
+
+mod error;
+pub use error::ApiError;
+
+mod types;
+pub use types::{Authorization, DoorStatus, EventKind, EventReport, Heartbeat, HeartbeatResponse};
+
+/// Base URL of the production backend.
+pub const DEFAULT_BASE_URL: &str = "https://app.lynx-locks.com/api/";
+
+/// Client for the Lynx backend API of a single door.
+pub struct LynxApi<C = DefaultConnection> {
+    client: Client<C>,
+    base_url: Url,
+    door_id: u32,
+}
+
+impl<C> LynxApi<C>
+where
+    C: Connection + ResponseHeaders,
+    C::Error: HttpError,
+{
+    /// Constructs a new `LynxApi` for `door_id`, sending requests to `base_url` with `client`.
+    pub fn new<U: IntoUrl>(client: Client<C>, base_url: U, door_id: u32) -> anyhow::Result<Self> {
+        Ok(Self {
+            client,
+            base_url: base_url.into_url()?,
+            door_id,
+        })
+    }
+
+    /// Get the base URL of the API.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Get the identifier of the door.
+    pub fn door_id(&self) -> u32 {
+        self.door_id
+    }
+
+    /// Get a mutable reference to the underlying `Client`.
+    pub fn client(&mut self) -> &mut Client<C> {
+        &mut self.client
+    }
+
+    /// Check whether an unlock was requested remotely.
+    pub fn door_status(&mut self) -> Result<DoorStatus, ApiError> {
+        let door = self.door_id.to_string();
+        let res = self.get(["doors", "unlocked", &door]).send()?;
+        match res.status() {
+            StatusCode::OK => Ok(DoorStatus::Unlocked),
+            StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Ok(DoorStatus::Locked),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    /// Ask whether the YubiKey with `serial` may open the door.
+    pub fn authorize(&mut self, serial: u32) -> Result<Authorization, ApiError> {
+        let door = self.door_id.to_string();
+        let serial = serial.to_string();
+        let res = self.get(["auth", "authorize", &door, &serial]).send()?;
+        match res.status() {
+            StatusCode::OK => Ok(Authorization::Granted),
+            StatusCode::FORBIDDEN => Ok(Authorization::Denied),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    /// Download the list of YubiKeys allowed while the backend is unreachable.
+    pub fn allow_list(&mut self) -> Result<SignedAllowList, ApiError> {
+        let door = self.door_id.to_string();
+        let res = self.get(["doors", &door, "allowlist"]).send()?;
+        match res.status() {
+            StatusCode::OK => Ok(res.json()?),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    /// Report an event that happened at the door.
+    pub fn report_event(&mut self, event: &EventReport) -> Result<(), ApiError> {
+        let door = self.door_id.to_string();
+        let res = self.post(["doors", &door, "events"]).json(event).send()?;
+        match res.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    /// Upload records of the local audit log.
+    pub fn upload_audit(&mut self, records: &[AuditRecord]) -> Result<(), ApiError> {
+        let door = self.door_id.to_string();
+        let res = self.post(["doors", &door, "audit"]).json(&records).send()?;
+        match res.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    /// Tell the backend the lock is alive.
+    pub fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<HeartbeatResponse, ApiError> {
+        let door = self.door_id.to_string();
+        let res = self
+            .post(["doors", &door, "heartbeat"])
+            .json(heartbeat)
+            .send()?;
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(HeartbeatResponse::default()),
+            status if status.is_success() => Ok(res.json()?),
+            status => Err(ApiError::from_status(status)),
+        }
+    }
+
+    fn get<'a, const N: usize>(&'a mut self, path: [&str; N]) -> RequestBuilder<'a, C> {
+        self.client.get(self.base_url.clone()).path_segments(path)
+    }
+
+    fn post<'a, const N: usize>(&'a mut self, path: [&str; N]) -> RequestBuilder<'a, C> {
+        self.client.post(self.base_url.clone()).path_segments(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_svc::http::Method;
+    use serde_json::json;
+
+    use super::*;
+    use crate::reqwesp::transport::{MockConnection, MockResponse};
+
+    fn api(response: MockResponse) -> (LynxApi<MockConnection>, MockConnection) {
+        let mock = MockConnection::new();
+        mock.push_response(response);
+        let client = Client::with_connection(mock.clone());
+        (
+            LynxApi::new(client, "http://lynx.local/api/", 7).unwrap(),
+            mock,
+        )
+    }
+
+    #[test]
+    fn maps_the_door_status() {
+        let (mut lynx, mock) = api(MockResponse::new(200));
+        assert_eq!(lynx.door_status().unwrap(), DoorStatus::Unlocked);
+        let request = mock.last_request().unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.uri, "http://lynx.local/api/doors/unlocked/7");
+
+        for status in [204, 404] {
+            let (mut lynx, _) = api(MockResponse::new(status));
+            assert_eq!(lynx.door_status().unwrap(), DoorStatus::Locked);
+        }
+    }
+
+    #[test]
+    fn maps_the_authorization() {
+        let (mut lynx, mock) = api(MockResponse::new(200));
+        assert_eq!(lynx.authorize(1234).unwrap(), Authorization::Granted);
+        assert_eq!(
+            mock.last_request().unwrap().uri,
+            "http://lynx.local/api/auth/authorize/7/1234"
+        );
+
+        let (mut lynx, _) = api(MockResponse::new(403));
+        assert_eq!(lynx.authorize(1234).unwrap(), Authorization::Denied);
+
+        let (mut lynx, _) = api(MockResponse::new(401));
+        assert!(matches!(lynx.authorize(1234), Err(ApiError::Unauthorized)));
+    }
+
+    #[test]
+    fn maps_error_statuses() {
+        let event = EventReport {
+            kind: EventKind::RemoteUnlock,
+            serial: None,
+            timestamp: 0,
+        };
+        let (mut lynx, _) = api(MockResponse::new(202));
+        lynx.report_event(&event).unwrap();
+
+        let (mut lynx, _) = api(MockResponse::new(403));
+        assert!(matches!(
+            lynx.report_event(&event),
+            Err(ApiError::Forbidden)
+        ));
+
+        let (mut lynx, _) = api(MockResponse::new(404));
+        assert!(matches!(lynx.authorize(1234), Err(ApiError::NotFound)));
+
+        let (mut lynx, _) = api(MockResponse::new(503));
+        let error = lynx.report_event(&event).unwrap_err();
+        assert!(matches!(
+            error,
+            ApiError::Server(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert!(error.is_transient());
+    }
+
+    #[test]
+    fn decodes_the_heartbeat_response() {
+        let heartbeat = Heartbeat {
+            firmware: "1.0.0".to_string(),
+            uptime: 60,
+            locked: true,
+        };
+        let (mut lynx, mock) = api(MockResponse::new(200).json(&json!({ "hold_open_ms": 3000 })));
+        let response = lynx.heartbeat(&heartbeat).unwrap();
+        assert_eq!(response.hold_open_ms, Some(3000));
+        let request = mock.last_request().unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.uri, "http://lynx.local/api/doors/7/heartbeat");
+
+        let (mut lynx, _) = api(MockResponse::new(204));
+        assert_eq!(lynx.heartbeat(&heartbeat).unwrap().hold_open_ms, None);
+    }
+
+    #[test]
+    fn malformed_json_is_an_http_error() {
+        let (mut lynx, _) = api(MockResponse::new(200).body("{not json"));
+        let Err(ApiError::Http(error)) = lynx.heartbeat(&Heartbeat {
+            firmware: "1.0.0".to_string(),
+            uptime: 60,
+            locked: true,
+        }) else {
+            panic!("malformed heartbeat response was accepted");
+        };
+        let error = error
+            .downcast_ref::<crate::reqwesp::error::Error<serde_json::Error>>()
+            .unwrap();
+        assert!(error.is_decode());
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether the backend wants the door unlocked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorStatus {
+    /// An unlock was requested remotely.
+    Unlocked,
+    /// No unlock is pending.
+    Locked,
+}
+
+/// Decision of the backend on a presented credential.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authorization {
+    Granted,
+    Denied,
+}
+
+/// Kinds of events reported with `LynxApi::report_event()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A credential was accepted and the door unlocked.
+    AccessGranted,
+    /// A credential was rejected.
+    AccessDenied,
+    /// The door was unlocked remotely.
+    RemoteUnlock,
+    /// The door relocked after being held open.
+    Relocked,
+    /// The latch failed to move.
+    Fault,
+}
+
+/// Event sent to `POST /doors/{door}/events`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventReport {
+    pub kind: EventKind,
+    /// Serial number of the YubiKey involved, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<u32>,
+    /// Unix time in seconds at which the event happened.
+    pub timestamp: u64,
+}
+
+/// Liveness report sent to `POST /doors/{door}/heartbeat`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// Firmware version of the lock.
+    pub firmware: String,
+    /// Seconds since the lock booted.
+    pub uptime: u64,
+    /// Whether the door is currently locked.
+    pub locked: bool,
+}
+
+/// Settings the backend may send back in answer to a `Heartbeat`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct HeartbeatResponse {
+    /// Time in milliseconds the door should stay unlocked after an unlock.
+    #[serde(default)]
+    pub hold_open_ms: Option<u64>,
+}
@@ -0,0 +1,156 @@
+//! Host-side stand-in for the flash, to exercise the `AuditLog` without hardware.
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+use embedded_storage::{ReadStorage, Storage};
+
+/// Size of the sectors erased as a whole by `NorFlash::erase`, as on the ESP32-C3.
+pub const SECTOR_SIZE: usize = 0x1000;
+
+/// Error returned by `RamStorage` for accesses out of bounds or misaligned,
+/// programming bytes that were not erased, or once failing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamStorageError {
+    OutOfBounds,
+    NotAligned,
+    NotErased,
+    WriteFailed,
+}
+
+impl NorFlashError for RamStorageError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            RamStorageError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            RamStorageError::NotAligned => NorFlashErrorKind::NotAligned,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// A `Storage` backed by a `Vec`, starting erased (all `0xFF`) like flash.
+///
+/// As a `NorFlash`, it refuses to program bytes that are not erased, so that a
+/// caller relying on overwrites is caught.
+#[derive(Clone, Debug)]
+pub struct RamStorage {
+    bytes: Vec<u8>,
+    writes: usize,
+    erases: usize,
+    fail_writes: bool,
+}
+
+impl RamStorage {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bytes: vec![0xFF; capacity],
+            writes: 0,
+            erases: 0,
+            fail_writes: false,
+        }
+    }
+
+    /// Makes every write fail, simulating a worn out flash.
+    pub fn fail_writes(&mut self, fail: bool) {
+        self.fail_writes = fail;
+    }
+
+    /// Number of successful writes.
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+
+    /// Number of sectors erased with `NorFlash::erase`.
+    pub fn erases(&self) -> usize {
+        self.erases
+    }
+
+    /// Raw content, to inspect or corrupt it.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<std::ops::Range<usize>, RamStorageError> {
+        let start = offset as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.bytes.len() => Ok(start..end),
+            _ => Err(RamStorageError::OutOfBounds),
+        }
+    }
+}
+
+impl ReadStorage for RamStorage {
+    type Error = RamStorageError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len())?;
+        bytes.copy_from_slice(&self.bytes[range]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl Storage for RamStorage {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.fail_writes {
+            return Err(RamStorageError::WriteFailed);
+        }
+        let range = self.range(offset, bytes.len())?;
+        self.bytes[range].copy_from_slice(bytes);
+        self.writes += 1;
+        Ok(())
+    }
+}
+
+impl ErrorType for RamStorage {
+    type Error = RamStorageError;
+}
+
+impl ReadNorFlash for RamStorage {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadStorage::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl NorFlash for RamStorage {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to || from as usize % SECTOR_SIZE != 0 || to as usize % SECTOR_SIZE != 0 {
+            return Err(RamStorageError::NotAligned);
+        }
+        if self.fail_writes {
+            return Err(RamStorageError::WriteFailed);
+        }
+        let range = self.range(from, (to - from) as usize)?;
+        self.erases += range.len() / SECTOR_SIZE;
+        self.bytes[range].fill(0xFF);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        if offset as usize % Self::WRITE_SIZE != 0 || bytes.len() % Self::WRITE_SIZE != 0 {
+            return Err(RamStorageError::NotAligned);
+        }
+        if self.fail_writes {
+            return Err(RamStorageError::WriteFailed);
+        }
+        let range = self.range(offset, bytes.len())?;
+        if self.bytes[range.clone()].iter().any(|byte| *byte != 0xFF) {
+            return Err(RamStorageError::NotErased);
+        }
+        self.bytes[range].copy_from_slice(bytes);
+        self.writes += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+//! Append-only log of access attempts, kept in flash until the backend has it.
+//!
+//! The log is a ring buffer of fixed-size records, each with its own CRC so a
+//! record torn by a power loss is skipped instead of corrupting the log. Record
+//! `seq` always goes to slot `seq % slots`: writes sweep the whole partition
+//! round-robin, so every sector wears out at the same rate, and the newest
+//! record is found again on boot by its sequence number.
+//!
+//! Records are programmed into erased slots, and a sector is only erased when
+//! the ring enters it, dropping the oldest records it held all at once. A
+//! sector is thus erased once per lap, not once per record.
+//!
+//! Uploaded records are not erased. Instead, a checkpoint record marks how far
+//! the backend got, and `drain()` resumes from there. Records overwritten
+//! before being uploaded are lost. The backend must ignore records with a
+//! sequence number it already has, since a lost checkpoint makes the log
+//! upload them again.
+
+use std::fmt::Debug;
+
+use anyhow::{anyhow, Result};
+use embedded_storage::nor_flash::NorFlash;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+mod record;
+pub use record::{crc32, AuditEntry, AuditRecord, Decision, Reason, RECORD_LEN};
+use record::{decode, encode, Slot};
+
+/// Label of the data partition holding the audit log in `partitions.csv`.
+pub const PARTITION_LABEL: &str = "audit";
+
+/// Ring buffer of `AuditEntry`s in a region of flash.
+pub struct AuditLog<S> {
+    storage: S,
+    offset: u32,
+    slots: u32,
+    slots_per_sector: u32,
+    next_seq: u32,
+    unsent: u32,
+}
+
+impl<S> AuditLog<S>
+where
+    S: NorFlash,
+    S::Error: Debug,
+{
+    /// Opens the log kept in the `size` bytes of `storage` starting at `offset`,
+    /// which must both be whole sectors.
+    ///
+    /// Every slot is read to find the newest record and the last checkpoint.
+    pub fn new(storage: S, offset: u32, size: usize) -> Result<Self> {
+        if S::ERASE_SIZE % RECORD_LEN != 0 || RECORD_LEN % S::WRITE_SIZE != 0 {
+            return Err(anyhow!(
+                "Audit records cannot be laid out in sectors of {} bytes",
+                S::ERASE_SIZE
+            ));
+        }
+        if offset as usize % S::ERASE_SIZE != 0 || size % S::ERASE_SIZE != 0 || size == 0 {
+            return Err(anyhow!(
+                "Audit log at 0x{offset:X} of {size} bytes is not made of whole sectors"
+            ));
+        }
+
+        let mut log = Self {
+            storage,
+            offset,
+            slots: (size / RECORD_LEN) as u32,
+            slots_per_sector: (S::ERASE_SIZE / RECORD_LEN) as u32,
+            next_seq: 0,
+            unsent: 0,
+        };
+        let slots = log.slots;
+
+        let mut newest = None;
+        let mut checkpoint = None;
+        for index in 0..slots {
+            let Some((seq, slot)) = log.read_slot(index)? else {
+                continue;
+            };
+            if seq % slots != index {
+                continue;
+            }
+            newest = newest.max(Some(seq));
+            if let Slot::Checkpoint { unsent } = slot {
+                checkpoint = checkpoint.max(Some((seq, unsent)));
+            }
+        }
+        log.next_seq = newest.map_or(0, |seq| seq + 1);
+        log.unsent = checkpoint.map_or(0, |(_, unsent)| unsent);
+
+        // A write torn by a power loss leaves a slot that can no longer be
+        // programmed, skip it until the next sector is erased.
+        while log.next_seq % log.slots_per_sector != 0 && !log.is_erased(log.next_seq % slots)? {
+            log::warn!("Skipping unerased audit slot {}", log.next_seq % slots);
+            log.next_seq += 1;
+        }
+        log::info!(
+            "Opened audit log: next record {}, first unsent {}",
+            log.next_seq,
+            log.first_unsent()
+        );
+        Ok(log)
+    }
+
+    /// Gives the storage back, e.g. to open the log again.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Number of records the log holds before overwriting the oldest one.
+    pub fn capacity(&self) -> u32 {
+        self.slots
+    }
+
+    /// Sequence number of the next record.
+    pub fn next_seq(&self) -> u32 {
+        self.next_seq
+    }
+
+    /// Sequence number of the oldest record still in flash.
+    ///
+    /// The sector holding the next record was erased when the ring entered
+    /// it, so the oldest record is the first one of the following sector.
+    pub fn oldest(&self) -> u32 {
+        self.next_seq
+            .next_multiple_of(self.slots_per_sector)
+            .saturating_sub(self.slots)
+    }
+
+    /// Sequence number of the first record the backend does not have.
+    pub fn first_unsent(&self) -> u32 {
+        self.unsent.max(self.oldest())
+    }
+
+    /// Whether some records were not uploaded yet.
+    pub fn has_unsent(&self) -> bool {
+        self.first_unsent() < self.next_seq
+    }
+
+    /// Records `entry`, returning its sequence number.
+    pub fn append(&mut self, entry: AuditEntry) -> Result<u32> {
+        self.write(Slot::Access(entry))
+    }
+
+    /// Reads up to `max` records not uploaded yet, oldest first.
+    ///
+    /// Corrupted records are skipped.
+    pub fn pending(&mut self, max: usize) -> Result<Vec<AuditRecord>> {
+        let mut records = vec![];
+        for seq in self.first_unsent()..self.next_seq {
+            if records.len() == max {
+                break;
+            }
+            match self.read_slot(seq % self.slots)? {
+                Some((found, Slot::Access(entry))) if found == seq => {
+                    records.push(AuditRecord { seq, entry })
+                }
+                Some((found, Slot::Checkpoint { .. })) if found == seq => {}
+                _ => log::warn!("Skipping corrupted audit record {seq}"),
+            }
+        }
+        Ok(records)
+    }
+
+    /// Records that every record up to and including `seq` reached the backend.
+    pub fn mark_uploaded(&mut self, seq: u32) -> Result<()> {
+        let unsent = seq + 1;
+        if unsent <= self.unsent {
+            return Ok(());
+        }
+        self.unsent = unsent;
+        let checkpoint = self.write(Slot::Checkpoint { unsent })?;
+        if self.unsent == checkpoint {
+            // Nothing was logged in the meantime, the checkpoint itself need not be sent.
+            self.unsent += 1;
+        }
+        Ok(())
+    }
+
+    /// Hands the unsent records to `send` in batches of at most `batch`, until
+    /// none are left or `send` fails. Returns the number of records sent.
+    pub fn drain<F>(&mut self, batch: usize, mut send: F) -> Result<usize>
+    where
+        F: FnMut(&[AuditRecord]) -> Result<()>,
+    {
+        let mut sent = 0;
+        while self.has_unsent() {
+            let records = self.pending(batch)?;
+            let Some(last) = records.last() else {
+                // Only checkpoints and corrupted records were left.
+                self.unsent = self.next_seq;
+                break;
+            };
+            let last = last.seq;
+            send(&records)?;
+            sent += records.len();
+            self.mark_uploaded(last)?;
+        }
+        Ok(sent)
+    }
+
+    fn write(&mut self, slot: Slot) -> Result<u32> {
+        let seq = self.next_seq;
+        let index = seq % self.slots;
+        // The slot can no longer be programmed once a write was attempted,
+        // even if it failed.
+        self.next_seq += 1;
+
+        if index % self.slots_per_sector == 0 {
+            let from = self.address(index);
+            self.storage
+                .erase(from, from + S::ERASE_SIZE as u32)
+                .map_err(|e| anyhow!("Cannot erase audit sector of record {seq}: {e:?}"))?;
+        }
+        let bytes = encode(seq, &slot);
+        self.storage
+            .write(self.address(index), &bytes)
+            .map_err(|e| anyhow!("Cannot write audit record {seq}: {e:?}"))?;
+        Ok(seq)
+    }
+
+    /// Whether slot `index` is erased, and can be programmed.
+    fn is_erased(&mut self, index: u32) -> Result<bool> {
+        let mut bytes = [0u8; RECORD_LEN];
+        self.storage
+            .read(self.address(index), &mut bytes)
+            .map_err(|e| anyhow!("Cannot read audit slot {index}: {e:?}"))?;
+        Ok(bytes.iter().all(|byte| *byte == 0xFF))
+    }
+
+    /// Reads the record in slot `index`, returning `None` if it is erased or invalid.
+    fn read_slot(&mut self, index: u32) -> Result<Option<(u32, Slot)>> {
+        let mut bytes = [0u8; RECORD_LEN];
+        self.storage
+            .read(self.address(index), &mut bytes)
+            .map_err(|e| anyhow!("Cannot read audit slot {index}: {e:?}"))?;
+        Ok(decode(&bytes).ok())
+    }
+
+    fn address(&self, index: u32) -> u32 {
+        self.offset + index * RECORD_LEN as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{RamStorage, SECTOR_SIZE};
+    use super::*;
+
+    const SIZE: usize = 2 * SECTOR_SIZE;
+    const SLOTS: u32 = (SIZE / RECORD_LEN) as u32;
+    const PER_SECTOR: u32 = (SECTOR_SIZE / RECORD_LEN) as u32;
+
+    fn entry(timestamp: u64) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            unsynced: false,
+            serial: Some(42),
+            decision: Decision::Granted,
+            reason: Reason::Backend,
+        }
+    }
+
+    fn log(storage: RamStorage) -> AuditLog<RamStorage> {
+        AuditLog::new(storage, 0, SIZE).unwrap()
+    }
+
+    #[test]
+    fn records_survive_a_restart() {
+        let mut audit = log(RamStorage::new(SIZE));
+        assert_eq!(audit.append(entry(1)).unwrap(), 0);
+        assert_eq!(audit.append(entry(2)).unwrap(), 1);
+
+        let mut audit = log(audit.into_storage());
+        assert_eq!(audit.next_seq(), 2);
+        let records = audit.pending(10).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                AuditRecord {
+                    seq: 0,
+                    entry: entry(1)
+                },
+                AuditRecord {
+                    seq: 1,
+                    entry: entry(2)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_uptime_of_an_unsynchronized_clock() {
+        let uptime = AuditEntry {
+            timestamp: 12,
+            unsynced: true,
+            serial: None,
+            decision: Decision::Denied,
+            reason: Reason::UnknownCredential,
+        };
+        let mut audit = log(RamStorage::new(SIZE));
+        audit.append(uptime).unwrap();
+        audit.append(entry(1_700_000_000)).unwrap();
+
+        let records = log(audit.into_storage()).pending(10).unwrap();
+        assert_eq!(records[0].entry, uptime);
+        assert_eq!(records[1].entry, entry(1_700_000_000));
+        assert_eq!(
+            serde_json::to_value(records).unwrap(),
+            serde_json::json!([
+                {
+                    "seq": 0,
+                    "timestamp": 12,
+                    "unsynced": true,
+                    "decision": "denied",
+                    "reason": "unknown_credential",
+                },
+                {
+                    "seq": 1,
+                    "timestamp": 1_700_000_000,
+                    "serial": 42,
+                    "decision": "granted",
+                    "reason": "backend",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn drain_resumes_after_the_last_upload() {
+        let mut audit = log(RamStorage::new(SIZE));
+        for timestamp in 0..5 {
+            audit.append(entry(timestamp)).unwrap();
+        }
+        let sent = audit
+            .drain(3, |records| {
+                assert!(records.len() <= 3);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(sent, 5);
+        assert!(!audit.has_unsent());
+
+        audit.append(entry(5)).unwrap();
+        let mut audit = log(audit.into_storage());
+        let records = audit.pending(10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entry, entry(5));
+    }
+
+    #[test]
+    fn failed_upload_keeps_the_records() {
+        let mut audit = log(RamStorage::new(SIZE));
+        audit.append(entry(1)).unwrap();
+        assert!(audit.drain(10, |_| Err(anyhow!("Backend down"))).is_err());
+        assert_eq!(audit.pending(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn erases_a_sector_only_when_the_ring_enters_it() {
+        let mut audit = log(RamStorage::new(SIZE));
+        for timestamp in 0..u64::from(PER_SECTOR) {
+            audit.append(entry(timestamp)).unwrap();
+        }
+        assert_eq!(audit.storage.erases(), 1);
+        audit.append(entry(0)).unwrap();
+        assert_eq!(audit.storage.erases(), 2);
+
+        // Going round the ring drops the whole oldest sector at once.
+        for timestamp in 0..u64::from(PER_SECTOR) {
+            audit.append(entry(timestamp)).unwrap();
+        }
+        assert_eq!(audit.storage.erases(), 3);
+        assert_eq!(audit.next_seq(), SLOTS + 1);
+        assert_eq!(audit.oldest(), PER_SECTOR);
+
+        let mut audit = log(audit.into_storage());
+        assert_eq!(audit.next_seq(), SLOTS + 1);
+        let records = audit.pending(usize::MAX).unwrap();
+        assert_eq!(records.len() as u32, SLOTS + 1 - PER_SECTOR);
+        assert_eq!(records[0].seq, PER_SECTOR);
+    }
+
+    #[test]
+    fn skips_a_torn_record() {
+        let mut audit = log(RamStorage::new(SIZE));
+        audit.append(entry(1)).unwrap();
+        let mut storage = audit.into_storage();
+        // A power loss while programming slot 1.
+        storage.bytes_mut()[RECORD_LEN..RECORD_LEN + 4].fill(0);
+
+        let mut audit = log(storage);
+        assert_eq!(audit.next_seq(), 2);
+        assert_eq!(audit.append(entry(2)).unwrap(), 2);
+        let records = audit.pending(10).unwrap();
+        assert_eq!(
+            records.iter().map(|record| record.seq).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+    }
+
+    #[test]
+    fn refuses_a_region_that_is_not_whole_sectors() {
+        assert!(AuditLog::new(RamStorage::new(SIZE), 0, 0).is_err());
+        assert!(AuditLog::new(RamStorage::new(SIZE), 0, SIZE - RECORD_LEN).is_err());
+        assert!(AuditLog::new(RamStorage::new(SIZE), RECORD_LEN as u32, SECTOR_SIZE).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Size of a record on flash.
+pub const RECORD_LEN: usize = 32;
+
+/// Bytes covered by the CRC, which takes the last 4 bytes of the record.
+const CRC_OFFSET: usize = RECORD_LEN - 4;
+
+const TAG_ACCESS: u8 = 1;
+const TAG_CHECKPOINT: u8 = 2;
+
+const FLAG_SERIAL: u8 = 1;
+const FLAG_UNSYNCED: u8 = 2;
+
+/// Outcome of an access attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Granted,
+    Denied,
+}
+
+/// Why the `Decision` was taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The backend decided on the credential.
+    Backend,
+    /// The backend was unreachable, the offline allow-list and policy decided.
+    Offline,
+    /// The backend unlocked the door remotely.
+    Remote,
+    /// The YubiKey failed the challenge-response.
+    BadCredential,
+    /// The card presented is not a YubiKey.
+    UnknownCredential,
+}
+
+impl Reason {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Reason::Backend,
+            2 => Reason::Offline,
+            3 => Reason::Remote,
+            4 => Reason::BadCredential,
+            5 => Reason::UnknownCredential,
+            _ => return None,
+        })
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Reason::Backend => 1,
+            Reason::Offline => 2,
+            Reason::Remote => 3,
+            Reason::BadCredential => 4,
+            Reason::UnknownCredential => 5,
+        }
+    }
+}
+
+/// An access attempt recorded in the `AuditLog`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix time in seconds of the attempt, or seconds since boot if `unsynced`.
+    pub timestamp: u64,
+    /// The clock was not synchronized yet, so `timestamp` is only the uptime.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unsynced: bool,
+    /// Serial number of the YubiKey, if one was read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<u32>,
+    pub decision: Decision,
+    pub reason: Reason,
+}
+
+/// An `AuditEntry` with its position in the log, as uploaded to the backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Sequence number, increasing by one for every record written.
+    pub seq: u32,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+}
+
+/// What a slot of the log holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Slot {
+    Access(AuditEntry),
+    /// Every record before `unsent` reached the backend.
+    Checkpoint {
+        unsent: u32,
+    },
+}
+
+/// Encodes the record `seq` as:
+///
+/// | Bytes  | Content                                      |
+/// |--------|----------------------------------------------|
+/// | 0..4   | `seq`                                        |
+/// | 4..12  | timestamp, or unsent `seq` for checkpoints   |
+/// | 12..16 | serial                                       |
+/// | 16     | tag                                          |
+/// | 17     | decision                                     |
+/// | 18     | reason                                       |
+/// | 19     | flags: serial present, clock unsynchronized  |
+/// | 20..28 | reserved, `0xFF`                             |
+/// | 28..32 | CRC-32 of bytes 0..28                        |
+///
+/// All integers are little-endian.
+pub(super) fn encode(seq: u32, slot: &Slot) -> [u8; RECORD_LEN] {
+    let mut bytes = [0xFF; RECORD_LEN];
+    bytes[0..4].copy_from_slice(&seq.to_le_bytes());
+    match slot {
+        Slot::Access(entry) => {
+            bytes[4..12].copy_from_slice(&entry.timestamp.to_le_bytes());
+            bytes[12..16].copy_from_slice(&entry.serial.unwrap_or(0).to_le_bytes());
+            bytes[16] = TAG_ACCESS;
+            bytes[17] = match entry.decision {
+                Decision::Granted => 1,
+                Decision::Denied => 0,
+            };
+            bytes[18] = entry.reason.to_u8();
+            let mut flags = 0;
+            if entry.serial.is_some() {
+                flags |= FLAG_SERIAL;
+            }
+            if entry.unsynced {
+                flags |= FLAG_UNSYNCED;
+            }
+            bytes[19] = flags;
+        }
+        Slot::Checkpoint { unsent } => {
+            bytes[4..12].copy_from_slice(&u64::from(*unsent).to_le_bytes());
+            bytes[12..16].copy_from_slice(&0u32.to_le_bytes());
+            bytes[16] = TAG_CHECKPOINT;
+            bytes[17..20].fill(0);
+        }
+    }
+    let crc = crc32(&bytes[..CRC_OFFSET]);
+    bytes[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+    bytes
+}
+
+/// Decodes a record, failing if it is erased, torn or corrupted.
+pub(super) fn decode(bytes: &[u8; RECORD_LEN]) -> Result<(u32, Slot)> {
+    let crc = u32::from_le_bytes(bytes[CRC_OFFSET..].try_into()?);
+    if crc != crc32(&bytes[..CRC_OFFSET]) {
+        return Err(anyhow!("Audit record CRC mismatch"));
+    }
+
+    let seq = u32::from_le_bytes(bytes[0..4].try_into()?);
+    let value = u64::from_le_bytes(bytes[4..12].try_into()?);
+    let slot = match bytes[16] {
+        TAG_ACCESS => Slot::Access(AuditEntry {
+            timestamp: value,
+            unsynced: bytes[19] & FLAG_UNSYNCED != 0,
+            serial: (bytes[19] & FLAG_SERIAL != 0)
+                .then(|| u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]])),
+            decision: if bytes[17] == 1 {
+                Decision::Granted
+            } else {
+                Decision::Denied
+            },
+            reason: Reason::from_u8(bytes[18])
+                .ok_or_else(|| anyhow!("Unknown audit reason {}", bytes[18]))?,
+        }),
+        TAG_CHECKPOINT => Slot::Checkpoint {
+            unsent: value as u32,
+        },
+        tag => return Err(anyhow!("Unknown audit record tag {tag}")),
+    };
+    Ok((seq, slot))
+}
+
+/// CRC-32 (IEEE 802.3), as computed by zlib.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
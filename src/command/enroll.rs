@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::hmac::{constant_time_eq, derive_key, hex, hmac_sha256};
+
+/// Domain of the key signing enrollment requests, derived from the key shared with the backend.
+const KEY_DOMAIN: &str = "enrollment";
+
+/// Permission from the backend to enroll the next YubiKey presented to a door.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnrollmentRequest {
+    /// The only door the request is valid for.
+    pub door_id: u32,
+    /// Unix time in seconds after which the request must be refused. Also tells
+    /// requests apart, so a request is never accepted twice.
+    pub expires_at: u64,
+    /// Hex secret the YubiKey was already programmed with, such as for another
+    /// door, to accept instead of programming a new one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// An `EnrollmentRequest` as sent by the backend in an `enroll` command.
+///
+/// `request` is the JSON of the `EnrollmentRequest`, kept as a string so the
+/// signature is checked over the exact bytes the backend signed. `signature` is
+/// the hex HMAC-SHA256 of `request` with the enrollment key derived from the
+/// key the lock shares with the backend by `derive_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedEnrollment {
+    pub request: String,
+    pub signature: String,
+}
+
+impl SignedEnrollment {
+    /// Signs `request` with `key`, like the backend does.
+    pub fn sign(request: &EnrollmentRequest, key: &[u8]) -> Result<Self> {
+        let request = serde_json::to_string(request)?;
+        let signature = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            request.as_bytes(),
+        )?);
+        Ok(Self { request, signature })
+    }
+
+    /// Checks the signature with `key`, and decodes the request.
+    pub fn verify(&self, key: &[u8]) -> Result<EnrollmentRequest> {
+        let expected = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            self.request.as_bytes(),
+        )?);
+        if !constant_time_eq(expected.as_bytes(), self.signature.as_bytes()) {
+            return Err(anyhow!("Enrollment request signature mismatch"));
+        }
+        Ok(serde_json::from_str(&self.request)?)
+    }
+}
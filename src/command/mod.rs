@@ -0,0 +1,63 @@
+//! Commands pushed by the backend, instead of polling it over HTTP.
+//!
+//! Commands are JSON messages such as `{"command": "unlock"}`, wrapped in a
+//! `SignedCommand` bound to a door, and received over MQTT by
+//! `crate::mqtt::MqttLink`. A `CommandChannel` only queues them: the caller
+//! drains it with `try_recv()` from its main loop, checks each command with a
+//! `CommandVerifier`, and feeds it to the `DoorController` with
+//! `RemoteCommand::event()`. An `enroll` command carries a `SignedEnrollment`
+//! instead, which opens the `crate::ykhmac::EnrollmentMode`, and a `revoke`
+//! command removes a YubiKey from the `crate::ykhmac::Keyring`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::lock::DoorEvent;
+
+mod enroll;
+pub use enroll::{EnrollmentRequest, SignedEnrollment};
+
+mod signed;
+pub use signed::{CommandRequest, CommandVerifier, SignedCommand};
+
+#[cfg(any(test, feature = "mock"))]
+mod stub;
+#[cfg(any(test, feature = "mock"))]
+pub use stub::StubChannel;
+
+/// NVS namespace of the `CommandVerifier`.
+pub const NVS_NAMESPACE: &str = "lynx_cmd";
+
+/// A command sent by the backend to the door.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum RemoteCommand {
+    Unlock,
+    Lock,
+    /// Enroll the next YubiKey presented, such as
+    /// `{"command": "enroll", "request": "...", "signature": "..."}`.
+    Enroll(SignedEnrollment),
+    /// Revoke the YubiKey with this serial number, such as
+    /// `{"command": "revoke", "serial": 1234}`.
+    Revoke {
+        serial: u32,
+    },
+}
+
+impl RemoteCommand {
+    /// The `DoorEvent` this command triggers, if it is meant for the door controller.
+    pub fn event(&self) -> Option<DoorEvent> {
+        match self {
+            RemoteCommand::Unlock => Some(DoorEvent::RemoteUnlock),
+            RemoteCommand::Lock => Some(DoorEvent::RemoteLock),
+            RemoteCommand::Enroll(_) | RemoteCommand::Revoke { .. } => None,
+        }
+    }
+}
+
+/// A source of `SignedCommand`s pushed by the backend.
+pub trait CommandChannel {
+    /// Returns the next pending command, without blocking. It has not been
+    /// verified yet.
+    fn try_recv(&mut self) -> Result<Option<SignedCommand>>;
+}
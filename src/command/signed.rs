@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::RemoteCommand;
+use crate::config::ConfigStorage;
+use crate::hmac::{constant_time_eq, derive_key, hex, hmac_sha256};
+
+/// Domain of the key signing commands, derived from the key shared with the backend.
+const KEY_DOMAIN: &str = "command";
+
+/// Key of the last accepted nonce in the `ConfigStorage` of a `CommandVerifier`.
+const NONCE_KEY: &str = "nonce";
+
+/// A `RemoteCommand` as issued by the backend for a single door.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandRequest {
+    /// The only door the command is valid for.
+    pub door_id: u32,
+    /// Increases with every command sent to the door, so a command is never
+    /// accepted twice.
+    pub nonce: u64,
+    /// Unix time in seconds after which the command must be refused.
+    pub expires_at: u64,
+    #[serde(flatten)]
+    pub command: RemoteCommand,
+}
+
+/// A `CommandRequest` as received from the backend.
+///
+/// `command` is the JSON of the `CommandRequest`, kept as a string so the
+/// signature is checked over the exact bytes the backend signed. `signature` is
+/// the hex HMAC-SHA256 of `command` with the command key derived from the key
+/// the lock shares with the backend by `derive_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedCommand {
+    pub command: String,
+    pub signature: String,
+}
+
+impl SignedCommand {
+    /// Parses a command message, returning `None` if it is malformed.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match serde_json::from_slice(payload) {
+            Ok(command) => Some(command),
+            Err(e) => {
+                log::warn!("Ignoring invalid command message: {e}");
+                None
+            }
+        }
+    }
+
+    /// Signs `request` with `key`, like the backend does.
+    pub fn sign(request: &CommandRequest, key: &[u8]) -> Result<Self> {
+        let command = serde_json::to_string(request)?;
+        let signature = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            command.as_bytes(),
+        )?);
+        Ok(Self { command, signature })
+    }
+
+    /// Checks the signature with `key`, and decodes the request.
+    pub fn verify(&self, key: &[u8]) -> Result<CommandRequest> {
+        let expected = hex(&hmac_sha256(
+            &derive_key(key, KEY_DOMAIN)?,
+            self.command.as_bytes(),
+        )?);
+        if !constant_time_eq(expected.as_bytes(), self.signature.as_bytes()) {
+            return Err(anyhow!("Command signature mismatch"));
+        }
+        Ok(serde_json::from_str(&self.command)?)
+    }
+}
+
+/// Accepts the `SignedCommand`s of a single door, each at most once.
+///
+/// The nonce of the last accepted command is kept in a `ConfigStorage`, such
+/// as an NVS namespace, so a recorded command cannot be replayed after a
+/// restart either.
+pub struct CommandVerifier<S> {
+    storage: S,
+    key: Vec<u8>,
+    door_id: u32,
+    last_nonce: Option<u64>,
+}
+
+impl<S: ConfigStorage> CommandVerifier<S> {
+    /// Verifies the commands for `door_id` with `key`, the key shared with the
+    /// backend. Fails if `key` is empty, as anyone could sign commands then.
+    pub fn new(mut storage: S, key: &[u8], door_id: u32) -> Result<Self> {
+        if key.is_empty() {
+            return Err(anyhow!("No key to verify commands with"));
+        }
+        let last_nonce = match storage.read(NONCE_KEY)? {
+            Some(nonce) => Some(nonce.parse()?),
+            None => None,
+        };
+        Ok(Self {
+            storage,
+            key: key.to_vec(),
+            door_id,
+            last_nonce,
+        })
+    }
+
+    /// Gives the storage back.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Checks `signed` and returns its command if it is meant for this door,
+    /// newer than the last one accepted and still valid at Unix time `now`.
+    ///
+    /// `now` is `None` while the clock is not synchronized, in which case no
+    /// command can be accepted.
+    pub fn verify(&mut self, signed: &SignedCommand, now: Option<u64>) -> Result<RemoteCommand> {
+        let request = signed.verify(&self.key)?;
+        if request.door_id != self.door_id {
+            return Err(anyhow!("Command is for door {}", request.door_id));
+        }
+        let now = now.ok_or_else(|| anyhow!("Time is not synchronized"))?;
+        if now >= request.expires_at {
+            return Err(anyhow!("Command expired"));
+        }
+        if self.last_nonce.is_some_and(|last| request.nonce <= last) {
+            return Err(anyhow!("Command was already used"));
+        }
+
+        // Stored before the command is carried out, so it is never carried out twice.
+        self.storage.write(NONCE_KEY, &request.nonce.to_string())?;
+        self.last_nonce = Some(request.nonce);
+        Ok(request.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::mock::MemoryStorage;
+
+    const KEY: &[u8] = b"secret";
+
+    fn unlock(door_id: u32, nonce: u64) -> SignedCommand {
+        let request = CommandRequest {
+            door_id,
+            nonce,
+            expires_at: 1_000,
+            command: RemoteCommand::Unlock,
+        };
+        SignedCommand::sign(&request, KEY).unwrap()
+    }
+
+    fn verifier() -> CommandVerifier<MemoryStorage> {
+        CommandVerifier::new(MemoryStorage::new(), KEY, 1).unwrap()
+    }
+
+    #[test]
+    fn accepts_a_signed_command() {
+        let mut verifier = verifier();
+        let command = verifier.verify(&unlock(1, 1), Some(999)).unwrap();
+        assert_eq!(command, RemoteCommand::Unlock);
+    }
+
+    #[test]
+    fn parses_the_message_of_the_backend() {
+        let message = serde_json::to_vec(&unlock(1, 1)).unwrap();
+        let signed = SignedCommand::parse(&message).unwrap();
+        assert_eq!(
+            signed.verify(KEY).unwrap(),
+            CommandRequest {
+                door_id: 1,
+                nonce: 1,
+                expires_at: 1_000,
+                command: RemoteCommand::Unlock,
+            }
+        );
+        assert!(SignedCommand::parse(br#"{"command": "unlock"}"#).is_none());
+    }
+
+    #[test]
+    fn refuses_a_forged_command() {
+        let mut signed = unlock(1, 1);
+        signed.command = signed.command.replace("unlock", "lock");
+        assert!(verifier().verify(&signed, Some(0)).is_err());
+
+        let other_key = CommandRequest {
+            door_id: 1,
+            nonce: 1,
+            expires_at: 1_000,
+            command: RemoteCommand::Unlock,
+        };
+        let signed = SignedCommand::sign(&other_key, b"other").unwrap();
+        assert!(verifier().verify(&signed, Some(0)).is_err());
+    }
+
+    #[test]
+    fn refuses_a_command_signed_with_the_key_of_another_domain() {
+        let signed = unlock(1, 1);
+        for key in [
+            KEY.to_vec(),
+            derive_key(KEY, "allow-list").unwrap().to_vec(),
+        ] {
+            let forged = SignedCommand {
+                signature: hex(&hmac_sha256(&key, signed.command.as_bytes()).unwrap()),
+                ..signed.clone()
+            };
+            assert!(verifier().verify(&forged, Some(0)).is_err());
+        }
+    }
+
+    #[test]
+    fn refuses_a_command_for_another_door() {
+        assert!(verifier().verify(&unlock(2, 1), Some(0)).is_err());
+    }
+
+    #[test]
+    fn refuses_an_expired_command_or_an_unsynchronized_clock() {
+        assert!(verifier().verify(&unlock(1, 1), Some(1_000)).is_err());
+        assert!(verifier().verify(&unlock(1, 1), None).is_err());
+    }
+
+    #[test]
+    fn refuses_a_replayed_command_after_a_restart() {
+        let mut verifier = verifier();
+        verifier.verify(&unlock(1, 5), Some(0)).unwrap();
+        assert!(verifier.verify(&unlock(1, 5), Some(0)).is_err());
+
+        let storage = verifier.into_storage();
+        assert_eq!(storage.get(NONCE_KEY), Some("5"));
+        let mut verifier = CommandVerifier::new(storage, KEY, 1).unwrap();
+        assert!(verifier.verify(&unlock(1, 5), Some(0)).is_err());
+        assert!(verifier.verify(&unlock(1, 4), Some(0)).is_err());
+        verifier.verify(&unlock(1, 6), Some(0)).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_command_it_cannot_remember() {
+        let mut storage = MemoryStorage::new();
+        storage.fail_writes(true);
+        let mut verifier = CommandVerifier::new(storage, KEY, 1).unwrap();
+        assert!(verifier.verify(&unlock(1, 1), Some(0)).is_err());
+    }
+
+    #[test]
+    fn refuses_an_empty_key() {
+        assert!(CommandVerifier::new(MemoryStorage::new(), b"", 1).is_err());
+    }
+}
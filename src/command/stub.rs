@@ -0,0 +1,117 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use super::{CommandChannel, SignedCommand};
+
+/// An in-memory `CommandChannel`, for exercising command handling on the host.
+///
+/// Clones share the same queue, so a clone can be kept around to push commands
+/// after handing the original to the code under test.
+#[derive(Clone, Default)]
+pub struct StubChannel {
+    queue: Rc<RefCell<VecDeque<SignedCommand>>>,
+}
+
+impl StubChannel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a command.
+    pub fn push(&self, command: SignedCommand) {
+        self.queue.borrow_mut().push_back(command);
+    }
+
+    /// Queue a raw command message, as it would be received from the backend.
+    ///
+    /// Malformed messages are dropped, like the other channels do.
+    pub fn push_payload(&self, payload: &[u8]) {
+        if let Some(command) = SignedCommand::parse(payload) {
+            self.push(command);
+        }
+    }
+
+    /// Number of commands not received yet.
+    pub fn pending(&self) -> usize {
+        self.queue.borrow().len()
+    }
+}
+
+impl CommandChannel for StubChannel {
+    fn try_recv(&mut self) -> Result<Option<SignedCommand>> {
+        Ok(self.queue.borrow_mut().pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::command::{CommandRequest, CommandVerifier, RemoteCommand};
+    use crate::config::mock::MemoryStorage;
+    use crate::lock::mock::MockClock;
+    use crate::lock::{DoorCommand, DoorController, DoorEvent, DoorState};
+
+    const KEY: &[u8] = b"secret";
+
+    fn payload(nonce: u64, command: RemoteCommand) -> Vec<u8> {
+        let request = CommandRequest {
+            door_id: 1,
+            nonce,
+            expires_at: 1_000,
+            command,
+        };
+        serde_json::to_vec(&SignedCommand::sign(&request, KEY).unwrap()).unwrap()
+    }
+
+    /// Drains `channel` the way the main loop does, returning the movements
+    /// the door asked for.
+    fn deliver(
+        channel: &mut StubChannel,
+        verifier: &mut CommandVerifier<MemoryStorage>,
+        door: &mut DoorController<MockClock>,
+    ) -> Vec<DoorCommand> {
+        let mut movements = vec![];
+        while let Some(signed) = channel.try_recv().unwrap() {
+            let Ok(command) = verifier.verify(&signed, Some(0)) else {
+                continue;
+            };
+            if let Some(movement) = command.event().and_then(|event| door.handle(event)) {
+                movements.push(movement);
+            }
+        }
+        movements
+    }
+
+    #[test]
+    fn delivers_remote_commands_to_the_door() {
+        let channel = StubChannel::new();
+        let mut receiver = channel.clone();
+        let mut verifier = CommandVerifier::new(MemoryStorage::new(), KEY, 1).unwrap();
+        let mut door = DoorController::new(MockClock::new(), Duration::from_secs(7));
+
+        channel.push_payload(&payload(1, RemoteCommand::Unlock));
+        channel.push_payload(b"not a command");
+        assert_eq!(channel.pending(), 1);
+        assert_eq!(
+            deliver(&mut receiver, &mut verifier, &mut door),
+            [DoorCommand::Unlock]
+        );
+        assert_eq!(door.state(), DoorState::Unlocking);
+        door.handle(DoorEvent::MotionComplete);
+
+        // A replayed unlock is refused, the lock goes through.
+        channel.push_payload(&payload(1, RemoteCommand::Unlock));
+        channel.push_payload(&payload(2, RemoteCommand::Lock));
+        assert_eq!(
+            deliver(&mut receiver, &mut verifier, &mut door),
+            [DoorCommand::Lock]
+        );
+        assert_eq!(door.state(), DoorState::Relocking);
+        assert_eq!(channel.pending(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+
+use super::{DeviceConfig, SCHEMA_VERSION};
+
+/// Brings a stored configuration of schema `version` up to `SCHEMA_VERSION`,
+/// one version at a time.
+pub(super) fn migrate(mut value: Value, version: u32, defaults: &DeviceConfig) -> Result<Value> {
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "Configuration schema v{version} is newer than the supported v{SCHEMA_VERSION}"
+        ));
+    }
+    for from in version..SCHEMA_VERSION {
+        log::info!("Migrating configuration from v{from} to v{}", from + 1);
+        value = match from {
+            1 => v1_to_v2(value, defaults)?,
+            _ => return Err(anyhow!("No migration from configuration v{from}")),
+        };
+    }
+    Ok(value)
+}
+
+/// v1 only held the credentials entered in the provisioning portal:
+/// `{"version", "ssid", "password", "backend_url", "door_id"}`.
+///
+/// The provisioned network goes first, before the ones from `cfg.toml`, and
+/// the backend URL stays the provisioned one.
+fn v1_to_v2(value: Value, defaults: &DeviceConfig) -> Result<Value> {
+    let ssid = value["ssid"]
+        .as_str()
+        .ok_or_else(|| anyhow!("v1 configuration has no SSID"))?;
+    let password = value["password"].as_str().unwrap_or_default();
+
+    let mut networks = vec![json!({ "ssid": ssid, "password": password })];
+    for network in defaults.networks.iter().filter(|n| n.ssid != ssid) {
+        networks.push(serde_json::to_value(network)?);
+    }
+
+    let mut migrated = json!({ "version": 2, "networks": networks, "provisioned": true });
+    for key in ["backend_url", "door_id"] {
+        if let Some(field) = value.get(key) {
+            migrated[key] = field.clone();
+        }
+    }
+    Ok(migrated)
+}
+
+/// Overwrites the fields of `base` with the ones set in `overlay`, recursively,
+/// so fields added without a schema change keep their default.
+pub(super) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Keeps the fields of `value` that differ from `base`, recursively, or `None`
+/// if none does: the reverse of `merge()`.
+pub(super) fn diff(base: &Value, value: Value) -> Option<Value> {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            let changed: Map<String, Value> = value
+                .into_iter()
+                .filter_map(|(key, field)| match base.get(&key) {
+                    Some(default) => diff(default, field).map(|field| (key, field)),
+                    None => Some((key, field)),
+                })
+                .collect();
+            (!changed.is_empty()).then_some(Value::Object(changed))
+        }
+        (base, value) => (*base != value).then_some(value),
+    }
+}
@@ -0,0 +1,54 @@
+//! Host-side stand-in for NVS, to exercise the `ConfigStore` without hardware.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use super::ConfigStorage;
+
+/// A `ConfigStorage` keeping every key in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    values: HashMap<String, String>,
+    fail_writes: bool,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes every write fail, simulating a full NVS partition.
+    pub fn fail_writes(&mut self, fail: bool) {
+        self.fail_writes = fail;
+    }
+
+    /// Get the raw value of `key`, to inspect what was stored.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Set the raw value of `key`, e.g. to store a configuration of an older schema.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+}
+
+impl ConfigStorage for MemoryStorage {
+    fn read(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, value: &str) -> Result<()> {
+        if self.fail_writes {
+            bail!("Mock storage is full");
+        }
+        self.set(key, value);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.values.remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! Settings of the lock, stored at runtime instead of compiled in.
+//!
+//! A `DeviceConfig` is kept as JSON by a `ConfigStore`, on top of any
+//! `ConfigStorage` (`NvsStorage` on the device). Its defaults come from
+//! `cfg.toml`. Every stored configuration carries its schema version, and is
+//! migrated to `SCHEMA_VERSION` when loaded. Only the fields that differ from
+//! the defaults are stored, and fields missing from a stored configuration
+//! take their default, so adding a field needs no migration and the secrets
+//! of `cfg.toml` never end up in NVS.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::access::OfflinePolicy;
+use crate::api::DEFAULT_BASE_URL;
+use crate::lock::ServoConfig;
+use crate::provision::Credentials;
+use crate::wifi::{self, Network};
+
+mod migrate;
+use migrate::{diff, merge, migrate};
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+#[cfg(target_os = "espidf")]
+mod nvs;
+#[cfg(target_os = "espidf")]
+pub use nvs::NvsStorage;
+
+/// Version of the `DeviceConfig` schema written by this firmware.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// NVS namespace of the configuration.
+pub const NVS_NAMESPACE: &str = "config";
+
+/// Key of the configuration.
+const CONFIG_KEY: &str = "config";
+
+#[toml_cfg::toml_config]
+pub(crate) struct Config {
+    #[default(1)]
+    door_id: u32,
+    #[default("")]
+    backend_url: &'static str,
+    #[default("mqtts://app.lynx-locks.com")]
+    mqtt_url: &'static str,
+    #[default(7000)]
+    hold_open_ms: u32,
+    #[default("fail-secure")]
+    offline_policy: &'static str,
+}
+
+/// Every setting of the lock that can change without a rebuild.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    /// Schema version, see `SCHEMA_VERSION`.
+    pub version: u32,
+    /// Wi-Fi networks, in priority order. Only the ones that are not defaults
+    /// are stored, and the defaults they do not replace follow them.
+    pub networks: Vec<Network>,
+    pub backend_url: String,
+    pub door_id: u32,
+    pub mqtt_url: String,
+    /// Time in milliseconds the door stays unlocked after an unlock.
+    pub hold_open_ms: u32,
+    pub offline_policy: OfflinePolicy,
+    pub servo: ServoConfig,
+    /// Whether the provisioning portal set `backend_url`, which it can then no
+    /// longer change.
+    #[serde(default)]
+    pub provisioned: bool,
+}
+
+impl Default for DeviceConfig {
+    /// The settings from `cfg.toml`.
+    fn default() -> Self {
+        let offline_policy = CONFIG.offline_policy.parse().unwrap_or_else(|e| {
+            log::warn!("{e}, failing secure");
+            OfflinePolicy::default()
+        });
+        let backend_url = if CONFIG.backend_url.is_empty() {
+            DEFAULT_BASE_URL
+        } else {
+            CONFIG.backend_url
+        };
+
+        Self {
+            version: SCHEMA_VERSION,
+            networks: wifi::networks(),
+            backend_url: backend_url.to_string(),
+            door_id: CONFIG.door_id,
+            mqtt_url: CONFIG.mqtt_url.to_string(),
+            hold_open_ms: CONFIG.hold_open_ms,
+            offline_policy,
+            servo: ServoConfig::default(),
+            provisioned: false,
+        }
+    }
+}
+
+impl DeviceConfig {
+    /// Applies the credentials entered in the provisioning portal. Their network
+    /// goes first, so it is preferred over the ones from `cfg.toml`.
+    ///
+    /// Fails if the lock was already provisioned for another backend: anyone
+    /// near the portal could otherwise hand the lock over to their own.
+    pub fn provision(&mut self, credentials: &Credentials) -> Result<()> {
+        if self.provisioned && credentials.backend_url != self.backend_url {
+            return Err(anyhow!(
+                "The backend URL cannot be changed once provisioned"
+            ));
+        }
+        self.networks
+            .retain(|network| network.ssid != credentials.ssid);
+        self.networks
+            .insert(0, Network::new(&credentials.ssid, &credentials.password));
+        self.backend_url = credentials.backend_url.clone();
+        self.door_id = credentials.door_id;
+        self.provisioned = true;
+        Ok(())
+    }
+}
+
+/// Something that can keep strings by key, such as an NVS namespace.
+pub trait ConfigStorage {
+    /// Returns the value of `key`, or `None` if it was never written.
+    fn read(&mut self, key: &str) -> Result<Option<String>>;
+
+    fn write(&mut self, key: &str, value: &str) -> Result<()>;
+
+    /// Removes `key`. Removing a missing key is not an error.
+    fn remove(&mut self, key: &str) -> Result<()>;
+}
+
+/// Loads and saves the `DeviceConfig`, migrating older schemas.
+pub struct ConfigStore<S> {
+    storage: S,
+    defaults: DeviceConfig,
+}
+
+impl<S: ConfigStorage> ConfigStore<S> {
+    /// Constructs a new `ConfigStore` filling missing settings from `defaults`.
+    pub fn new(storage: S, defaults: DeviceConfig) -> Self {
+        Self { storage, defaults }
+    }
+
+    /// Get the settings used when none are stored.
+    pub fn defaults(&self) -> &DeviceConfig {
+        &self.defaults
+    }
+
+    /// Gives the storage back.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Reads the stored configuration, or the defaults if none is stored.
+    ///
+    /// A configuration of an older schema is migrated and saved back. Fails if
+    /// the stored configuration cannot be decoded, or is from a newer firmware.
+    pub fn load(&mut self) -> Result<DeviceConfig> {
+        let Some(json) = self.storage.read(CONFIG_KEY)? else {
+            return Ok(self.defaults.clone());
+        };
+        let value: serde_json::Value = serde_json::from_str(&json)?;
+        let version = value["version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Stored configuration has no version"))?;
+        let version = u32::try_from(version)?;
+
+        let mut config = serde_json::to_value(&self.defaults)?;
+        merge(&mut config, migrate(value, version, &self.defaults)?);
+        let mut config: DeviceConfig = serde_json::from_value(config)?;
+        for network in &self.defaults.networks {
+            if !config.networks.iter().any(|n| n.ssid == network.ssid) {
+                config.networks.push(network.clone());
+            }
+        }
+
+        if version != SCHEMA_VERSION {
+            self.save(&config)?;
+        }
+        Ok(config)
+    }
+
+    /// Stores the settings of `config` that differ from the defaults, with the
+    /// current schema version.
+    pub fn save(&mut self, config: &DeviceConfig) -> Result<()> {
+        let defaults = DeviceConfig {
+            networks: vec![],
+            ..self.defaults.clone()
+        };
+        let config = DeviceConfig {
+            version: SCHEMA_VERSION,
+            networks: config
+                .networks
+                .iter()
+                .filter(|network| !self.defaults.networks.contains(network))
+                .cloned()
+                .collect(),
+            ..config.clone()
+        };
+        let mut stored = diff(
+            &serde_json::to_value(&defaults)?,
+            serde_json::to_value(&config)?,
+        )
+        .unwrap_or_else(|| json!({}));
+        stored["version"] = json!(SCHEMA_VERSION);
+        self.storage
+            .write(CONFIG_KEY, &serde_json::to_string(&stored)?)
+    }
+
+    /// Forgets the stored configuration, going back to the defaults.
+    pub fn reset(&mut self) -> Result<()> {
+        self.storage.remove(CONFIG_KEY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MemoryStorage;
+    use super::*;
+
+    fn defaults() -> DeviceConfig {
+        DeviceConfig {
+            networks: vec![Network::new("office", "office-secret")],
+            backend_url: "https://lynx.example.com/api/".to_string(),
+            door_id: 1,
+            ..DeviceConfig::default()
+        }
+    }
+
+    fn store(storage: MemoryStorage) -> ConfigStore<MemoryStorage> {
+        ConfigStore::new(storage, defaults())
+    }
+
+    fn credentials(backend_url: &str) -> Credentials {
+        Credentials {
+            ssid: "site".to_string(),
+            password: "password".to_string(),
+            backend_url: backend_url.to_string(),
+            door_id: 7,
+        }
+    }
+
+    #[test]
+    fn provisioning_puts_the_network_first() {
+        let mut config = DeviceConfig {
+            networks: vec![Network::new("office", ""), Network::new("site", "old")],
+            ..DeviceConfig::default()
+        };
+        config
+            .provision(&credentials("https://lynx.example.com/api/"))
+            .unwrap();
+        assert_eq!(
+            config.networks,
+            vec![Network::new("site", "password"), Network::new("office", "")]
+        );
+        assert_eq!(config.backend_url, "https://lynx.example.com/api/");
+        assert_eq!(config.door_id, 7);
+        assert!(config.provisioned);
+    }
+
+    #[test]
+    fn provisioning_keeps_the_backend_once_provisioned() {
+        let mut config = DeviceConfig::default();
+        config
+            .provision(&credentials("https://lynx.example.com/api/"))
+            .unwrap();
+        assert!(config
+            .provision(&credentials("https://attacker.example.com/"))
+            .is_err());
+        assert_eq!(config.backend_url, "https://lynx.example.com/api/");
+
+        config
+            .provision(&credentials("https://lynx.example.com/api/"))
+            .unwrap();
+    }
+
+    #[test]
+    fn loads_the_defaults_until_something_is_saved() {
+        let mut store = store(MemoryStorage::new());
+        assert_eq!(store.load().unwrap(), defaults());
+    }
+
+    #[test]
+    fn saves_only_what_differs_from_the_defaults() {
+        let mut store = store(MemoryStorage::new());
+        let mut config = defaults();
+        config
+            .provision(&credentials("https://lynx.example.com/api/"))
+            .unwrap();
+        config.hold_open_ms = 3000;
+        store.save(&config).unwrap();
+
+        let stored: serde_json::Value =
+            serde_json::from_str(store.into_storage().get(CONFIG_KEY).unwrap()).unwrap();
+        assert_eq!(
+            stored,
+            json!({
+                "version": SCHEMA_VERSION,
+                "networks": [{ "ssid": "site", "password": "password" }],
+                "door_id": 7,
+                "hold_open_ms": 3000,
+                "provisioned": true,
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips_a_saved_configuration() {
+        let mut store = store(MemoryStorage::new());
+        let mut config = defaults();
+        config
+            .provision(&credentials("https://lynx.example.com/api/"))
+            .unwrap();
+        config.servo.unlocked_angle = 10;
+        store.save(&config).unwrap();
+
+        let mut store = self::store(store.into_storage());
+        assert_eq!(store.load().unwrap(), config);
+    }
+
+    #[test]
+    fn unchanged_settings_follow_the_defaults() {
+        let mut store = store(MemoryStorage::new());
+        let config = DeviceConfig {
+            door_id: 7,
+            ..defaults()
+        };
+        store.save(&config).unwrap();
+
+        let new_defaults = DeviceConfig {
+            hold_open_ms: 5000,
+            networks: vec![Network::new("office", "rotated")],
+            ..defaults()
+        };
+        let mut store = ConfigStore::new(store.into_storage(), new_defaults);
+        let config = store.load().unwrap();
+        assert_eq!(config.door_id, 7);
+        assert_eq!(config.hold_open_ms, 5000);
+        assert_eq!(config.networks, vec![Network::new("office", "rotated")]);
+    }
+
+    #[test]
+    fn migrates_a_v1_configuration() {
+        let mut storage = MemoryStorage::new();
+        storage.set(
+            CONFIG_KEY,
+            r#"{"version":1,"ssid":"site","password":"password","backend_url":"https://lynx.example.com/v1/","door_id":5}"#,
+        );
+        let mut store = store(storage);
+        let config = store.load().unwrap();
+        assert_eq!(
+            config,
+            DeviceConfig {
+                networks: vec![
+                    Network::new("site", "password"),
+                    Network::new("office", "office-secret"),
+                ],
+                backend_url: "https://lynx.example.com/v1/".to_string(),
+                door_id: 5,
+                provisioned: true,
+                ..defaults()
+            }
+        );
+
+        // Saved back with the current schema, and without the defaults.
+        let stored = store.into_storage();
+        let stored: serde_json::Value =
+            serde_json::from_str(stored.get(CONFIG_KEY).unwrap()).unwrap();
+        assert_eq!(stored["version"], SCHEMA_VERSION);
+        assert!(!stored.to_string().contains("office-secret"));
+    }
+
+    #[test]
+    fn refuses_a_configuration_it_cannot_read() {
+        let mut storage = MemoryStorage::new();
+        storage.set(
+            CONFIG_KEY,
+            &json!({ "version": SCHEMA_VERSION + 1 }).to_string(),
+        );
+        assert!(store(storage).load().is_err());
+
+        let mut storage = MemoryStorage::new();
+        storage.set(CONFIG_KEY, r#"{"door_id":5}"#);
+        assert!(store(storage).load().is_err());
+    }
+
+    #[test]
+    fn reset_goes_back_to_the_defaults() {
+        let mut store = store(MemoryStorage::new());
+        store
+            .save(&DeviceConfig {
+                door_id: 7,
+                ..defaults()
+            })
+            .unwrap();
+        store.reset().unwrap();
+        assert_eq!(store.load().unwrap(), defaults());
+    }
+}
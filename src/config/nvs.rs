@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+use super::ConfigStorage;
+
+/// `ConfigStorage` backed by a namespace of the default NVS partition.
+pub struct NvsStorage {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl NvsStorage {
+    pub fn new(partition: EspDefaultNvsPartition, namespace: &str) -> Result<Self> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, namespace, true)?,
+        })
+    }
+}
+
+impl ConfigStorage for NvsStorage {
+    fn read(&mut self, key: &str) -> Result<Option<String>> {
+        let Some(len) = self.nvs.str_len(key)? else {
+            return Ok(None);
+        };
+        // Room for the NUL terminator.
+        let mut buf = vec![0u8; len + 1];
+        Ok(self.nvs.get_str(key, &mut buf)?.map(str::to_string))
+    }
+
+    fn write(&mut self, key: &str, value: &str) -> Result<()> {
+        self.nvs.set_str(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.nvs.remove(key)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Sha256;
+
+/// Length of a HMAC-SHA1 digest.
+pub const SHA1_LEN: usize = 20;
+
+/// Length of a HMAC-SHA256 digest.
+pub const SHA256_LEN: usize = 32;
+
+/// Computes the HMAC-SHA1 of `data`.
+pub fn hmac_sha1(key: &[u8], data: &[u8]) -> Result<[u8; SHA1_LEN]> {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(key).map_err(|e| anyhow!("Invalid HMAC key: {e}"))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// Computes the HMAC-SHA256 of `data`.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<[u8; SHA256_LEN]> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).map_err(|e| anyhow!("Invalid HMAC key: {e}"))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().into())
+}
+
+/// Derives the key signing the messages of `domain`, such as `"command"`,
+/// from `key`, the key shared with the backend: the HMAC-SHA256 of
+/// `lynx-key:<domain>` with `key`. A signature made for one domain is then
+/// never valid in another.
+pub fn derive_key(key: &[u8], domain: &str) -> Result<[u8; SHA256_LEN]> {
+    hmac_sha256(key, format!("lynx-key:{domain}").as_bytes())
+}
+
+/// Lowercase hex encoding of `bytes`, as signatures are exchanged with the backend.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Compares without leaking the position of the first difference through timing.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -1,15 +1,42 @@
 pub mod wifi;
 
+pub mod provision;
+
+pub mod config;
+
+pub mod hmac;
+
+pub mod api;
+
+pub mod access;
+
+#[cfg(target_os = "espidf")]
+pub mod partition;
+
+pub mod audit;
+
 pub mod reqwesp;
 use reqwesp::*;
 
+#[cfg(target_os = "espidf")]
 mod led_strip;
+#[cfg(target_os = "espidf")]
 pub use led_strip::{EspError as LedError, Led};
 
+#[cfg(target_os = "espidf")]
 mod pn532;
+#[cfg(target_os = "espidf")]
 pub use pn532::{Pn532, Pn532Error};
 
+#[cfg(target_os = "espidf")]
 mod led;
+#[cfg(target_os = "espidf")]
 pub use led::Led as ExternalLed;
 
 pub mod ykhmac;
+
+pub mod lock;
+
+pub mod command;
+
+pub mod mqtt;
@@ -0,0 +1,293 @@
+use std::time::{Duration, Instant};
+
+/// Source of monotonic time for the `DoorController`.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed starting point.
+    fn now(&self) -> Duration;
+}
+
+/// `Clock` backed by `std::time::Instant`.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorState {
+    /// The latch is locked and waiting for a credential.
+    Locked,
+    /// An unlock command was issued and the latch is moving.
+    Unlocking,
+    /// The latch is unlocked until the hold-open timer expires.
+    Unlocked,
+    /// A lock command was issued and the latch is moving.
+    Relocking,
+    /// The latch failed to move. Only a button press will try to lock it again.
+    Fault,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorEvent {
+    /// A credential was presented and authorized.
+    CredentialAccepted,
+    /// The backend requested the door to be unlocked.
+    RemoteUnlock,
+    /// The backend requested the door to be locked.
+    RemoteLock,
+    /// The hold-open timer expired.
+    TimerExpired,
+    /// The request-to-exit button was pressed.
+    ButtonPressed,
+    /// The latch finished the last requested movement.
+    MotionComplete,
+    /// The latch failed to finish the last requested movement.
+    MotionFailed,
+}
+
+/// Movements the caller must perform on behalf of the `DoorController`.
+///
+/// Once the movement is done, report it back with `DoorEvent::MotionComplete`
+/// or `DoorEvent::MotionFailed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorCommand {
+    Unlock,
+    Lock,
+}
+
+/// Non-blocking door state machine.
+///
+/// The controller never touches hardware. It is fed `DoorEvent`s, and returns
+/// the `DoorCommand` the caller should carry out, if any. `poll()` must be
+/// called regularly so the hold-open timer can expire.
+pub struct DoorController<C: Clock> {
+    clock: C,
+    state: DoorState,
+    hold_open: Duration,
+    deadline: Option<Duration>,
+}
+
+impl<C: Clock> DoorController<C> {
+    /// Creates a new `DoorController` in the `Locked` state.
+    pub fn new(clock: C, hold_open: Duration) -> Self {
+        Self {
+            clock,
+            state: DoorState::Locked,
+            hold_open,
+            deadline: None,
+        }
+    }
+
+    /// Get the current state.
+    pub fn state(&self) -> DoorState {
+        self.state
+    }
+
+    /// Get the time the door is held open after an unlock.
+    pub fn hold_open(&self) -> Duration {
+        self.hold_open
+    }
+
+    /// Set the time the door is held open after an unlock.
+    ///
+    /// Takes effect on the next unlock or extension.
+    pub fn set_hold_open(&mut self, hold_open: Duration) {
+        self.hold_open = hold_open;
+    }
+
+    /// Time left before the door relocks, if it is currently unlocked.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_sub(self.clock.now()))
+    }
+
+    /// Checks the hold-open timer, firing `DoorEvent::TimerExpired` if it has run out.
+    pub fn poll(&mut self) -> Option<DoorCommand> {
+        match self.deadline {
+            Some(deadline) if self.clock.now() >= deadline => self.handle(DoorEvent::TimerExpired),
+            _ => None,
+        }
+    }
+
+    /// Advances the state machine with `event`.
+    pub fn handle(&mut self, event: DoorEvent) -> Option<DoorCommand> {
+        use DoorEvent::*;
+        use DoorState::*;
+
+        let (state, command) = match (self.state, event) {
+            (Locked, CredentialAccepted | RemoteUnlock | ButtonPressed) => {
+                (Unlocking, Some(DoorCommand::Unlock))
+            }
+            (Unlocking, MotionComplete) => {
+                self.restart_timer();
+                (Unlocked, None)
+            }
+            (Unlocked, CredentialAccepted | RemoteUnlock | ButtonPressed) => {
+                // Another unlock request extends the unlock window.
+                self.restart_timer();
+                (Unlocked, None)
+            }
+            (Unlocked, TimerExpired) => {
+                self.deadline = None;
+                (Relocking, Some(DoorCommand::Lock))
+            }
+            (Unlocking | Unlocked, RemoteLock) => {
+                self.deadline = None;
+                (Relocking, Some(DoorCommand::Lock))
+            }
+            (Relocking, MotionComplete) => (Locked, None),
+            (Relocking, CredentialAccepted | RemoteUnlock | ButtonPressed) => {
+                (Unlocking, Some(DoorCommand::Unlock))
+            }
+            (Unlocking | Relocking, MotionFailed) => {
+                self.deadline = None;
+                (Fault, None)
+            }
+            (Fault, ButtonPressed) => (Relocking, Some(DoorCommand::Lock)),
+            (state, event) => {
+                log::debug!("Ignoring {event:?} in {state:?} state");
+                (state, None)
+            }
+        };
+
+        if state != self.state {
+            log::info!("Door state: {:?} -> {state:?}", self.state);
+            self.state = state;
+        }
+        command
+    }
+
+    fn restart_timer(&mut self) {
+        self.deadline = Some(self.clock.now() + self.hold_open);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::lock::mock::MockClock;
+
+    const HOLD_OPEN: Duration = Duration::from_secs(7);
+
+    fn unlocked(clock: &MockClock) -> DoorController<MockClock> {
+        let mut door = DoorController::new(clock.clone(), HOLD_OPEN);
+        assert_eq!(
+            door.handle(DoorEvent::CredentialAccepted),
+            Some(DoorCommand::Unlock)
+        );
+        assert_eq!(door.state(), DoorState::Unlocking);
+        assert_eq!(door.handle(DoorEvent::MotionComplete), None);
+        assert_eq!(door.state(), DoorState::Unlocked);
+        door
+    }
+
+    #[test]
+    fn relocks_once_the_hold_open_time_runs_out() {
+        let clock = MockClock::new();
+        let mut door = unlocked(&clock);
+
+        clock.advance(HOLD_OPEN - Duration::from_millis(1));
+        assert_eq!(door.poll(), None);
+        assert_eq!(door.remaining(), Some(Duration::from_millis(1)));
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(door.poll(), Some(DoorCommand::Lock));
+        assert_eq!(door.state(), DoorState::Relocking);
+        assert_eq!(door.remaining(), None);
+
+        assert_eq!(door.handle(DoorEvent::MotionComplete), None);
+        assert_eq!(door.state(), DoorState::Locked);
+        assert_eq!(door.poll(), None);
+    }
+
+    #[test]
+    fn second_credential_extends_the_unlock_window() {
+        let clock = MockClock::new();
+        let mut door = unlocked(&clock);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(door.handle(DoorEvent::CredentialAccepted), None);
+        assert_eq!(door.remaining(), Some(HOLD_OPEN));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(door.poll(), None);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(door.poll(), Some(DoorCommand::Lock));
+    }
+
+    #[test]
+    fn remote_lock_relocks_immediately() {
+        let clock = MockClock::new();
+        let mut door = unlocked(&clock);
+
+        assert_eq!(door.handle(DoorEvent::RemoteLock), Some(DoorCommand::Lock));
+        assert_eq!(door.state(), DoorState::Relocking);
+    }
+
+    #[test]
+    fn unlock_while_relocking_reopens_the_door() {
+        let clock = MockClock::new();
+        let mut door = unlocked(&clock);
+        clock.advance(HOLD_OPEN);
+        door.poll();
+
+        assert_eq!(
+            door.handle(DoorEvent::RemoteUnlock),
+            Some(DoorCommand::Unlock)
+        );
+        assert_eq!(door.state(), DoorState::Unlocking);
+    }
+
+    #[test]
+    fn failed_motion_waits_for_the_button() {
+        let clock = MockClock::new();
+        let mut door = DoorController::new(clock.clone(), HOLD_OPEN);
+        door.handle(DoorEvent::RemoteUnlock);
+        assert_eq!(door.handle(DoorEvent::MotionFailed), None);
+        assert_eq!(door.state(), DoorState::Fault);
+
+        assert_eq!(door.handle(DoorEvent::CredentialAccepted), None);
+        clock.advance(HOLD_OPEN);
+        assert_eq!(door.poll(), None);
+        assert_eq!(door.state(), DoorState::Fault);
+
+        assert_eq!(
+            door.handle(DoorEvent::ButtonPressed),
+            Some(DoorCommand::Lock)
+        );
+        assert_eq!(door.handle(DoorEvent::MotionComplete), None);
+        assert_eq!(door.state(), DoorState::Locked);
+    }
+
+    #[test]
+    fn new_hold_open_time_applies_to_the_next_unlock() {
+        let clock = MockClock::new();
+        let mut door = unlocked(&clock);
+        door.set_hold_open(Duration::from_secs(2));
+        assert_eq!(door.remaining(), Some(HOLD_OPEN));
+
+        door.handle(DoorEvent::ButtonPressed);
+        assert_eq!(door.remaining(), Some(Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use super::{Clock, Indicator};
+
+/// States shown to the user on the status LEDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Waiting for a credential.
+    Idle,
+    /// Access granted, the door is unlocked.
+    Granted,
+    /// Access denied or unknown credential.
+    Denied,
+    /// Waiting for a credential, without a connection to the backend.
+    Offline,
+}
+
+impl Signal {
+    pub fn color(&self) -> RGB8 {
+        match self {
+            Signal::Idle => RGB8::new(0x00, 0x00, 0x10),
+            Signal::Granted => RGB8::new(0x00, 0x10, 0x00),
+            Signal::Denied => RGB8::new(0x10, 0x00, 0x00),
+            Signal::Offline => RGB8::new(0x10, 0x08, 0x00),
+        }
+    }
+}
+
+/// Shows every `Signal` as a solid color across a strip of smart LEDs.
+pub struct StripIndicator<S> {
+    strip: S,
+    length: usize,
+}
+
+impl<S> StripIndicator<S>
+where
+    S: SmartLedsWrite<Color = RGB8>,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub fn new(strip: S, length: usize) -> Self {
+        Self { strip, length }
+    }
+}
+
+impl<S> Indicator for StripIndicator<S>
+where
+    S: SmartLedsWrite<Color = RGB8>,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    fn show(&mut self, signal: Signal) -> Result<()> {
+        self.strip
+            .write(std::iter::repeat(signal.color()).take(self.length))?;
+        Ok(())
+    }
+}
+
+/// Non-blocking timer for a `Signal` shown for a while, such as `Signal::Denied`.
+///
+/// Like the hold-open timer of a `DoorController`, it never waits: `poll()`
+/// must be called regularly, and tells the caller when to show the signal of
+/// the current state again.
+pub struct SignalTimer<C: Clock> {
+    clock: C,
+    duration: Duration,
+    deadline: Option<Duration>,
+}
+
+impl<C: Clock> SignalTimer<C> {
+    /// Creates a new `SignalTimer` showing signals for `duration`.
+    pub fn new(clock: C, duration: Duration) -> Self {
+        Self {
+            clock,
+            duration,
+            deadline: None,
+        }
+    }
+
+    /// Shows `signal` on `indicator` and restarts the timer.
+    pub fn show<I: Indicator>(&mut self, indicator: &mut I, signal: Signal) -> Result<()> {
+        indicator.show(signal)?;
+        self.deadline = Some(self.clock.now() + self.duration);
+        Ok(())
+    }
+
+    /// Returns true while a signal is shown.
+    pub fn is_active(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    /// Returns true once the signal has been shown for long enough, after
+    /// which the timer is stopped.
+    pub fn poll(&mut self) -> bool {
+        match self.deadline {
+            Some(deadline) if self.clock.now() >= deadline => {
+                self.deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::lock::mock::{MockClock, MockIndicator};
+
+    #[test]
+    fn signal_expires_without_blocking() {
+        let clock = MockClock::new();
+        let mut timer = SignalTimer::new(clock.clone(), Duration::from_secs(3));
+        let mut indicator = MockIndicator::new();
+
+        timer.show(&mut indicator, Signal::Denied).unwrap();
+        assert_eq!(indicator.current(), Some(Signal::Denied));
+        assert!(timer.is_active());
+
+        clock.advance(Duration::from_millis(2999));
+        assert!(!timer.poll());
+        clock.advance(Duration::from_millis(1));
+        assert!(timer.poll());
+        assert!(!timer.is_active());
+        assert!(!timer.poll());
+    }
+
+    #[test]
+    fn showing_again_restarts_the_timer() {
+        let clock = MockClock::new();
+        let mut timer = SignalTimer::new(clock.clone(), Duration::from_secs(3));
+        let mut indicator = MockIndicator::new();
+
+        timer.show(&mut indicator, Signal::Denied).unwrap();
+        clock.advance(Duration::from_secs(2));
+        timer.show(&mut indicator, Signal::Denied).unwrap();
+        clock.advance(Duration::from_secs(2));
+        assert!(!timer.poll());
+        clock.advance(Duration::from_secs(1));
+        assert!(timer.poll());
+    }
+}
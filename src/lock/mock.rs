@@ -0,0 +1,134 @@
+//! Host-side stand-ins for the lock hardware, recording everything they are asked to do.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use embedded_hal::delay::DelayNs;
+
+use super::{Clock, DoorPosition, Indicator, LockActuator, Signal};
+
+/// A `LockActuator` that records every position it is driven to.
+#[derive(Debug)]
+pub struct MockLock {
+    position: DoorPosition,
+    history: Vec<DoorPosition>,
+    fail_at: Option<DoorPosition>,
+}
+
+impl Default for MockLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockLock {
+    pub fn new() -> Self {
+        Self {
+            position: DoorPosition::Neutral,
+            history: vec![],
+            fail_at: None,
+        }
+    }
+
+    /// Makes every move to `position` fail, simulating a jammed latch.
+    pub fn fail_at(&mut self, position: Option<DoorPosition>) {
+        self.fail_at = position;
+    }
+
+    /// All positions the latch was successfully driven to, oldest first.
+    pub fn history(&self) -> &[DoorPosition] {
+        &self.history
+    }
+}
+
+impl LockActuator for MockLock {
+    fn set_position(&mut self, position: DoorPosition) -> Result<()> {
+        if self.fail_at == Some(position) {
+            bail!("Mock latch jammed moving to {position:?}");
+        }
+        self.position = position;
+        self.history.push(position);
+        Ok(())
+    }
+
+    fn position(&self) -> DoorPosition {
+        self.position
+    }
+}
+
+/// An `Indicator` that records every signal it is asked to show.
+#[derive(Debug, Default)]
+pub struct MockIndicator {
+    history: Vec<Signal>,
+}
+
+impl MockIndicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All signals shown, oldest first.
+    pub fn history(&self) -> &[Signal] {
+        &self.history
+    }
+
+    /// The signal currently shown, if any.
+    pub fn current(&self) -> Option<Signal> {
+        self.history.last().copied()
+    }
+}
+
+impl Indicator for MockIndicator {
+    fn show(&mut self, signal: Signal) -> Result<()> {
+        self.history.push(signal);
+        Ok(())
+    }
+}
+
+/// A `DelayNs` that returns immediately and adds up the time it was asked to wait.
+#[derive(Debug, Default)]
+pub struct MockDelay {
+    elapsed_ns: u64,
+}
+
+impl MockDelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total time waited in milliseconds.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ns / 1_000_000
+    }
+}
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += ns as u64;
+    }
+}
+
+/// A `Clock` that only moves when told to. Clones share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::Result;
+use embedded_hal::delay::DelayNs;
+
+mod controller;
+pub use controller::{Clock, DoorCommand, DoorController, DoorEvent, DoorState, SystemClock};
+
+mod indicator;
+pub use indicator::{Signal, SignalTimer, StripIndicator};
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+mod servo;
+pub use servo::ServoConfig;
+#[cfg(target_os = "espidf")]
+pub use servo::ServoLock;
+
+/// Positions the lock actuator can be driven to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorPosition {
+    /// Resting position, the latch is free to be moved by hand.
+    Neutral,
+    Unlocked,
+    Locked,
+}
+
+/// Anything that can physically move the door latch.
+pub trait LockActuator {
+    /// Drives the latch to `position`, returning once the movement has finished.
+    fn set_position(&mut self, position: DoorPosition) -> Result<()>;
+
+    /// Returns the most recent position the latch was driven to.
+    fn position(&self) -> DoorPosition;
+}
+
+/// Anything that can show the user the result of an access attempt.
+pub trait Indicator {
+    fn show(&mut self, signal: Signal) -> Result<()>;
+}
+
+/// Unlocks the door for `hold_ms` milliseconds, then locks it again and returns
+/// the latch to the neutral position.
+pub fn unlock<A, I, D>(
+    actuator: &mut A,
+    indicator: &mut I,
+    delay: &mut D,
+    hold_ms: u32,
+) -> Result<()>
+where
+    A: LockActuator,
+    I: Indicator,
+    D: DelayNs,
+{
+    indicator.show(Signal::Granted)?;
+    actuator.set_position(DoorPosition::Unlocked)?;
+
+    delay.delay_ms(hold_ms);
+    indicator.show(Signal::Idle)?;
+    actuator.set_position(DoorPosition::Locked)?;
+    actuator.set_position(DoorPosition::Neutral)?;
+    Ok(())
+}
+
+/// Signals a denied access attempt for `wait_ms` milliseconds without moving the latch.
+///
+/// Blocks for the whole time. A main loop should use a `SignalTimer` instead.
+pub fn deny<I, D>(indicator: &mut I, delay: &mut D, wait_ms: u32) -> Result<()>
+where
+    I: Indicator,
+    D: DelayNs,
+{
+    indicator.show(Signal::Denied)?;
+
+    delay.delay_ms(wait_ms);
+    indicator.show(Signal::Idle)?;
+    Ok(())
+}
+
+/// Carries out a `DoorCommand` issued by a `DoorController`, returning the
+/// `DoorEvent` to report back to it.
+pub fn execute<A, I>(command: DoorCommand, actuator: &mut A, indicator: &mut I) -> DoorEvent
+where
+    A: LockActuator,
+    I: Indicator,
+{
+    let result = match command {
+        DoorCommand::Unlock => indicator
+            .show(Signal::Granted)
+            .and_then(|_| actuator.set_position(DoorPosition::Unlocked)),
+        DoorCommand::Lock => indicator
+            .show(Signal::Idle)
+            .and_then(|_| actuator.set_position(DoorPosition::Locked))
+            .and_then(|_| actuator.set_position(DoorPosition::Neutral)),
+    };
+
+    match result {
+        Ok(()) => DoorEvent::MotionComplete,
+        Err(e) => {
+            log::error!("Failed to {command:?} door: {e:?}");
+            DoorEvent::MotionFailed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockDelay, MockIndicator, MockLock};
+    use super::*;
+
+    #[test]
+    fn unlock_holds_the_door_then_locks_it() {
+        let mut lock = MockLock::new();
+        let mut indicator = MockIndicator::new();
+        let mut delay = MockDelay::new();
+
+        unlock(&mut lock, &mut indicator, &mut delay, 7000).unwrap();
+
+        assert_eq!(
+            lock.history(),
+            [
+                DoorPosition::Unlocked,
+                DoorPosition::Locked,
+                DoorPosition::Neutral
+            ]
+        );
+        assert_eq!(indicator.history(), [Signal::Granted, Signal::Idle]);
+        assert_eq!(delay.elapsed_ms(), 7000);
+        assert_eq!(lock.position(), DoorPosition::Neutral);
+    }
+
+    #[test]
+    fn unlock_stops_on_a_jammed_latch() {
+        let mut lock = MockLock::new();
+        lock.fail_at(Some(DoorPosition::Locked));
+        let mut indicator = MockIndicator::new();
+
+        assert!(unlock(&mut lock, &mut indicator, &mut MockDelay::new(), 7000).is_err());
+        assert_eq!(lock.history(), [DoorPosition::Unlocked]);
+        assert_eq!(lock.position(), DoorPosition::Unlocked);
+    }
+
+    #[test]
+    fn servo_config_maps_positions_to_angles() {
+        let config = ServoConfig::default();
+        assert_eq!(config.angle(DoorPosition::Neutral), 90);
+        assert_eq!(config.angle(DoorPosition::Unlocked), 37);
+        assert_eq!(config.angle(DoorPosition::Locked), 135);
+    }
+}
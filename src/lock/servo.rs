@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::hal::delay::FreeRtos;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::hal::ledc::LedcDriver;
+
+use super::DoorPosition;
+#[cfg(target_os = "espidf")]
+use super::LockActuator;
+
+/// Angles and pulse limits of a hobby servo driving the latch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServoConfig {
+    /// Angle in degrees (0-180) of `DoorPosition::Neutral`.
+    pub neutral_angle: u32,
+    /// Angle in degrees (0-180) of `DoorPosition::Unlocked`.
+    pub unlocked_angle: u32,
+    /// Angle in degrees (0-180) of `DoorPosition::Locked`.
+    pub locked_angle: u32,
+    /// Pulse width at 0 degrees, in thousandths of the max duty.
+    pub min_pulse: u32,
+    /// Pulse width at 180 degrees, in thousandths of the max duty.
+    pub max_pulse: u32,
+    /// Time to wait between each degree of movement (lower number == higher speed).
+    pub step_delay_ms: u32,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self {
+            neutral_angle: 90,
+            unlocked_angle: 37,
+            locked_angle: 135,
+            min_pulse: 25,
+            max_pulse: 125,
+            step_delay_ms: 12,
+        }
+    }
+}
+
+impl ServoConfig {
+    /// Checks the angles are within 0-180 degrees and the pulse widths within
+    /// the max duty, the one at 0 degrees below the one at 180.
+    pub fn validate(&self) -> Result<()> {
+        for angle in [self.neutral_angle, self.unlocked_angle, self.locked_angle] {
+            if angle > 180 {
+                return Err(anyhow!("Servo angle {angle} is above 180 degrees"));
+            }
+        }
+        if self.min_pulse >= self.max_pulse || self.max_pulse > 1000 {
+            return Err(anyhow!(
+                "Servo pulse widths {}-{} are not an increasing range within 1000",
+                self.min_pulse,
+                self.max_pulse
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the angle in degrees of the given `DoorPosition`.
+    pub fn angle(&self, position: DoorPosition) -> u32 {
+        match position {
+            DoorPosition::Neutral => self.neutral_angle,
+            DoorPosition::Unlocked => self.unlocked_angle,
+            DoorPosition::Locked => self.locked_angle,
+        }
+    }
+}
+
+/// Servo-driven latch over a 50 Hz `LedcDriver`.
+#[cfg(target_os = "espidf")]
+pub struct ServoLock<'d> {
+    servo: LedcDriver<'d>,
+    config: ServoConfig,
+    position: DoorPosition,
+    current_angle: u32,
+    min_limit: u32,
+    max_limit: u32,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> ServoLock<'d> {
+    /// Creates a new `ServoLock` and immediately moves the servo to the neutral position.
+    ///
+    /// Fails if `config` does not pass `ServoConfig::validate()`.
+    pub fn new(servo: LedcDriver<'d>, config: ServoConfig) -> Result<Self> {
+        config.validate()?;
+        let max_duty = servo.get_max_duty();
+        let mut lock = Self {
+            servo,
+            config,
+            position: DoorPosition::Neutral,
+            current_angle: config.neutral_angle,
+            min_limit: max_duty * config.min_pulse / 1000,
+            max_limit: max_duty * config.max_pulse / 1000,
+        };
+        lock.set_duty(config.neutral_angle)?;
+        Ok(lock)
+    }
+
+    pub fn config(&self) -> &ServoConfig {
+        &self.config
+    }
+
+    fn set_duty(&mut self, angle: u32) -> Result<()> {
+        log::debug!("Servo angle: {angle}");
+        let duty = map(angle.min(180), 0, 180, self.min_limit, self.max_limit);
+        self.servo.set_duty(duty)?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> LockActuator for ServoLock<'d> {
+    fn set_position(&mut self, position: DoorPosition) -> Result<()> {
+        log::info!("Moving to {position:?} position...");
+        let target = self.config.angle(position);
+        for angle in angle_range(self.current_angle, target) {
+            self.set_duty(angle)?;
+            // Give servo some time to update
+            FreeRtos::delay_ms(self.config.step_delay_ms);
+            self.current_angle = angle;
+        }
+        self.position = position;
+        log::info!("Finished moving to {position:?} position!");
+        Ok(())
+    }
+
+    fn position(&self) -> DoorPosition {
+        self.position
+    }
+}
+
+/// Maps `x` from one range to another.
+#[cfg(target_os = "espidf")]
+fn map(x: u32, in_min: u32, in_max: u32, out_min: u32, out_max: u32) -> u32 {
+    (x - in_min) * (out_max - out_min) / (in_max - in_min) + out_min
+}
+
+#[cfg(target_os = "espidf")]
+fn angle_range(a: u32, b: u32) -> Box<dyn Iterator<Item = u32>> {
+    if b > a {
+        Box::new(a..=b)
+    } else {
+        Box::new((b..=a).rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_default_config() {
+        ServoConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn refuses_an_angle_above_180_degrees() {
+        let config = ServoConfig {
+            locked_angle: 181,
+            ..ServoConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn refuses_pulse_widths_out_of_order_or_range() {
+        let swapped = ServoConfig {
+            min_pulse: 125,
+            max_pulse: 25,
+            ..ServoConfig::default()
+        };
+        assert!(swapped.validate().is_err());
+
+        let equal = ServoConfig {
+            min_pulse: 25,
+            max_pulse: 25,
+            ..ServoConfig::default()
+        };
+        assert!(equal.validate().is_err());
+
+        let too_wide = ServoConfig {
+            max_pulse: 1001,
+            ..ServoConfig::default()
+        };
+        assert!(too_wide.validate().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+/// Something that happened on the lock, published for fleet monitoring.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The lock booted.
+    Boot { firmware: String },
+    /// The Wi-Fi connection went up or down.
+    Wifi {
+        connected: bool,
+        /// Signal strength in dBm, once connected.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rssi: Option<i8>,
+    },
+    /// A YubiKey was presented to the reader.
+    CredentialPresented { serial: u32 },
+    /// The door was unlocked. `serial` is `None` for a remote unlock.
+    AccessGranted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        serial: Option<u32>,
+    },
+    /// A credential was rejected. `serial` is `None` if it could not be read.
+    AccessDenied {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        serial: Option<u32>,
+    },
+    /// A YubiKey was programmed and enrolled on the lock.
+    KeyEnrolled { serial: u32 },
+    /// A YubiKey was revoked on the lock.
+    KeyRevoked { serial: u32 },
+    /// The latch failed to move.
+    ServoFault,
+}
+
+/// Payload published on `event_topic()`, such as
+/// `{"door_id": 1, "timestamp": 1700000000, "event": "access_granted", "serial": 42}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventMessage {
+    pub door_id: u32,
+    /// Unix time in seconds at which the event happened.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn message(event: Event) -> EventMessage {
+        EventMessage {
+            door_id: 1,
+            timestamp: 1_700_000_000,
+            event,
+        }
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let events = [
+            Event::Boot {
+                firmware: "0.1.0".to_string(),
+            },
+            Event::Wifi {
+                connected: true,
+                rssi: Some(-60),
+            },
+            Event::Wifi {
+                connected: false,
+                rssi: None,
+            },
+            Event::CredentialPresented { serial: 42 },
+            Event::AccessGranted { serial: Some(42) },
+            Event::AccessGranted { serial: None },
+            Event::AccessDenied { serial: None },
+            Event::KeyEnrolled { serial: 42 },
+            Event::KeyRevoked { serial: 42 },
+            Event::ServoFault,
+        ];
+        for event in events {
+            let message = message(event);
+            let json = serde_json::to_string(&message).unwrap();
+            assert_eq!(
+                serde_json::from_str::<EventMessage>(&json).unwrap(),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn message_is_flat() {
+        let value =
+            serde_json::to_value(message(Event::AccessGranted { serial: Some(42) })).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "door_id": 1,
+                "timestamp": 1_700_000_000,
+                "event": "access_granted",
+                "serial": 42,
+            })
+        );
+
+        let value = serde_json::to_value(message(Event::AccessDenied { serial: None })).unwrap();
+        assert_eq!(
+            value,
+            json!({ "door_id": 1, "timestamp": 1_700_000_000, "event": "access_denied" })
+        );
+    }
+
+    #[test]
+    fn parses_a_message_without_optional_fields() {
+        let message: EventMessage = serde_json::from_value(json!({
+            "door_id": 1,
+            "timestamp": 1_700_000_000,
+            "event": "wifi",
+            "connected": false,
+        }))
+        .unwrap();
+        assert_eq!(
+            message.event,
+            Event::Wifi {
+                connected: false,
+                rssi: None
+            }
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+use embedded_svc::mqtt::client::{EventPayload, QoS};
+use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
+
+use super::{
+    command_topic, event_topic, Event, EventMessage, OfflineQueue, DEFAULT_QUEUE_CAPACITY,
+};
+use crate::command::{CommandChannel, SignedCommand};
+
+/// What the MQTT event callback hands over to the link.
+enum Message {
+    Connected,
+    Disconnected,
+    Command(SignedCommand),
+}
+
+/// Connection to the MQTT broker of a single door.
+///
+/// The client runs in its own task, so nothing happens on this side until
+/// `poll()` or `try_recv()` is called: that is when the command topic is
+/// subscribed to again after a reconnection, and queued events are flushed.
+/// The broker does not keep the subscription of a clean session.
+pub struct MqttLink {
+    client: EspMqttClient<'static>,
+    door_id: u32,
+    command_topic: String,
+    event_topic: String,
+    connected: bool,
+    messages: Receiver<Message>,
+    commands: VecDeque<SignedCommand>,
+    queue: OfflineQueue<Vec<u8>>,
+}
+
+impl MqttLink {
+    /// Connects to the broker at `url` on behalf of `door_id`.
+    pub fn new(url: &str, door_id: u32, config: &MqttClientConfiguration) -> Result<Self> {
+        let command_topic = command_topic(door_id);
+        let (sender, messages) = channel();
+
+        let expected = command_topic.clone();
+        let client = EspMqttClient::new_cb(url, config, move |event| {
+            let message = match event.payload() {
+                EventPayload::Connected(_) => Message::Connected,
+                EventPayload::Disconnected => Message::Disconnected,
+                EventPayload::Received { topic, data, .. } if topic == Some(expected.as_str()) => {
+                    match SignedCommand::parse(data) {
+                        Some(command) => Message::Command(command),
+                        None => return,
+                    }
+                }
+                _ => return,
+            };
+            // Only fails once the link is dropped.
+            let _ = sender.send(message);
+        })?;
+
+        Ok(Self {
+            client,
+            door_id,
+            command_topic,
+            event_topic: event_topic(door_id),
+            connected: false,
+            messages,
+            commands: VecDeque::new(),
+            queue: OfflineQueue::new(DEFAULT_QUEUE_CAPACITY),
+        })
+    }
+
+    /// Set the number of events kept while the broker is unreachable.
+    ///
+    /// Events already queued are discarded.
+    pub fn set_queue_capacity(&mut self, capacity: usize) {
+        self.queue = OfflineQueue::new(capacity);
+    }
+
+    /// Get the identifier of the door.
+    pub fn door_id(&self) -> u32 {
+        self.door_id
+    }
+
+    /// Get the topic the commands are received from.
+    pub fn command_topic(&self) -> &str {
+        &self.command_topic
+    }
+
+    /// Get the topic the events are published to.
+    pub fn event_topic(&self) -> &str {
+        &self.event_topic
+    }
+
+    /// Whether the broker was reachable the last time the link was polled.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Get the events waiting for the broker to come back.
+    pub fn queue(&self) -> &OfflineQueue<Vec<u8>> {
+        &self.queue
+    }
+
+    /// Publish `event`, or queue it until the broker is reachable.
+    pub fn publish(&mut self, event: Event) -> Result<()> {
+        let message = EventMessage {
+            door_id: self.door_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            event,
+        };
+        self.queue.push(serde_json::to_vec(&message)?);
+        self.flush();
+        Ok(())
+    }
+
+    /// Handles the pending connection changes and commands, then flushes the queued events.
+    pub fn poll(&mut self) -> Result<()> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(Message::Connected) => {
+                    log::info!("MQTT connected, subscribing to {}", self.command_topic);
+                    self.client
+                        .subscribe(&self.command_topic, QoS::AtLeastOnce)?;
+                    self.connected = true;
+                }
+                Ok(Message::Disconnected) => {
+                    if self.connected {
+                        log::warn!("MQTT disconnected, queuing events");
+                    }
+                    self.connected = false;
+                }
+                Ok(Message::Command(command)) => self.commands.push_back(command),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(anyhow!("MQTT client stopped delivering events"))
+                }
+            }
+        }
+        self.flush();
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        while self.connected {
+            let Some(payload) = self.queue.pop() else {
+                break;
+            };
+            if let Err(e) =
+                self.client
+                    .publish(&self.event_topic, QoS::AtLeastOnce, false, &payload)
+            {
+                log::warn!("Cannot publish event, queuing it: {e}");
+                self.queue.push_front(payload);
+                break;
+            }
+        }
+    }
+}
+
+impl CommandChannel for MqttLink {
+    fn try_recv(&mut self) -> Result<Option<SignedCommand>> {
+        self.poll()?;
+        Ok(self.commands.pop_front())
+    }
+}
@@ -0,0 +1,29 @@
+//! MQTT link to the backend, for fleet telemetry and remote commands.
+//!
+//! Each door publishes `EventMessage`s as JSON on `event_topic()`, and receives
+//! `RemoteCommand`s on `command_topic()`. Events published while the broker is
+//! unreachable are kept in an `OfflineQueue` and sent once it reconnects.
+
+mod event;
+pub use event::{Event, EventMessage};
+
+#[cfg(target_os = "espidf")]
+mod link;
+#[cfg(target_os = "espidf")]
+pub use link::MqttLink;
+
+mod queue;
+pub use queue::OfflineQueue;
+
+/// Number of events kept while the broker is unreachable.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 32;
+
+/// Topic the commands for `door_id` are published to.
+pub fn command_topic(door_id: u32) -> String {
+    format!("lynx/doors/{door_id}/commands")
+}
+
+/// Topic the events of `door_id` are published to.
+pub fn event_topic(door_id: u32) -> String {
+    format!("lynx/doors/{door_id}/events")
+}
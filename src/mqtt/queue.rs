@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+/// Bounded FIFO holding messages while the broker is unreachable.
+///
+/// Once full, the oldest message is dropped to make room for the newest one:
+/// the latest state of the lock matters more than its full history.
+#[derive(Clone, Debug)]
+pub struct OfflineQueue<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    dropped: usize,
+}
+
+impl<T> OfflineQueue<T> {
+    /// Constructs an empty `OfflineQueue` holding at most `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Queue `item`, dropping the oldest message if the queue is full.
+    pub fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+            self.dropped += 1;
+        }
+        self.items.push_back(item);
+    }
+
+    /// Put back a message that could not be sent, so it is sent first next time.
+    ///
+    /// Does nothing if the queue filled up in the meantime.
+    pub fn push_front(&mut self, item: T) {
+        if self.items.len() < self.capacity {
+            self.items.push_front(item);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// Take the oldest message out of the queue.
+    pub fn pop(&mut self) -> Option<T> {
+        self.items.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of messages dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OfflineQueue;
+
+    #[test]
+    fn drops_the_oldest_message_once_full() {
+        let mut queue = OfflineQueue::new(2);
+        queue.push(1);
+        queue.push(2);
+        queue.push(3);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn puts_back_a_message_that_could_not_be_sent() {
+        let mut queue = OfflineQueue::new(2);
+        queue.push(1);
+        queue.push(2);
+        let first = queue.pop().unwrap();
+        queue.push_front(first);
+        assert_eq!(queue.pop(), Some(1));
+
+        queue.push(3);
+        queue.push_front(0);
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop(), Some(2));
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut queue = OfflineQueue::new(0);
+        queue.push(1);
+        assert!(queue.is_empty());
+        assert_eq!(queue.dropped(), 1);
+    }
+}
@@ -0,0 +1,192 @@
+//! Data partitions of `partitions.csv`, found by label, as `embedded_storage` flash.
+
+use std::ffi::{c_void, CString};
+
+use anyhow::{anyhow, Result};
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+use embedded_storage::{ReadStorage, Storage};
+
+use esp_idf_svc::sys::{
+    esp, esp_partition_erase_range, esp_partition_find_first, esp_partition_read,
+    esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY, esp_partition_t,
+    esp_partition_type_t_ESP_PARTITION_TYPE_DATA, esp_partition_write, EspError,
+};
+
+/// Size of the flash sectors, erased as a whole.
+pub const SECTOR_SIZE: usize = 0x1000;
+
+/// Error returned when accessing a `Partition`.
+#[derive(Debug)]
+pub enum PartitionError {
+    /// The access does not fit in the partition.
+    OutOfBounds,
+    /// An erase does not start and end on sector boundaries.
+    NotAligned,
+    /// The flash driver failed.
+    Esp(EspError),
+}
+
+impl NorFlashError for PartitionError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            PartitionError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            PartitionError::NotAligned => NorFlashErrorKind::NotAligned,
+            PartitionError::Esp(_) => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<EspError> for PartitionError {
+    fn from(e: EspError) -> Self {
+        PartitionError::Esp(e)
+    }
+}
+
+/// A data partition, found by its label.
+///
+/// Offsets are relative to the start of the partition, so nothing can be
+/// written over another partition. As a `Storage`, a write erases and rewrites
+/// the whole sectors it touches, keeping the rest of their content. As a
+/// `NorFlash`, a write only programs erased bytes, leaving erases to the caller.
+pub struct Partition {
+    partition: &'static esp_partition_t,
+}
+
+// The partition table is read-only and lives as long as the program.
+unsafe impl Send for Partition {}
+
+impl Partition {
+    /// Looks up the data partition named `label` in the partition table.
+    pub fn find(label: &str) -> Result<Self> {
+        let name = CString::new(label)?;
+        let partition = unsafe {
+            esp_partition_find_first(
+                esp_partition_type_t_ESP_PARTITION_TYPE_DATA,
+                esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY,
+                name.as_ptr(),
+            )
+            .as_ref()
+        }
+        .ok_or_else(|| anyhow!("No data partition labelled {label}"))?;
+        if partition.erase_size as usize != SECTOR_SIZE {
+            return Err(anyhow!(
+                "Partition {label} erases blocks of {} bytes",
+                partition.erase_size
+            ));
+        }
+        log::info!(
+            "Found partition {label} at 0x{:X}, {} bytes",
+            partition.address,
+            partition.size
+        );
+        Ok(Self { partition })
+    }
+
+    fn check_range(&self, offset: u32, len: usize) -> Result<(), PartitionError> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= self.partition.size as usize => Ok(()),
+            _ => Err(PartitionError::OutOfBounds),
+        }
+    }
+
+    fn read_raw(&self, offset: usize, bytes: &mut [u8]) -> Result<(), PartitionError> {
+        esp!(unsafe {
+            esp_partition_read(
+                self.partition,
+                offset,
+                bytes.as_mut_ptr() as *mut c_void,
+                bytes.len(),
+            )
+        })?;
+        Ok(())
+    }
+
+    fn erase_raw(&self, offset: usize, len: usize) -> Result<(), PartitionError> {
+        esp!(unsafe { esp_partition_erase_range(self.partition, offset, len) })?;
+        Ok(())
+    }
+
+    fn write_raw(&self, offset: usize, bytes: &[u8]) -> Result<(), PartitionError> {
+        esp!(unsafe {
+            esp_partition_write(
+                self.partition,
+                offset,
+                bytes.as_ptr() as *const c_void,
+                bytes.len(),
+            )
+        })?;
+        Ok(())
+    }
+}
+
+impl ReadStorage for Partition {
+    type Error = PartitionError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_range(offset, bytes.len())?;
+        self.read_raw(offset as usize, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        self.partition.size as usize
+    }
+}
+
+impl Storage for Partition {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_range(offset, bytes.len())?;
+
+        let offset = offset as usize;
+        let sector = SECTOR_SIZE;
+        let start = offset / sector * sector;
+        let end = (offset + bytes.len()).div_ceil(sector) * sector;
+        let mut buf = vec![0u8; end - start];
+        self.read_raw(start, &mut buf)?;
+
+        let range = offset - start..offset - start + bytes.len();
+        if buf[range.clone()] == *bytes {
+            // Spare the flash an erase cycle.
+            return Ok(());
+        }
+        buf[range].copy_from_slice(bytes);
+
+        self.erase_raw(start, buf.len())?;
+        self.write_raw(start, &buf)
+    }
+}
+
+impl ErrorType for Partition {
+    type Error = PartitionError;
+}
+
+impl ReadNorFlash for Partition {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadStorage::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        ReadStorage::capacity(self)
+    }
+}
+
+impl NorFlash for Partition {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to || from as usize % SECTOR_SIZE != 0 || to as usize % SECTOR_SIZE != 0 {
+            return Err(PartitionError::NotAligned);
+        }
+        self.check_range(from, (to - from) as usize)?;
+        self.erase_raw(from as usize, (to - from) as usize)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_range(offset, bytes.len())?;
+        self.write_raw(offset as usize, bytes)
+    }
+}
@@ -23,6 +23,7 @@ where
     pn532: pn532::Pn532<SpiWrapper<'d, S>, TimerWrapper<'d>, N>,
     timeout: Duration,
     target: u8,
+    uid: Vec<u8>,
 }
 
 impl<'d, S: Borrow<SpiDriver<'d>> + 'd, const N: usize> Pn532<'d, S, N> {
@@ -34,6 +35,7 @@ impl<'d, S: Borrow<SpiDriver<'d>> + 'd, const N: usize> Pn532<'d, S, N> {
             pn532,
             timeout: Duration::from_millis(50),
             target: 0,
+            uid: Vec::new(),
         }
     }
 
@@ -109,8 +111,15 @@ impl<'d, S: Borrow<SpiDriver<'d>> + 'd, const N: usize> Pn532<'d, S, N> {
         Ok(())
     }
 
+    /// UID of the target found by the last `inlist_passive_target()`, empty if
+    /// none was found.
+    pub fn uid(&self) -> &[u8] {
+        &self.uid
+    }
+
     pub fn inlist_passive_target(&mut self, timeout: Duration) -> Result<(), Pn532Error> {
         let mut target = 0;
+        let mut found_uid = Vec::new();
         let response = match self.pn532.process(
             &Request::INLIST_ONE_ISO_A_TARGET,
             N - 9,
@@ -148,6 +157,7 @@ impl<'d, S: Borrow<SpiDriver<'d>> + 'd, const N: usize> Pn532<'d, S, N> {
 
                 let uid = &res[6..6 + uid_length as usize];
                 log::info!("UID Value: {uid:02X?}");
+                found_uid = uid.to_vec();
                 Ok(())
             }
             Err(e) => {
@@ -162,6 +172,7 @@ impl<'d, S: Borrow<SpiDriver<'d>> + 'd, const N: usize> Pn532<'d, S, N> {
             }
         };
         self.target = target;
+        self.uid = found_uid;
         response
     }
 
@@ -0,0 +1,127 @@
+use std::net::Ipv4Addr;
+
+/// Length of the header of a DNS message.
+const HEADER_LEN: usize = 12;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const FLAG_RECURSION_AVAILABLE: u16 = 0x0080;
+const OPCODE_MASK: u16 = 0x7800;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+/// Time to live of the answers, short so phones forget them once provisioned.
+const TTL: u32 = 60;
+
+/// Answers the DNS `query` as if every name resolved to `address`, so that
+/// phones joining the access point find the `Portal` whatever they look up.
+///
+/// Queries for other records than A get an empty answer, so the client falls
+/// back to A. Returns `None` for anything but a standard query for a single
+/// name, which is then left unanswered.
+pub fn captive_dns_reply(query: &[u8], address: Ipv4Addr) -> Option<Vec<u8>> {
+    let header = query.get(..HEADER_LEN)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    if flags & (FLAG_RESPONSE | OPCODE_MASK) != 0 || questions != 1 {
+        return None;
+    }
+
+    // Names in queries are never compressed: a sequence of labels, up to an empty one.
+    let mut end = HEADER_LEN;
+    loop {
+        let len = *query.get(end)? as usize;
+        end += 1;
+        if len == 0 {
+            break;
+        }
+        if len > 63 {
+            return None;
+        }
+        end += len;
+    }
+    let question = query.get(HEADER_LEN..end + 4)?;
+    let record_type = u16::from_be_bytes([query[end], query[end + 1]]);
+    let class = u16::from_be_bytes([query[end + 2], query[end + 3]]);
+    let answers = u16::from(matches!(record_type, TYPE_A | TYPE_ANY) && class == CLASS_IN);
+
+    let flags = FLAG_RESPONSE
+        | FLAG_AUTHORITATIVE
+        | FLAG_RECURSION_AVAILABLE
+        | (flags & FLAG_RECURSION_DESIRED);
+    let mut reply = Vec::with_capacity(HEADER_LEN + question.len() + 16);
+    reply.extend_from_slice(&header[..2]);
+    reply.extend_from_slice(&flags.to_be_bytes());
+    reply.extend_from_slice(&1u16.to_be_bytes());
+    reply.extend_from_slice(&answers.to_be_bytes());
+    reply.extend_from_slice(&[0; 4]);
+    reply.extend_from_slice(question);
+    if answers == 1 {
+        // The name is a pointer to the one in the question, right after the header.
+        reply.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+        reply.extend_from_slice(&TYPE_A.to_be_bytes());
+        reply.extend_from_slice(&CLASS_IN.to_be_bytes());
+        reply.extend_from_slice(&TTL.to_be_bytes());
+        reply.extend_from_slice(&4u16.to_be_bytes());
+        reply.extend_from_slice(&address.octets());
+    }
+    Some(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 71, 1);
+
+    /// A query with ID `0x1234` and recursion desired, for `name` and `record_type`.
+    fn query(name: &str, record_type: u16) -> Vec<u8> {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.push(0);
+        query.extend_from_slice(&record_type.to_be_bytes());
+        query.extend_from_slice(&CLASS_IN.to_be_bytes());
+        query
+    }
+
+    #[test]
+    fn resolves_any_name_to_the_portal() {
+        let query = query("connectivitycheck.gstatic.com", TYPE_A);
+        let reply = captive_dns_reply(&query, ADDRESS).unwrap();
+
+        assert_eq!(reply[..2], [0x12, 0x34]);
+        assert_eq!(reply[2..4], [0x85, 0x80]);
+        assert_eq!(reply[4..12], [0, 1, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(reply[12..query.len()], query[12..]);
+        let answer = &reply[query.len()..];
+        assert_eq!(
+            answer,
+            [0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 168, 71, 1]
+        );
+    }
+
+    #[test]
+    fn answers_other_records_with_nothing() {
+        let query = query("captive.apple.com", 28);
+        let reply = captive_dns_reply(&query, ADDRESS).unwrap();
+        assert_eq!(reply[4..12], [0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(reply.len(), query.len());
+    }
+
+    #[test]
+    fn ignores_what_is_not_a_query() {
+        let mut response = query("example.com", TYPE_A);
+        response[2] |= 0x80;
+        assert!(captive_dns_reply(&response, ADDRESS).is_none());
+
+        let query = query("example.com", TYPE_A);
+        assert!(captive_dns_reply(&query[..query.len() - 1], ADDRESS).is_none());
+        assert!(captive_dns_reply(&query[..5], ADDRESS).is_none());
+    }
+}
@@ -0,0 +1,86 @@
+//! Runtime provisioning of the network settings, instead of rebuilding for every site.
+//!
+//! The settings entered in the `Portal` are applied to the `DeviceConfig` with
+//! `DeviceConfig::provision()`, and take precedence over the ones compiled in
+//! from `cfg.toml`.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::hmac::{hex, hmac_sha256};
+use crate::Url;
+
+mod dns;
+pub use dns::captive_dns_reply;
+
+#[cfg(target_os = "espidf")]
+mod portal;
+#[cfg(target_os = "espidf")]
+pub use portal::{Portal, PORTAL_ADDRESS};
+
+/// Password of the access point of the `Portal`, derived from `key`, the key
+/// the lock shares with the backend, and the `mac` address of the lock.
+///
+/// It differs for every lock, and only the backend can tell it to the installer.
+pub fn setup_password(key: &[u8], mac: &[u8; 6]) -> Result<String> {
+    if key.is_empty() {
+        return Err(anyhow!("No key to derive the setup password from"));
+    }
+    let digest = hmac_sha256(key, format!("lynx-setup:{}", hex(mac)).as_bytes())?;
+    Ok(hex(&digest[..6]))
+}
+
+/// Everything a lock needs to reach its backend.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    pub ssid: String,
+    /// Empty for an open network.
+    #[serde(default)]
+    pub password: String,
+    pub backend_url: String,
+    pub door_id: u32,
+}
+
+impl Credentials {
+    /// Checks the credentials can be used, to reject a bad form before saving it.
+    pub fn validate(&self) -> Result<()> {
+        if self.ssid.is_empty() || self.ssid.len() > 32 {
+            return Err(anyhow!("The SSID must be 1 to 32 bytes long"));
+        }
+        if !self.password.is_empty() && !(8..=64).contains(&self.password.len()) {
+            return Err(anyhow!("The password must be empty or 8 to 64 bytes long"));
+        }
+        let url = Url::parse(&self.backend_url).map_err(|e| anyhow!("Invalid backend URL: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("The backend URL must use http or https"));
+        }
+        if self.door_id == 0 {
+            return Err(anyhow!("The door ID must be a positive number"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x34, 0x85, 0x18, 0x00, 0xAB, 0xCD];
+
+    #[test]
+    fn setup_password_differs_for_every_lock() {
+        let password = setup_password(b"secret", &MAC).unwrap();
+        assert_eq!(password.len(), 12);
+        assert_eq!(password, setup_password(b"secret", &MAC).unwrap());
+
+        let mut other = MAC;
+        other[5] += 1;
+        assert_ne!(password, setup_password(b"secret", &other).unwrap());
+        assert_ne!(password, setup_password(b"other", &MAC).unwrap());
+    }
+
+    #[test]
+    fn setup_password_needs_a_key() {
+        assert!(setup_password(b"", &MAC).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use embedded_svc::http::Headers;
+use embedded_svc::io::{Read, Write};
+use embedded_svc::wifi::{AccessPointConfiguration, AuthMethod, Configuration};
+use esp_idf_svc::http::server::{
+    Configuration as ServerConfiguration, EspHttpConnection, EspHttpServer, Request,
+};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::wifi::EspWifi;
+
+use super::{captive_dns_reply, Credentials};
+use crate::api::DEFAULT_BASE_URL;
+
+/// Address of the lock on its access point, where the form is served.
+pub const PORTAL_ADDRESS: &str = "192.168.71.1";
+
+/// Largest form accepted.
+const MAX_FORM_LEN: usize = 1024;
+
+/// How long the DNS server waits for a query before checking whether to stop.
+const DNS_POLL: Duration = Duration::from_millis(500);
+
+/// Access point serving a form to enter the `Credentials` of the lock.
+///
+/// A DNS server resolves every name to the lock, and every other path
+/// redirects to the form, so phones that probe for a captive portal open it on
+/// their own. Submitted credentials are validated, then handed over through
+/// `try_recv()`: storing them is up to the caller.
+pub struct Portal {
+    _server: EspHttpServer<'static>,
+    received: Receiver<Credentials>,
+    stop_dns: Arc<AtomicBool>,
+}
+
+impl Portal {
+    /// Starts an access point named `ssid` on `wifi`, protected by `password`,
+    /// such as a `setup_password()`.
+    ///
+    /// With a `backend_url`, the lock was already provisioned and the form only
+    /// accepts that backend.
+    pub fn start(
+        wifi: &mut EspWifi<'static>,
+        ssid: &str,
+        password: &str,
+        backend_url: Option<&str>,
+    ) -> Result<Self> {
+        if !(8..=63).contains(&password.len()) {
+            return Err(anyhow!("Access point password must be 8 to 63 bytes long"));
+        }
+        wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+            ssid: ssid
+                .parse()
+                .map_err(|_| anyhow!("Access point SSID is too long"))?,
+            password: password
+                .parse()
+                .map_err(|_| anyhow!("Access point password is too long"))?,
+            auth_method: AuthMethod::WPA2Personal,
+            ..Default::default()
+        }))?;
+        wifi.start()?;
+        log::info!("Provisioning portal on {ssid}, at http://{PORTAL_ADDRESS}/");
+
+        let mut server = EspHttpServer::new(&ServerConfiguration {
+            uri_match_wildcard: true,
+            ..Default::default()
+        })?;
+        let (sender, received) = channel();
+        let fixed_url = backend_url.map(str::to_string);
+        let form_url = fixed_url.clone();
+
+        server.fn_handler("/", Method::Get, move |req| {
+            form(req, form_url.as_deref(), None)
+        })?;
+        server.fn_handler("/", Method::Post, move |mut req| {
+            let credentials = read_form(&mut req).and_then(|credentials| {
+                credentials.validate()?;
+                if fixed_url
+                    .as_ref()
+                    .is_some_and(|url| *url != credentials.backend_url)
+                {
+                    return Err(anyhow!(
+                        "The backend URL cannot be changed once provisioned"
+                    ));
+                }
+                Ok(credentials)
+            });
+            match credentials {
+                Ok(credentials) => {
+                    req.into_response(200, None, &[("Content-Type", "text/html")])?
+                        .write_all(SAVED.as_bytes())?;
+                    // Only fails once the portal is dropped.
+                    let _ = sender.send(credentials);
+                    Ok(())
+                }
+                Err(e) => form(req, fixed_url.as_deref(), Some(&e.to_string())),
+            }
+        })?;
+        server.fn_handler("/*", Method::Get, |req| -> Result<()> {
+            let location = format!("http://{PORTAL_ADDRESS}/");
+            req.into_response(302, None, &[("Location", &location)])?;
+            Ok(())
+        })?;
+
+        let stop_dns = Arc::new(AtomicBool::new(false));
+        serve_dns(PORTAL_ADDRESS.parse()?, stop_dns.clone())?;
+
+        Ok(Self {
+            _server: server,
+            received,
+            stop_dns,
+        })
+    }
+
+    /// Returns the credentials submitted since the last call, if any.
+    pub fn try_recv(&self) -> Result<Option<Credentials>> {
+        match self.received.try_recv() {
+            Ok(credentials) => Ok(Some(credentials)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(anyhow!("Provisioning server stopped")),
+        }
+    }
+}
+
+impl Drop for Portal {
+    fn drop(&mut self) {
+        self.stop_dns.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Answers every DNS query with `address` from a thread, until `stop` is set.
+fn serve_dns(address: Ipv4Addr, stop: Arc<AtomicBool>) -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 53))?;
+    socket.set_read_timeout(Some(DNS_POLL))?;
+    thread::Builder::new().stack_size(4096).spawn(move || {
+        let mut query = [0u8; 512];
+        while !stop.load(Ordering::Relaxed) {
+            let (len, client) = match socket.recv_from(&mut query) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(e) => {
+                    log::warn!("DNS server stopped: {e}");
+                    return;
+                }
+            };
+            if let Some(reply) = captive_dns_reply(&query[..len], address) {
+                if let Err(e) = socket.send_to(&reply, client) {
+                    log::warn!("Cannot answer DNS query: {e}");
+                }
+            }
+        }
+    })?;
+    Ok(())
+}
+
+fn read_form(req: &mut Request<&mut EspHttpConnection>) -> Result<Credentials> {
+    let len = req.content_len().unwrap_or(0) as usize;
+    if len > MAX_FORM_LEN {
+        return Err(anyhow!("Form is too large"));
+    }
+    let mut body = vec![0u8; len];
+    req.read_exact(&mut body)
+        .map_err(|e| anyhow!("Cannot read form: {e:?}"))?;
+    serde_urlencoded::from_bytes(&body).map_err(|e| anyhow!("Invalid form: {e}"))
+}
+
+/// Serves the form, with the backend URL fixed to `backend_url` if set.
+fn form(
+    req: Request<&mut EspHttpConnection>,
+    backend_url: Option<&str>,
+    error: Option<&str>,
+) -> Result<()> {
+    let error = error
+        .map(|error| format!("<p class=\"error\">{}</p>", escape(error)))
+        .unwrap_or_default();
+    let page = FORM
+        .replace("{error}", &error)
+        .replace(
+            "{backend_url}",
+            &escape(backend_url.unwrap_or(DEFAULT_BASE_URL)),
+        )
+        .replace(
+            "{readonly}",
+            if backend_url.is_some() {
+                " readonly"
+            } else {
+                ""
+            },
+        );
+    let status = if error.is_empty() { 200 } else { 400 };
+    req.into_response(status, None, &[("Content-Type", "text/html")])?
+        .write_all(page.as_bytes())?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const FORM: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Lynx Lock Setup</title>
+<style>body{font-family:sans-serif;max-width:24em;margin:auto}label,input{display:block;width:100%}input{margin-bottom:1em}.error{color:#b00}</style>
+</head>
+<body>
+<h1>Lynx Lock Setup</h1>
+{error}
+<form method="post" action="/">
+<label for="ssid">Wi-Fi network</label>
+<input id="ssid" name="ssid" maxlength="32" required>
+<label for="password">Wi-Fi password</label>
+<input id="password" name="password" type="password" maxlength="64">
+<label for="backend_url">Backend URL</label>
+<input id="backend_url" name="backend_url" type="url" value="{backend_url}" required{readonly}>
+<label for="door_id">Door ID</label>
+<input id="door_id" name="door_id" type="number" min="1" required>
+<input type="submit" value="Save">
+</form>
+</body>
+</html>
+"#;
+
+const SAVED: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width, initial-scale=1"><title>Lynx Lock Setup</title></head>
+<body><h1>Saved</h1><p>The lock restarts and joins the network.</p></body>
+</html>
+"#;
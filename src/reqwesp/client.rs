@@ -1,45 +1,127 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::time::Duration;
+
 use anyhow::Result;
+use hyper::header::{HeaderName, HeaderValue, LOCATION, USER_AGENT};
+use hyper::HeaderMap;
 
 use embedded_svc::http::client::Client as HttpClient;
+use embedded_svc::http::client::Connection;
 use embedded_svc::http::Method;
-use esp_idf_svc::http::client::{Configuration as HttpConfig, EspHttpConnection};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::http::client::{
+    Configuration as HttpConfig, EspHttpConnection, FollowRedirectsPolicy,
+};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::sys::{esp, esp_tls_free_global_ca_store, esp_tls_set_global_ca_store};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::tls::X509;
+
+use crate::reqwesp::error::HttpError;
+use crate::reqwesp::redirect;
+use crate::reqwesp::sign::Signer;
+#[cfg(not(target_os = "espidf"))]
+use crate::reqwesp::transport::TcpConnection;
+use crate::reqwesp::transport::{DefaultConnection, ResponseHeaders};
+use crate::{
+    IntoUrl, RedirectPolicy, Request, RequestBuilder, Response, RetryPolicy, DEFAULT_BODY_LIMIT,
+};
 
-use crate::{Request, RequestBuilder, Response};
+/// Header carrying the identifier set with `ClientBuilder::device_id()`.
+pub const DEVICE_ID: HeaderName = HeaderName::from_static("x-device-id");
 
-pub struct Client {
-    client: HttpClient<EspHttpConnection>,
+/// An HTTP client sending requests over a `Connection`.
+///
+/// On the device this is an `EspHttpConnection`, and a `TcpConnection` on the
+/// host. Any other `Connection`, such as the ones in `reqwesp::transport`, can
+/// be used with `Client::with_connection()`.
+pub struct Client<C = DefaultConnection> {
+    client: HttpClient<C>,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+    redirect: RedirectPolicy,
+    signer: Option<Box<dyn Signer>>,
+    connector: Option<Connector<C>>,
 }
 
-impl<'a> Client {
-    /// Constructs a new `Client`.
+/// Opens a new `Connection` to replace one that can no longer be used.
+type Connector<C> = Box<dyn FnMut() -> Result<C>>;
+
+impl Client {
+    /// Constructs a new `Client` over the `DefaultConnection`.
+    ///
+    /// Trusts the built-in CA bundle and uses the default timeouts. Use
+    /// `Client::builder()` to configure the client instead.
     pub fn new() -> Result<Self> {
-        Ok(Client {
-            client: Self::create_http_client()?,
-        })
+        ClientBuilder::new().build()
+    }
+
+    /// Creates a `ClientBuilder` to configure a `Client`.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+impl<'a, C> Client<C>
+where
+    C: Connection + ResponseHeaders,
+    C::Error: HttpError,
+{
+    /// Constructs a new `Client` over the given `Connection`.
+    pub fn with_connection(connection: C) -> Self {
+        Client {
+            client: HttpClient::wrap(connection),
+            headers: HeaderMap::new(),
+            retry: RetryPolicy::none(),
+            redirect: RedirectPolicy::default(),
+            signer: None,
+            connector: None,
+        }
+    }
+
+    /// Get the headers added to every request.
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the `RetryPolicy` of requests that do not set their own.
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Get the `RedirectPolicy` of this client.
+    pub fn redirect(&self) -> &RedirectPolicy {
+        &self.redirect
+    }
+
+    /// Get a mutable reference to the underlying `Connection`.
+    pub fn connection(&mut self) -> &mut C {
+        self.client.connection()
     }
 
     /// Start building a `Request` with the `Method` and url.
-    pub fn request(&'a mut self, method: Method, url: &'a str) -> RequestBuilder {
+    pub fn request<U: IntoUrl>(&'a mut self, method: Method, url: U) -> RequestBuilder<'a, C> {
         RequestBuilder::new(self, method, url)
     }
 
     /// Convenience method to make a `GET` request to a URL.
-    pub fn get(&'a mut self, url: &'a str) -> RequestBuilder {
+    pub fn get<U: IntoUrl>(&'a mut self, url: U) -> RequestBuilder<'a, C> {
         self.request(Method::Get, url)
     }
 
     /// Convenience method to make a `POST` request to a URL.
-    pub fn post(&'a mut self, url: &'a str) -> RequestBuilder {
+    pub fn post<U: IntoUrl>(&'a mut self, url: U) -> RequestBuilder<'a, C> {
         self.request(Method::Post, url)
     }
 
     /// Convenience method to make a `PUT` request to a URL.
-    pub fn put(&'a mut self, url: &'a str) -> RequestBuilder {
+    pub fn put<U: IntoUrl>(&'a mut self, url: U) -> RequestBuilder<'a, C> {
         self.request(Method::Put, url)
     }
 
     /// Convenience method to make a `DELETE` request to a URL.
-    pub fn delete(&'a mut self, url: &'a str) -> RequestBuilder {
+    pub fn delete<U: IntoUrl>(&'a mut self, url: U) -> RequestBuilder<'a, C> {
         self.request(Method::Delete, url)
     }
 
@@ -50,21 +132,409 @@ impl<'a> Client {
     ///
     /// You should prefer to use the `RequestBuilder` and
     /// `RequestBuilder::send()`.
-    pub fn execute(&'a mut self, request: &'a Request) -> Result<Response> {
-        Response::new(&mut self.client, request)
+    ///
+    /// Redirects are followed according to the `RedirectPolicy` of the client,
+    /// and each request is sent again according to its `RetryPolicy`. The
+    /// `Signer` of the client signs the request, and each redirect within its origin.
+    pub fn execute(&'a mut self, request: &Request) -> Result<Response<'a, C>> {
+        let Self {
+            client,
+            headers: default_headers,
+            retry,
+            redirect: redirect_policy,
+            signer,
+            connector,
+        } = self;
+        let retry = request.retry.as_ref().unwrap_or(retry);
+
+        let mut current = Cow::Borrowed(request);
+        let mut hops = 0;
+        loop {
+            if let Some(signer) = signer {
+                if current.url.origin() == request.url.origin() {
+                    signer.sign(current.to_mut())?;
+                }
+            }
+
+            let headers = header_pairs(&current.headers, default_headers)?;
+            let status = Self::send_with_retry(client, connector, retry, &current, &headers)?;
+            if !redirect::is_redirect(status) {
+                break;
+            }
+
+            let Some(location) = client.connection().header(LOCATION.as_str()) else {
+                break;
+            };
+            let next = match current.url.join(location) {
+                Ok(next) => next,
+                Err(err) => {
+                    log::warn!("Invalid redirect location `{location}`: {err}");
+                    break;
+                }
+            };
+            if !redirect_policy.check(hops, &current.url, &next)? {
+                break;
+            }
+
+            log::debug!("Redirected ({status}) from {} to {next}", current.url);
+            discard_body(client.connection());
+            current = Cow::Owned(redirect::follow(&current, status, next));
+            hops += 1;
+        }
+
+        Ok(Response::new(client.connection(), current.url.clone()))
     }
 
-    /// Create a new `HttpClient` with a `EspHttpConnection` handler.
-    fn create_http_client() -> Result<HttpClient<EspHttpConnection>> {
-        // Create HTTPS Connection Handler
-        let connection = EspHttpConnection::new(&HttpConfig {
+    /// Send `request`, sending it again according to `policy`.
+    ///
+    /// Once the attempts run out, returns the last response status, or a
+    /// request error reporting the number of attempts.
+    fn send_with_retry(
+        client: &mut HttpClient<C>,
+        connector: &mut Option<Connector<C>>,
+        policy: &RetryPolicy,
+        request: &Request,
+        headers: &[(&str, &str)],
+    ) -> Result<u16> {
+        let mut attempt = 1;
+        loop {
+            match Self::send_once(client, request, headers) {
+                Ok(status)
+                    if policy.retries_status(status)
+                        && policy.can_retry(request.method, attempt) =>
+                {
+                    log::warn!("Attempt {attempt} to {} returned {status}", request.url);
+                    discard_body(client.connection());
+                }
+                Ok(status) => return Ok(status),
+                Err(err) => {
+                    let recovered = Self::recover(client, connector);
+                    if !recovered || !policy.can_retry(request.method, attempt) {
+                        return Err(crate::error::request(err, request.url.as_str(), attempt));
+                    }
+                    log::warn!("Attempt {attempt} to {} failed: {err}", request.url);
+                }
+            }
+
+            std::thread::sleep(policy.delay(attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Send `request` once, returning the response status.
+    fn send_once(
+        client: &mut HttpClient<C>,
+        request: &Request,
+        headers: &[(&str, &str)],
+    ) -> Result<u16, C::Error> {
+        let connection = client.connection();
+        connection.initiate_request(request.method, request.url.as_str(), headers)?;
+
+        if let Some(data) = &request.body {
+            log::debug!("Adding data to request: {} bytes", data.len());
+            connection.write_all(data)?;
+            connection.flush()?;
+        }
+
+        connection.initiate_response()?;
+        Ok(connection.status())
+    }
+
+    /// Make the connection usable again after a failed request.
+    ///
+    /// An `EspHttpConnection` stays in the request phase when sending fails, and
+    /// panics on the next request. Such a connection is replaced by a new one.
+    fn recover(client: &mut HttpClient<C>, connector: &mut Option<Connector<C>>) -> bool {
+        if !client.connection().is_request_initiated() {
+            return true;
+        }
+
+        match connector.as_mut().map(|connect| connect()) {
+            Some(Ok(connection)) => {
+                *client = HttpClient::wrap(connection);
+                true
+            }
+            Some(Err(err)) => {
+                log::error!("Cannot open a new connection: {err}");
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Flatten the headers of a request, followed by the default headers it does not set.
+///
+/// Fails with a builder error if a value is not visible ASCII.
+fn header_pairs<'h>(
+    headers: &'h HeaderMap,
+    default_headers: &'h HeaderMap,
+) -> Result<Vec<(&'h str, &'h str)>> {
+    let defaults = default_headers
+        .iter()
+        .filter(|(name, _)| !headers.contains_key(*name));
+
+    headers
+        .iter()
+        .chain(defaults)
+        .map(|(name, value)| {
+            let value = value.to_str().map_err(crate::error::builder)?;
+            Ok((name.as_str(), value))
+        })
+        .collect()
+}
+
+/// Read and drop the body of a response that is not returned to the caller.
+fn discard_body<C: Connection>(connection: &mut C) {
+    let mut buf = [0u8; 64];
+    let mut remaining = DEFAULT_BODY_LIMIT;
+    while remaining > 0 {
+        match connection.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => remaining = remaining.saturating_sub(size),
+        }
+    }
+}
+
+/// A `ClientBuilder` can be used to create a `Client` with custom configuration.
+///
+/// Timeouts only apply to the `DefaultConnection` created by
+/// `ClientBuilder::build()`, and buffer sizes and TLS settings only exist on the
+/// device. Default headers apply to any `Connection`.
+#[must_use]
+pub struct ClientBuilder {
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    #[cfg(target_os = "espidf")]
+    buffer_size: Option<usize>,
+    #[cfg(target_os = "espidf")]
+    buffer_size_tx: Option<usize>,
+    #[cfg(target_os = "espidf")]
+    root_certs: Vec<X509<'static>>,
+    #[cfg(target_os = "espidf")]
+    identity: Option<(X509<'static>, X509<'static>)>,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+    redirect: RedirectPolicy,
+    signer: Option<Box<dyn Signer>>,
+    error: Option<anyhow::Error>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    /// Constructs a new `ClientBuilder` with the defaults used by `Client::new()`.
+    pub fn new() -> Self {
+        Self {
+            connect_timeout: None,
+            timeout: None,
+            #[cfg(target_os = "espidf")]
+            buffer_size: None,
+            #[cfg(target_os = "espidf")]
+            buffer_size_tx: None,
+            #[cfg(target_os = "espidf")]
+            root_certs: vec![],
+            #[cfg(target_os = "espidf")]
+            identity: None,
+            headers: HeaderMap::new(),
+            retry: RetryPolicy::none(),
+            redirect: RedirectPolicy::default(),
+            signer: None,
+            error: None,
+        }
+    }
+
+    /// Set a timeout for connecting to the server.
+    ///
+    /// ESP-IDF only has a single network timeout, so the larger of this and
+    /// `ClientBuilder::timeout()` is used for both.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for each read and write on the connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the size of the buffer used to receive response headers.
+    #[cfg(target_os = "espidf")]
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = Some(size);
+        self
+    }
+
+    /// Set the size of the buffer used to send request headers.
+    #[cfg(target_os = "espidf")]
+    pub fn buffer_size_tx(mut self, size: usize) -> Self {
+        self.buffer_size_tx = Some(size);
+        self
+    }
+
+    /// Trust a PEM encoded root certificate.
+    ///
+    /// Once a certificate is added, the built-in CA bundle is no longer trusted:
+    /// the server must present a chain ending in one of the added certificates.
+    /// Adding the backend's self-signed certificate pins the client to it.
+    ///
+    /// The certificates are loaded into the ESP-TLS global CA store, which is
+    /// shared with every other client using it.
+    #[cfg(target_os = "espidf")]
+    pub fn add_root_certificate(mut self, cert: X509<'static>) -> Self {
+        self.root_certs.push(cert);
+        self
+    }
+
+    /// Set the PEM encoded certificate and private key presented to the server
+    /// for mutual TLS.
+    #[cfg(target_os = "espidf")]
+    pub fn identity(mut self, cert: X509<'static>, key: X509<'static>) -> Self {
+        self.identity = Some((cert, key));
+        self
+    }
+
+    /// Set the default headers for every request.
+    ///
+    /// Headers set on a `RequestBuilder` take precedence.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        for (key, value) in headers.iter() {
+            self.headers.insert(key, value.clone());
+        }
+        self
+    }
+
+    /// Set the `RetryPolicy` of requests that do not set their own.
+    ///
+    /// Requests are not retried by default.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Set the `RedirectPolicy` of the client.
+    pub fn redirect(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect = policy;
+        self
+    }
+
+    /// Set the `Signer` run on every request right before it is sent.
+    pub fn signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Box::new(signer));
+        self
+    }
+
+    /// Set the `User-Agent` header sent with every request.
+    pub fn user_agent(self, value: impl Display) -> Self {
+        self.default_header(USER_AGENT, value)
+    }
+
+    /// Set the `X-Device-Id` header sent with every request.
+    pub fn device_id(self, id: impl Display) -> Self {
+        self.default_header(DEVICE_ID, id)
+    }
+
+    fn default_header(mut self, key: HeaderName, value: impl Display) -> Self {
+        match HeaderValue::from_str(&value.to_string()) {
+            Ok(value) => {
+                self.headers.insert(key, value);
+            }
+            Err(err) => self.error = Some(crate::error::builder(err)),
+        }
+        self
+    }
+
+    /// Returns a `Client` over an `EspHttpConnection` using this configuration.
+    #[cfg(target_os = "espidf")]
+    pub fn build(self) -> Result<Client> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let timeout = match (self.connect_timeout, self.timeout) {
+            (Some(connect), Some(read)) => Some(connect.max(read)),
+            (connect, read) => connect.or(read),
+        };
+
+        let (client_certificate, private_key) = self.identity.unzip();
+
+        let use_bundle = self.root_certs.is_empty();
+        if !use_bundle {
+            Self::load_root_certificates(&self.root_certs)?;
+        }
+
+        let config = HttpConfig {
+            buffer_size: self.buffer_size,
+            buffer_size_tx: self.buffer_size_tx,
+            timeout,
+            // Redirects are followed by the `Client`, according to its `RedirectPolicy`.
+            follow_redirects_policy: FollowRedirectsPolicy::FollowNone,
             use_global_ca_store: true,
-            crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+            crt_bundle_attach: if use_bundle {
+                Some(esp_idf_svc::sys::esp_crt_bundle_attach)
+            } else {
+                None
+            },
+            client_certificate,
+            private_key,
             ..Default::default()
-        })?;
+        };
+
+        // Create HTTPS Connection Handler
+        let connection = EspHttpConnection::new(&config)?;
+
+        Ok(Client {
+            client: HttpClient::wrap(connection),
+            headers: self.headers,
+            retry: self.retry,
+            redirect: self.redirect,
+            signer: self.signer,
+            connector: Some(Box::new(move || Ok(EspHttpConnection::new(&config)?))),
+        })
+    }
+
+    /// Returns a `Client` over a `TcpConnection` using the timeouts of this
+    /// configuration.
+    #[cfg(not(target_os = "espidf"))]
+    pub fn build(self) -> Result<Client> {
+        let connection = TcpConnection::with_timeouts(self.connect_timeout, self.timeout);
+        self.build_with(connection)
+    }
+
+    /// Returns a `Client` over the given `Connection`, with the default headers
+    /// of this configuration.
+    pub fn build_with<C>(self, connection: C) -> Result<Client<C>>
+    where
+        C: Connection + ResponseHeaders,
+        C::Error: HttpError,
+    {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        Ok(Client {
+            client: HttpClient::wrap(connection),
+            headers: self.headers,
+            retry: self.retry,
+            redirect: self.redirect,
+            signer: self.signer,
+            connector: None,
+        })
+    }
 
-        // Create HTTPS Client
-        let client = HttpClient::wrap(connection);
-        Ok(client)
+    /// Replace the contents of the ESP-TLS global CA store with `certs`.
+    #[cfg(target_os = "espidf")]
+    fn load_root_certificates(certs: &[X509<'static>]) -> Result<()> {
+        unsafe { esp_tls_free_global_ca_store() };
+        for cert in certs {
+            let data = cert.data();
+            esp!(unsafe { esp_tls_set_global_ca_store(data.as_ptr(), data.len() as u32) })?;
+        }
+        Ok(())
     }
 }
@@ -14,6 +14,7 @@ struct Inner<E: HttpError> {
     kind: Kind,
     source: Option<E>,
     url: Option<String>,
+    attempts: Option<u32>,
 }
 
 impl<E: HttpError> Error<E> {
@@ -21,8 +22,9 @@ impl<E: HttpError> Error<E> {
         Error {
             inner: Box::new(Inner {
                 kind,
-                source: source.map(Into::into),
+                source,
                 url: None,
+                attempts: None,
             }),
         }
     }
@@ -53,6 +55,18 @@ impl<E: HttpError> Error<E> {
         self
     }
 
+    /// Returns the number of times the request was sent before giving up, if
+    /// the error was generated while sending it.
+    pub fn attempts(&self) -> Option<u32> {
+        self.inner.attempts
+    }
+
+    /// Add the number of times the request was sent (overwriting any existing)
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.inner.attempts = Some(attempts);
+        self
+    }
+
     /// Returns true if the error is from a type Builder.
     pub fn is_builder(&self) -> bool {
         matches!(self.inner.kind, Kind::Builder)
@@ -101,6 +115,9 @@ impl<E: HttpError> fmt::Debug for Error<E> {
         if let Some(ref url) = self.inner.url {
             builder.field("url", url);
         }
+        if let Some(attempts) = self.inner.attempts {
+            builder.field("attempts", &attempts);
+        }
         if let Some(ref source) = self.inner.source {
             builder.field("source", source);
         }
@@ -133,6 +150,13 @@ impl<E: HttpError> fmt::Display for Error<E> {
             write!(f, " for url ({})", url.as_str())?;
         }
 
+        if let Some(attempts) = self.inner.attempts {
+            write!(f, " after {attempts} attempt")?;
+            if attempts != 1 {
+                f.write_str("s")?;
+            }
+        }
+
         if let Some(e) = &self.inner.source {
             write!(f, ": {e}")?;
         }
@@ -165,27 +189,80 @@ pub(crate) fn builder(e: impl HttpError) -> anyhow::Error {
     anyhow::Error::new(Error::new(Kind::Builder, Some(e)))
 }
 
-#[allow(dead_code)]
 pub(crate) fn body(e: impl HttpError) -> anyhow::Error {
     anyhow::Error::new(Error::new(Kind::Body, Some(e)))
 }
 
+pub(crate) fn body_too_large(url: impl Into<String>, limit: usize) -> anyhow::Error {
+    anyhow::Error::new(Error::new(Kind::Body, Some(BodyTooLarge(limit))).with_url(url.into()))
+}
+
 pub(crate) fn decode(e: impl HttpError) -> anyhow::Error {
     anyhow::Error::new(Error::new(Kind::Decode, Some(e)))
 }
 
-#[allow(dead_code)]
-pub(crate) fn request(e: impl HttpError) -> anyhow::Error {
-    anyhow::Error::new(Error::new(Kind::Request, Some(e)))
+pub(crate) fn request(e: impl HttpError, url: impl Into<String>, attempts: u32) -> anyhow::Error {
+    anyhow::Error::new(
+        Error::new(Kind::Request, Some(e))
+            .with_url(url.into())
+            .with_attempts(attempts),
+    )
 }
 
-#[allow(dead_code)]
 pub(crate) fn redirect(e: impl HttpError, url: impl Into<String>) -> anyhow::Error {
     anyhow::Error::new(Error::new(Kind::Redirect, Some(e)).with_url(url.into()))
 }
 
+pub(crate) fn url_bad_scheme(url: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(Error::new(Kind::Builder, Some(BadScheme)).with_url(url.into()))
+}
+
 pub(crate) fn status_code(url: impl Into<String>, status: StatusCode) -> anyhow::Error {
     anyhow::Error::new(
         Error::new(Kind::Status(status), None::<hyper::http::Error>).with_url(url.into()),
     )
 }
+
+#[derive(Debug)]
+pub struct BadScheme;
+
+impl fmt::Display for BadScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("URL scheme is not allowed")
+    }
+}
+
+impl StdError for BadScheme {}
+
+#[derive(Debug)]
+pub struct BodyTooLarge(pub usize);
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "body is larger than the limit of {} bytes", self.0)
+    }
+}
+
+impl StdError for BodyTooLarge {}
+
+#[derive(Debug)]
+pub struct TooManyRedirects(pub usize);
+
+impl fmt::Display for TooManyRedirects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "too many redirects, the limit is {}", self.0)
+    }
+}
+
+impl StdError for TooManyRedirects {}
+
+#[derive(Debug)]
+pub struct CrossOriginRedirect;
+
+impl fmt::Display for CrossOriginRedirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("redirect to another origin is not allowed")
+    }
+}
+
+impl StdError for CrossOriginRedirect {}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use url::Url;
+
+/// A trait to try to convert some type into a `Url`.
+///
+/// Only `http` and `https` URLs are accepted.
+pub trait IntoUrl {
+    fn into_url(self) -> Result<Url>;
+}
+
+impl IntoUrl for Url {
+    fn into_url(self) -> Result<Url> {
+        if self.has_host() && matches!(self.scheme(), "http" | "https") {
+            Ok(self)
+        } else {
+            Err(crate::error::url_bad_scheme(self))
+        }
+    }
+}
+
+impl IntoUrl for &str {
+    fn into_url(self) -> Result<Url> {
+        Url::parse(self).map_err(crate::error::builder)?.into_url()
+    }
+}
+
+impl IntoUrl for &String {
+    fn into_url(self) -> Result<Url> {
+        (&**self).into_url()
+    }
+}
+
+impl IntoUrl for String {
+    fn into_url(self) -> Result<Url> {
+        (&*self).into_url()
+    }
+}
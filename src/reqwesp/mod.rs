@@ -1,10 +1,24 @@
 mod client;
-pub use client::Client;
+pub use client::{Client, ClientBuilder, DEVICE_ID};
+
+mod into_url;
+pub use into_url::IntoUrl;
+pub use url::Url;
+
+mod redirect;
+pub use redirect::RedirectPolicy;
 
 mod request;
 pub use request::{Request, RequestBuilder};
 
 mod response;
-pub use response::Response;
+pub use response::{Response, DEFAULT_BODY_LIMIT};
+
+mod retry;
+pub use retry::RetryPolicy;
 
 pub mod error;
+
+pub mod sign;
+
+pub mod transport;
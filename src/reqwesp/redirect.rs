@@ -0,0 +1,194 @@
+use anyhow::Result;
+use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, PROXY_AUTHORIZATION};
+use hyper::StatusCode;
+
+use embedded_svc::http::Method;
+
+use crate::reqwesp::error::{BadScheme, CrossOriginRedirect, TooManyRedirects};
+use crate::reqwesp::sign;
+use crate::{Request, Url};
+
+/// Default maximum number of redirects followed by a `Client`.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Decides whether a `Client` follows redirects.
+///
+/// A redirect is followed when the response status is `301`, `302`, `303`, `307`
+/// or `308` and it has a `Location` header. `303` switches the request to a `GET`
+/// without a body, as do `301` and `302` for a `POST`.
+///
+/// The default policy follows up to 10 redirects.
+#[derive(Clone, Debug)]
+pub struct RedirectPolicy {
+    inner: Policy,
+}
+
+#[derive(Clone, Debug)]
+enum Policy {
+    None,
+    Limited(usize),
+    SameOrigin(usize),
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::limited(DEFAULT_MAX_REDIRECTS)
+    }
+}
+
+impl RedirectPolicy {
+    /// Constructs a `RedirectPolicy` that never follows redirects.
+    ///
+    /// The redirect response is returned as-is.
+    pub fn none() -> Self {
+        Self {
+            inner: Policy::None,
+        }
+    }
+
+    /// Constructs a `RedirectPolicy` following at most `max` redirects.
+    ///
+    /// Fails with a redirect error once the limit is reached.
+    pub fn limited(max: usize) -> Self {
+        Self {
+            inner: Policy::Limited(max),
+        }
+    }
+
+    /// Constructs a `RedirectPolicy` following at most `max` redirects, all
+    /// within the origin of the original request.
+    ///
+    /// Fails with a redirect error on a redirect to another scheme, host or port.
+    pub fn same_origin(max: usize) -> Self {
+        Self {
+            inner: Policy::SameOrigin(max),
+        }
+    }
+
+    /// Returns true if the redirect from `previous` to `next`, after `hops`
+    /// redirects were already followed, should be followed.
+    ///
+    /// Fails with a redirect error if `next` is not an `http` or `https` URL.
+    pub(crate) fn check(&self, hops: usize, previous: &Url, next: &Url) -> Result<bool> {
+        let max = match self.inner {
+            Policy::None => return Ok(false),
+            Policy::Limited(max) => max,
+            Policy::SameOrigin(max) => {
+                if next.origin() != previous.origin() {
+                    return Err(crate::error::redirect(CrossOriginRedirect, next.as_str()));
+                }
+                max
+            }
+        };
+
+        if !next.has_host() || !matches!(next.scheme(), "http" | "https") {
+            return Err(crate::error::redirect(BadScheme, next.as_str()));
+        }
+        if hops >= max {
+            Err(crate::error::redirect(TooManyRedirects(max), next.as_str()))
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+/// Returns true if `status` is a redirect that can be followed.
+pub(crate) fn is_redirect(status: u16) -> bool {
+    matches!(
+        StatusCode::from_u16(status),
+        Ok(StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT)
+    )
+}
+
+/// Builds the request sent to `url` after `request` was answered with the redirect `status`.
+pub(crate) fn follow(request: &Request, status: u16, url: Url) -> Request {
+    let mut next = request.clone();
+
+    let to_get = match status {
+        303 => request.method != Method::Head,
+        301 | 302 => request.method == Method::Post,
+        _ => false,
+    };
+    if to_get {
+        next.method = Method::Get;
+        next.body = None;
+        next.headers.remove(CONTENT_LENGTH);
+        next.headers.remove(CONTENT_TYPE);
+    }
+
+    // Do not leak credentials to another origin.
+    if url.origin() != request.url.origin() {
+        next.headers.remove(AUTHORIZATION);
+        next.headers.remove(COOKIE);
+        next.headers.remove(PROXY_AUTHORIZATION);
+        next.headers.remove(sign::KEY_ID);
+        next.headers.remove(sign::TIMESTAMP);
+        next.headers.remove(sign::SIGNATURE);
+    }
+
+    next.url = url;
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_svc::http::Method;
+
+    use super::RedirectPolicy;
+    use crate::reqwesp::error::{BadScheme, Error};
+    use crate::reqwesp::transport::{MockConnection, MockResponse};
+    use crate::reqwesp::Client;
+
+    #[test]
+    fn follows_see_other_with_a_get() {
+        let mock = MockConnection::new();
+        mock.push_response(MockResponse::new(303).header("Location", "/done"));
+        mock.push_response(MockResponse::new(200).body("ok"));
+
+        let mut client = Client::with_connection(mock.clone());
+        let response = client
+            .post("http://lock.local/start")
+            .body(b"data")
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.url().as_str(), "http://lock.local/done");
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, Method::Get);
+        assert!(requests[1].body.is_empty());
+    }
+
+    #[test]
+    fn redirect_to_another_scheme_is_a_redirect_error() {
+        let mock = MockConnection::new();
+        mock.push_response(MockResponse::new(302).header("Location", "ftp://lock.local/file"));
+
+        let mut client = Client::with_connection(mock.clone());
+        let Err(err) = client.get("http://lock.local/start").send() else {
+            panic!("redirect to ftp was followed");
+        };
+        let err = err.downcast_ref::<Error<BadScheme>>().unwrap();
+        assert!(err.is_redirect());
+        assert_eq!(err.url().unwrap(), "ftp://lock.local/file");
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[test]
+    fn none_returns_the_redirect() {
+        let mock = MockConnection::new();
+        mock.push_response(MockResponse::new(302).header("Location", "ftp://lock.local/file"));
+
+        let mut client = Client::builder()
+            .redirect(RedirectPolicy::none())
+            .build_with(mock)
+            .unwrap();
+        let response = client.get("http://lock.local/start").send().unwrap();
+        assert_eq!(response.status(), 302);
+    }
+}
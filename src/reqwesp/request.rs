@@ -1,29 +1,34 @@
-use anyhow::{bail, Result};
+use anyhow::Result;
 use hyper::body::Bytes;
 use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
-use hyper::http::HeaderName;
+use hyper::http::{self, HeaderName};
 use hyper::HeaderMap;
 use serde::Serialize;
 
+use embedded_svc::http::client::Connection;
 use embedded_svc::http::Method;
 
-use crate::{Client, Response};
+use crate::reqwesp::error::HttpError;
+use crate::reqwesp::transport::{DefaultConnection, ResponseHeaders};
+use crate::{Client, IntoUrl, Response, RetryPolicy, Url};
 
 #[derive(Clone)]
-pub struct Request<'a> {
+pub struct Request {
     pub(crate) method: Method,
-    pub(crate) url: &'a str,
-    pub(crate) headers: Vec<(&'a str, &'a str)>,
+    pub(crate) url: Url,
+    pub(crate) headers: HeaderMap,
     pub(crate) body: Option<Bytes>,
+    pub(crate) retry: Option<RetryPolicy>,
 }
 
-impl<'a> Request<'a> {
-    pub fn new(method: Method, url: &'a str) -> Self {
+impl Request {
+    pub fn new(method: Method, url: Url) -> Self {
         Self {
             method,
             url,
-            headers: vec![],
+            headers: HeaderMap::new(),
             body: None,
+            retry: None,
         }
     }
 
@@ -38,22 +43,27 @@ impl<'a> Request<'a> {
     }
 
     /// Get the url.
-    pub fn url(&self) -> &str {
-        self.url
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get a mutable reference to the url.
+    pub fn url_mut(&mut self) -> &mut Url {
+        &mut self.url
     }
 
     /// Set a new url.
-    pub fn set_url(&mut self, url: &'a str) {
+    pub fn set_url(&mut self, url: Url) {
         self.url = url
     }
 
     /// Get the headers.
-    pub fn headers(&self) -> &Vec<(&'a str, &'a str)> {
+    pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 
     /// Get a mutable reference to the headers.
-    pub fn headers_mut(&mut self) -> &mut Vec<(&'a str, &'a str)> {
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
         &mut self.headers
     }
 
@@ -66,84 +76,166 @@ impl<'a> Request<'a> {
     pub fn body_mut(&mut self) -> &mut Option<Bytes> {
         &mut self.body
     }
+
+    /// Get the `RetryPolicy` overriding the one of the `Client`.
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    /// Get a mutable reference to the `RetryPolicy` overriding the one of the `Client`.
+    pub fn retry_mut(&mut self) -> &mut Option<RetryPolicy> {
+        &mut self.retry
+    }
 }
 
-pub struct RequestBuilder<'a> {
-    client: &'a mut Client,
-    headers: HeaderMap,
-    request: Result<Request<'a>>,
+pub struct RequestBuilder<'a, C = DefaultConnection> {
+    client: &'a mut Client<C>,
+    request: Result<Request>,
 }
 
-impl<'a> RequestBuilder<'a> {
-    pub fn new(client: &'a mut Client, method: Method, url: &'a str) -> Self {
+impl<'a, C> RequestBuilder<'a, C>
+where
+    C: Connection + ResponseHeaders,
+    C::Error: HttpError,
+{
+    pub fn new<U: IntoUrl>(client: &'a mut Client<C>, method: Method, url: U) -> Self {
         Self {
             client,
-            headers: HeaderMap::new(),
-            request: Ok(Request::new(method, url)),
+            request: url.into_url().map(|url| Request::new(method, url)),
         }
     }
 
     /// Constructs the `Request` and sends it to the target URL, returning a `Response`.
-    pub fn send(&'a mut self) -> Result<Response> {
-        match &mut self.request {
-            Ok(ref mut request) => {
-                // Convert from HeaderMap to vec
-                request.headers = self
-                    .headers
-                    .iter()
-                    .map(|(key, value)| (key.as_str(), value.to_str().unwrap()))
-                    .collect();
-
-                self.client.execute(request)
-            }
-            Err(err) => bail!(err.to_string()),
-        }
+    pub fn send(self) -> Result<Response<'a, C>> {
+        let request = self.request?;
+        self.client.execute(&request)
     }
 
     /// Build a `Request`, which can be inspected, modified and executed with
     /// `Client::execute()`.
-    pub fn build(&'a mut self) -> Result<Request> {
-        match &mut self.request {
-            Ok(ref mut request) => {
-                // Convert from HeaderMap to vec
-                request.headers = self
-                    .headers
-                    .iter()
-                    .map(|(key, value)| (key.as_str(), value.to_str().unwrap()))
-                    .collect();
-
-                Ok(request.clone())
-            }
-            Err(err) => bail!(err.to_string()),
-        }
+    pub fn build(self) -> Result<Request> {
+        self.request
     }
 
     /// Add a header to this request.
-    pub fn header(mut self, key: HeaderName, value: HeaderValue) -> Self {
-        self.headers.insert(key, value);
+    ///
+    /// The header is appended, so adding the same header several times sends
+    /// all of its values. Fails with a builder error if the name or value is invalid.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        let mut error = None;
+        if let Ok(ref mut req) = self.request {
+            match <HeaderName as TryFrom<K>>::try_from(key) {
+                Ok(key) => match <HeaderValue as TryFrom<V>>::try_from(value) {
+                    Ok(value) => {
+                        req.headers.append(key, value);
+                    }
+                    Err(err) => error = Some(crate::error::builder(err.into())),
+                },
+                Err(err) => error = Some(crate::error::builder(err.into())),
+            }
+        }
+        if let Some(err) = error {
+            self.request = Err(err);
+        }
         self
     }
 
     /// Add multiple headers to this request.
-    pub fn headers(mut self, headers: Vec<(HeaderName, HeaderValue)>) -> Self {
-        for (key, value) in headers.into_iter() {
-            self.headers.insert(key, value);
+    ///
+    /// Each header replaces all previous values of the same name.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        if let Ok(ref mut req) = self.request {
+            // Only the first value of each header comes with its name.
+            let mut previous = None;
+            for (key, value) in headers {
+                match key {
+                    Some(key) => {
+                        req.headers.insert(key.clone(), value);
+                        previous = Some(key);
+                    }
+                    None => {
+                        if let Some(ref key) = previous {
+                            req.headers.append(key, value);
+                        }
+                    }
+                }
+            }
         }
         self
     }
 
     /// Add a body to this request.
     pub fn body(mut self, data: &[u8]) -> Self {
-        let content_length_header = data.len().to_string();
         if let Ok(ref mut req) = self.request {
+            req.headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(data.len()));
             req.body = Some(Bytes::copy_from_slice(data));
         }
-        self.header(CONTENT_LENGTH, content_length_header.parse().unwrap())
+        self
     }
 
-    // TODO Add method to modify the query string of the URL.
-    //  Consider finding an external crate for parsing and handling URLs.
-    // pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {}
+    /// Set the `RetryPolicy` of this request, overriding the one of the `Client`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        if let Ok(ref mut req) = self.request {
+            req.retry = Some(policy);
+        }
+        self
+    }
+
+    /// Modify the query string of the URL.
+    ///
+    /// Modifies the URL of this request, adding the parameters provided.
+    /// This method appends and does not overwrite. This means that it can
+    /// be called multiple times and that existing query parameters are not
+    /// overwritten if the same key is used. The key will simply show up
+    /// twice in the query string.
+    /// Calling `.query(&[("foo", "a"), ("foo", "b")])` gives `"foo=a&foo=b"`.
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        let mut error = None;
+        if let Ok(ref mut req) = self.request {
+            let url = req.url_mut();
+            let mut pairs = url.query_pairs_mut();
+            let serializer = serde_urlencoded::Serializer::new(&mut pairs);
+
+            if let Err(err) = query.serialize(serializer) {
+                error = Some(crate::error::builder(err));
+            }
+        }
+        if let Ok(ref mut req) = self.request {
+            if let Some("") = req.url().query() {
+                req.url_mut().set_query(None);
+            }
+        }
+        if let Some(err) = error {
+            self.request = Err(err);
+        }
+        self
+    }
+
+    /// Append path segments to the URL, percent-encoding each of them.
+    ///
+    /// A trailing slash in the URL is dropped first, so
+    /// `client.get("https://host/api/").path_segments(["a b", "1"])` requests
+    /// `"https://host/api/a%20b/1"`.
+    pub fn path_segments<I>(mut self, segments: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        if let Ok(ref mut req) = self.request {
+            // Only fails for cannot-be-a-base URLs, which `IntoUrl` already rejects.
+            if let Ok(mut path) = req.url_mut().path_segments_mut() {
+                path.pop_if_empty().extend(segments);
+            }
+        }
+        self
+    }
 
     /// Send a form body.
     pub fn form<T: Serialize + ?Sized>(mut self, form: &'a T) -> Self {
@@ -151,10 +243,9 @@ impl<'a> RequestBuilder<'a> {
         if let Ok(ref mut req) = self.request {
             match serde_urlencoded::to_string(form) {
                 Ok(body) => {
-                    let content_length_header = body.len().to_string();
-                    self.headers
-                        .insert(CONTENT_LENGTH, content_length_header.parse().unwrap());
-                    self.headers.insert(
+                    req.headers
+                        .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+                    req.headers.insert(
                         CONTENT_TYPE,
                         HeaderValue::from_static("application/x-www-form-urlencoded"),
                     );
@@ -175,11 +266,10 @@ impl<'a> RequestBuilder<'a> {
         if let Ok(ref mut req) = self.request {
             match serde_json::to_vec(json) {
                 Ok(body) => {
-                    let content_length_header = body.len().to_string();
-                    self.headers
-                        .insert(CONTENT_LENGTH, content_length_header.parse().unwrap());
-                    if !self.headers.contains_key(CONTENT_TYPE) {
-                        self.headers
+                    req.headers
+                        .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+                    if !req.headers.contains_key(CONTENT_TYPE) {
+                        req.headers
                             .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
                     }
                     req.body = Some(Bytes::from(body));
@@ -193,3 +283,59 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::reqwesp::transport::MockConnection;
+    use crate::reqwesp::Client;
+
+    #[test]
+    fn appends_the_query_to_an_existing_one() {
+        let mut client = Client::with_connection(MockConnection::new());
+        let request = client
+            .get("http://lock.local/api?door=1")
+            .query(&[("door", "2")])
+            .query(&[("source", "nfc")])
+            .build()
+            .unwrap();
+        assert_eq!(request.url().query(), Some("door=1&door=2&source=nfc"));
+
+        let request = client
+            .get("http://lock.local/api?door=1")
+            .query(&Vec::<(&str, &str)>::new())
+            .build()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "http://lock.local/api?door=1");
+    }
+
+    #[test]
+    fn encodes_reserved_characters_in_the_query() {
+        let mut client = Client::with_connection(MockConnection::new());
+        let request = client
+            .get("http://lock.local/api")
+            .query(&[("a&b=c", "d e&f=g#h"), ("ü", "100%")])
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().query(),
+            Some("a%26b%3Dc=d+e%26f%3Dg%23h&%C3%BC=100%25")
+        );
+        let pairs: Vec<_> = request.url().query_pairs().collect();
+        assert_eq!(pairs[0], ("a&b=c".into(), "d e&f=g#h".into()));
+    }
+
+    #[test]
+    fn encodes_reserved_characters_in_path_segments() {
+        let mut client = Client::with_connection(MockConnection::new());
+        let request = client
+            .get("http://lock.local/api/")
+            .path_segments(["a b", "c/d", "e?f#g", "100%"])
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://lock.local/api/a%20b/c%2Fd/e%3Ff%23g/100%25"
+        );
+        assert_eq!(request.url().query(), None);
+    }
+}
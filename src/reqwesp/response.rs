@@ -1,61 +1,64 @@
 use anyhow::Result;
 use encoding_rs::{Encoding, UTF_8};
 use hyper::body::Bytes;
-use hyper::header::HeaderValue;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::HeaderMap;
 use hyper::StatusCode;
 use mime::Mime;
 use serde::de::DeserializeOwned;
 
-use embedded_svc::http::client::{Client as HttpClient, Response as HttpResponse};
-use embedded_svc::io::Write;
-use esp_idf_svc::http::client::EspHttpConnection;
+use embedded_svc::http::client::{Connection, Response as HttpResponse};
+use embedded_svc::io::{ErrorType, Read};
 
-use crate::reqwesp::Request;
+use crate::reqwesp::error::HttpError;
+use crate::reqwesp::transport::{DefaultConnection, ResponseHeaders};
+use crate::reqwesp::Url;
 
-pub struct Response<'a> {
-    body: Bytes,
-    res: HttpResponse<&'a mut EspHttpConnection>,
-    url: &'a str,
-}
+/// Size of the chunks read from the connection by `Response::chunk()`.
+const CHUNK_SIZE: usize = 256;
 
-impl<'a> Response<'a> {
-    pub(crate) fn new(
-        client: &'a mut HttpClient<EspHttpConnection>,
-        request: &'a Request<'a>,
-    ) -> Result<Self> {
-        let mut req = client.request(request.method, request.url, request.headers.as_slice())?;
-
-        if let Some(data) = &request.body {
-            log::debug!("Adding data to request: {} bytes", data.len());
-            req.write_all(data)?;
-            req.flush()?;
-        }
+/// Maximum body size read by `Response::bytes()`, `Response::text()` and `Response::json()`.
+pub const DEFAULT_BODY_LIMIT: usize = 16 * 1024;
 
-        let mut response = Self {
-            body: Bytes::new(),
-            res: req.submit()?,
-            url: request.url,
-        };
-        response.read()?;
-        Ok(response)
-    }
+/// A `Response` to a submitted `Request`.
+///
+/// The body is not read until it is asked for, either in chunks with
+/// `Response::chunk()` and the `Read` implementation, or all at once with
+/// `Response::bytes()` and friends.
+pub struct Response<'a, C = DefaultConnection>
+where
+    C: Connection,
+{
+    res: HttpResponse<&'a mut C>,
+    url: Url,
+    headers: HeaderMap,
+}
 
-    // Read the `HttpResponse` into the `Response` body as `Bytes`.
-    fn read(&mut self) -> Result<()> {
-        // Use a vector so we don't need to know the max size of the response
-        let mut data = Vec::new();
-        let mut buf = [0u8; 256];
-        // Read into buffer and append to vector until the reader is empty
-        loop {
-            let size = self.res.read(&mut buf)?;
-            if size == 0 {
-                break;
+impl<'a, C> Response<'a, C>
+where
+    C: Connection + ResponseHeaders,
+    C::Error: HttpError,
+{
+    /// Wraps a `Connection` that received the response to `url`.
+    pub(crate) fn new(connection: &'a mut C, url: Url) -> Self {
+        let mut headers = HeaderMap::new();
+        for (name, value) in connection.response_headers() {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.append(name, value);
+                }
+                _ => log::error!("Ignoring invalid header: name=`{name}`, value=`{value}`"),
             }
-            data.extend_from_slice(&buf[..size])
         }
 
-        self.body = Bytes::from(data);
-        Ok(())
+        Self {
+            res: HttpResponse::wrap(connection),
+            url,
+            headers,
+        }
     }
 
     /// Get the `StatusCode` of this `Response`.
@@ -67,24 +70,58 @@ impl<'a> Response<'a> {
     }
 
     /// Get the final URL of this `Response`.
-    pub fn url(&self) -> &str {
-        self.url
+    pub fn url(&self) -> &Url {
+        &self.url
     }
 
-    /// Obtain the given header.
+    /// Get the `Headers` of this `Response`.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Obtain the first value of the given header.
+    ///
+    /// A header missing from `Response::headers()`, such as a custom header on
+    /// a `Connection` that only lists well-known headers, is looked up by name
+    /// on the connection instead.
     pub fn header(&self, name: &str) -> Option<HeaderValue> {
+        if let Some(value) = self.headers.get(name) {
+            return Some(value.clone());
+        }
+
         let raw_value = self.res.header(name)?;
         match HeaderValue::from_str(raw_value) {
-            Ok(header) => Some(header),
+            Ok(value) => Some(value),
             Err(_) => {
-                log::error!(
-                    "Header value for exists, but is invalid: name=`{name}`, value=`{raw_value}`"
-                );
+                log::error!("Ignoring invalid header: name=`{name}`, value=`{raw_value}`");
                 None
             }
         }
     }
 
+    /// Get the content length of the response, if it is known.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Stream a chunk of the response body.
+    ///
+    /// Returns `None` once the whole body has been read.
+    pub fn chunk(&mut self) -> Result<Option<Bytes>> {
+        let mut buf = [0u8; CHUNK_SIZE];
+        let size = self.res.read(&mut buf).map_err(crate::error::body)?;
+        if size == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Bytes::copy_from_slice(&buf[..size])))
+        }
+    }
+
     /// Get the full response text.
     pub fn text(self) -> Result<String> {
         self.text_with_charset("utf-8")
@@ -93,8 +130,8 @@ impl<'a> Response<'a> {
     /// Get the full response text given a specific encoding.
     pub fn text_with_charset(self, default_encoding: &str) -> Result<String> {
         let content_type = self
-            .header("Content-Type")
-            .as_ref()
+            .headers
+            .get(CONTENT_TYPE)
             .and_then(|value| value.to_str().ok())
             .and_then(|value| value.parse::<Mime>().ok());
 
@@ -104,7 +141,7 @@ impl<'a> Response<'a> {
             .unwrap_or(default_encoding);
         let encoding = Encoding::for_label(encoding_name.as_bytes()).unwrap_or(UTF_8);
 
-        let full = self.bytes();
+        let full = self.bytes()?;
 
         let (text, _, _) = encoding.decode(&full);
         Ok(text.into_owned())
@@ -112,20 +149,43 @@ impl<'a> Response<'a> {
 
     /// Try to deserialize the response body as JSON.
     pub fn json<T: DeserializeOwned>(self) -> Result<T> {
-        let full = self.bytes();
+        let full = self.bytes()?;
         serde_json::from_slice(&full).map_err(crate::error::decode)
     }
 
     /// Get the full response body as `Bytes`.
-    pub fn bytes(self) -> Bytes {
-        self.body
+    ///
+    /// Fails with a body error if the body is larger than `DEFAULT_BODY_LIMIT`.
+    pub fn bytes(self) -> Result<Bytes> {
+        self.bytes_limited(DEFAULT_BODY_LIMIT)
+    }
+
+    /// Get the full response body as `Bytes`, reading at most `max` bytes.
+    ///
+    /// Fails with a body error as soon as the body is known to be larger than `max`,
+    /// without reading the rest of it into memory.
+    pub fn bytes_limited(mut self, max: usize) -> Result<Bytes> {
+        if let Some(length) = self.content_length() {
+            if length > max as u64 {
+                return Err(crate::error::body_too_large(self.url.as_str(), max));
+            }
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = self.chunk()? {
+            if data.len() + chunk.len() > max {
+                return Err(crate::error::body_too_large(self.url.as_str(), max));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(Bytes::from(data))
     }
 
     /// Turn a response into an error if the server returned an error.
     pub fn error_for_status(self) -> Result<Self> {
         let status = self.status();
         if status.is_client_error() || status.is_server_error() {
-            Err(crate::error::status_code(self.url, status))
+            Err(crate::error::status_code(self.url.as_str(), status))
         } else {
             Ok(self)
         }
@@ -135,9 +195,110 @@ impl<'a> Response<'a> {
     pub fn error_for_status_ref(&self) -> Result<&Self> {
         let status = self.status();
         if status.is_client_error() || status.is_server_error() {
-            Err(crate::error::status_code(self.url, status))
+            Err(crate::error::status_code(self.url.as_str(), status))
         } else {
             Ok(self)
         }
     }
 }
+
+impl<'a, C> ErrorType for Response<'a, C>
+where
+    C: Connection,
+{
+    type Error = C::Error;
+}
+
+impl<'a, C> Read for Response<'a, C>
+where
+    C: Connection,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::result::Result<usize, Self::Error> {
+        self.res.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::SET_COOKIE;
+
+    use super::DEFAULT_BODY_LIMIT;
+    use crate::reqwesp::error::{BodyTooLarge, Error};
+    use crate::reqwesp::transport::{MockConnection, MockResponse};
+    use crate::reqwesp::Client;
+
+    fn body_of(response: MockResponse) -> anyhow::Result<usize> {
+        let mock = MockConnection::new();
+        mock.push_response(response);
+        let mut client = Client::with_connection(mock);
+        let response = client.get("http://lock.local/").send().unwrap();
+        Ok(response.bytes()?.len())
+    }
+
+    fn assert_too_large(result: anyhow::Result<usize>) {
+        let err = result.unwrap_err();
+        let err = err.downcast_ref::<Error<BodyTooLarge>>().unwrap();
+        assert!(err.is_body());
+        assert_eq!(err.url().unwrap(), "http://lock.local/");
+    }
+
+    #[test]
+    fn reads_a_body_exactly_at_the_limit() {
+        let body = vec![b'a'; DEFAULT_BODY_LIMIT];
+        assert_eq!(
+            body_of(MockResponse::new(200).body(body.clone())).unwrap(),
+            DEFAULT_BODY_LIMIT
+        );
+        assert_eq!(
+            body_of(MockResponse::new(200).chunked(body)).unwrap(),
+            DEFAULT_BODY_LIMIT
+        );
+    }
+
+    #[test]
+    fn refuses_a_body_announced_larger_than_the_limit() {
+        let response =
+            MockResponse::new(200).header("Content-Length", &(DEFAULT_BODY_LIMIT + 1).to_string());
+        assert_too_large(body_of(response));
+    }
+
+    #[test]
+    fn refuses_a_chunked_body_larger_than_the_limit() {
+        let body = vec![b'a'; DEFAULT_BODY_LIMIT + 1];
+        assert_too_large(body_of(MockResponse::new(200).chunked(body)));
+    }
+
+    #[test]
+    fn keeps_repeated_headers() {
+        let mock = MockConnection::new();
+        mock.push_response(
+            MockResponse::new(200)
+                .header("Set-Cookie", "a=1")
+                .header("Set-Cookie", "b=2"),
+        );
+
+        let mut client = Client::with_connection(mock);
+        let response = client.get("http://lock.local/").send().unwrap();
+        let cookies: Vec<_> = response.headers().get_all(SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(response.header("set-cookie").unwrap(), "a=1");
+    }
+
+    #[test]
+    fn looks_up_unlisted_headers_on_the_connection() {
+        let mock = MockConnection::new();
+        mock.known_headers_only();
+        mock.push_response(
+            MockResponse::new(200)
+                .header("Content-Type", "text/plain")
+                .header("X-Custom", "yes"),
+        );
+
+        let mut client = Client::with_connection(mock);
+        let response = client.get("http://lock.local/").send().unwrap();
+        assert_eq!(response.headers().len(), 1);
+        assert!(response.headers().get("x-custom").is_none());
+        assert_eq!(response.header("X-Custom").unwrap(), "yes");
+        assert!(response.header("X-Missing").is_none());
+    }
+}
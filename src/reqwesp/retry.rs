@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use hyper::StatusCode;
+
+use embedded_svc::http::Method;
+
+/// Delay before the first retry.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Upper bound of the delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Decides whether and when a failed request is sent again.
+///
+/// A request is retried when the connection fails, or when the server answers
+/// with one of the retry statuses (by default `429`, `502`, `503` and `504`).
+/// Only idempotent methods are retried unless `retry_non_idempotent()` is set,
+/// since a failed `POST` may still have reached the server.
+///
+/// The delay doubles after every attempt, up to a maximum. With jitter enabled,
+/// each delay is picked at random between half and all of it, so that locks
+/// recovering from the same outage do not retry in lockstep.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    statuses: Vec<StatusCode>,
+    non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Constructs a `RetryPolicy` sending a request at most `max_attempts` times.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+            jitter: true,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            non_idempotent: false,
+        }
+    }
+
+    /// Constructs a `RetryPolicy` that never retries.
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    /// Get the maximum number of times a request is sent.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Set the delay before the first retry, and the maximum delay between attempts.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Enable or disable randomizing the delay between attempts.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Set the response statuses that are retried, replacing the defaults.
+    pub fn retry_on<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Allow retrying methods that are not idempotent, such as `POST`.
+    pub fn retry_non_idempotent(mut self, enabled: bool) -> Self {
+        self.non_idempotent = enabled;
+        self
+    }
+
+    /// Returns true if a request with `method` can be sent again after `attempt` attempts.
+    pub(crate) fn can_retry(&self, method: Method, attempt: u32) -> bool {
+        attempt < self.max_attempts && (self.non_idempotent || is_idempotent(method))
+    }
+
+    /// Returns true if a response with `status` should be retried.
+    pub(crate) fn retries_status(&self, status: u16) -> bool {
+        self.statuses.iter().any(|code| code.as_u16() == status)
+    }
+
+    /// Time to wait after the given attempt failed, the first attempt being 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+
+        if self.jitter {
+            delay.mul_f32(0.5 + rand::random::<f32>() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Returns true if sending a request with `method` twice has the same effect as sending it once.
+fn is_idempotent(method: Method) -> bool {
+    matches!(
+        method,
+        Method::Get | Method::Head | Method::Put | Method::Delete | Method::Options | Method::Trace
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use embedded_svc::http::Method;
+
+    use super::RetryPolicy;
+    use crate::reqwesp::transport::{MockConnection, MockResponse};
+    use crate::reqwesp::Client;
+
+    fn fast(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(max_attempts).backoff(Duration::ZERO, Duration::ZERO)
+    }
+
+    #[test]
+    fn doubles_the_delay_up_to_the_maximum() {
+        let policy = RetryPolicy::new(10)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .jitter(false);
+        let delays: Vec<_> = (1..=5).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500].map(Duration::from_millis));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn keeps_jittered_delays_between_half_and_all_of_the_backoff() {
+        let policy =
+            RetryPolicy::new(10).backoff(Duration::from_millis(100), Duration::from_millis(500));
+        for attempt in 1..=6 {
+            let full =
+                Duration::from_millis(100 * (1 << (attempt - 1))).min(Duration::from_millis(500));
+            for _ in 0..100 {
+                let delay = policy.delay(attempt);
+                assert!(delay >= full / 2 && delay <= full, "{delay:?} for {full:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn stops_after_the_maximum_attempts() {
+        let policy = RetryPolicy::new(3);
+        assert!(policy.can_retry(Method::Get, 2));
+        assert!(!policy.can_retry(Method::Get, 3));
+        assert_eq!(RetryPolicy::new(0).max_attempts(), 1);
+
+        let mock = MockConnection::new();
+        for _ in 0..4 {
+            mock.push_response(MockResponse::new(503));
+        }
+        let mut client = Client::with_connection(mock.clone());
+        let response = client
+            .get("http://lock.local/")
+            .retry(fast(3))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[test]
+    fn does_not_retry_a_post_unless_allowed() {
+        let mock = MockConnection::new();
+        mock.push_response(MockResponse::new(503));
+        mock.push_error(io::ErrorKind::ConnectionReset);
+        let mut client = Client::with_connection(mock.clone());
+
+        let response = client
+            .post("http://lock.local/")
+            .retry(fast(3))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        assert!(client
+            .post("http://lock.local/")
+            .retry(fast(3))
+            .send()
+            .is_err());
+        assert_eq!(mock.requests().len(), 2);
+
+        mock.push_response(MockResponse::new(503));
+        mock.push_response(MockResponse::new(200));
+        let response = client
+            .post("http://lock.local/")
+            .retry(fast(3).retry_non_idempotent(true))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(mock.requests().len(), 4);
+    }
+}
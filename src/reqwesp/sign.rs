@@ -0,0 +1,239 @@
+//! Request signing, so the backend can tell requests came from a provisioned lock.
+//!
+//! A `Signer` set with `ClientBuilder::signer()` is run on every request right
+//! before it is sent, including each redirect within the origin of the
+//! original request. Redirects to another origin are sent unsigned.
+//!
+//! `HmacSigner` adds the following headers:
+//!
+//! - `X-Lynx-Key-Id`: the identifier of the shared key.
+//! - `X-Lynx-Timestamp`: the Unix time in seconds.
+//! - `X-Lynx-Signature`: `sha1=<hex>` or `sha256=<hex>`, the HMAC of
+//!   `"{METHOD}\n{path and query}\n{timestamp}\n"` followed by the raw body.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION};
+
+use crate::hmac::{hex, hmac_sha1, hmac_sha256};
+use crate::reqwesp::transport::method_name;
+use crate::Request;
+
+/// Header carrying the identifier of the key used by `HmacSigner`.
+pub const KEY_ID: HeaderName = HeaderName::from_static("x-lynx-key-id");
+
+/// Header carrying the time a request was signed by `HmacSigner`.
+pub const TIMESTAMP: HeaderName = HeaderName::from_static("x-lynx-timestamp");
+
+/// Header carrying the signature added by `HmacSigner`.
+pub const SIGNATURE: HeaderName = HeaderName::from_static("x-lynx-signature");
+
+/// Adds authentication to requests before they are sent.
+pub trait Signer {
+    /// Sign `request`, usually by adding headers to it.
+    fn sign(&self, request: &mut Request) -> Result<()>;
+}
+
+impl<F> Signer for F
+where
+    F: Fn(&mut Request) -> Result<()>,
+{
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        self(request)
+    }
+}
+
+/// `Signer` adding a device token as an `Authorization: Bearer` header.
+#[derive(Clone, Debug)]
+pub struct BearerToken {
+    value: HeaderValue,
+}
+
+impl BearerToken {
+    /// Fails with a builder error if `token` is not a valid header value.
+    pub fn new(token: &str) -> Result<Self> {
+        let mut value =
+            HeaderValue::from_str(&format!("Bearer {token}")).map_err(crate::error::builder)?;
+        value.set_sensitive(true);
+        Ok(Self { value })
+    }
+}
+
+impl Signer for BearerToken {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        request.headers.insert(AUTHORIZATION, self.value.clone());
+        Ok(())
+    }
+}
+
+/// Hash function used by `HmacSigner`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl HmacAlgorithm {
+    fn name(self) -> &'static str {
+        match self {
+            HmacAlgorithm::Sha1 => "sha1",
+            HmacAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    fn hmac(self, key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            HmacAlgorithm::Sha1 => hmac_sha1(key, data)?.to_vec(),
+            HmacAlgorithm::Sha256 => hmac_sha256(key, data)?.to_vec(),
+        })
+    }
+}
+
+/// `Signer` adding an HMAC signature over the method, path, timestamp and body.
+///
+/// The timestamp comes from the system clock, so it must be synchronized
+/// (e.g. with SNTP) for the backend to accept the signatures.
+#[derive(Clone)]
+pub struct HmacSigner {
+    key_id: HeaderValue,
+    key: Vec<u8>,
+    algorithm: HmacAlgorithm,
+    now: fn() -> u64,
+}
+
+impl HmacSigner {
+    /// Fails with a builder error if `key_id` is not a valid header value.
+    pub fn new(key_id: &str, key: &[u8], algorithm: HmacAlgorithm) -> Result<Self> {
+        Ok(Self {
+            key_id: HeaderValue::from_str(key_id).map_err(crate::error::builder)?,
+            key: key.to_vec(),
+            algorithm,
+            now: unix_time,
+        })
+    }
+
+    /// Use `now` instead of the system clock to get the Unix time in seconds.
+    pub fn with_clock(mut self, now: fn() -> u64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Computes the signature of `request` at `timestamp`.
+    pub fn signature(&self, request: &Request, timestamp: u64) -> Result<String> {
+        let url = request.url();
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        let mut message =
+            format!("{}\n{path}\n{timestamp}\n", method_name(request.method)).into_bytes();
+        if let Some(body) = request.body() {
+            message.extend_from_slice(body);
+        }
+
+        let digest = self.algorithm.hmac(&self.key, &message)?;
+        Ok(format!("{}={}", self.algorithm.name(), hex(&digest)))
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        let timestamp = (self.now)();
+        let signature = self.signature(request, timestamp)?;
+
+        request.headers.insert(KEY_ID, self.key_id.clone());
+        request
+            .headers
+            .insert(TIMESTAMP, HeaderValue::from(timestamp));
+        request.headers.insert(
+            SIGNATURE,
+            HeaderValue::from_str(&signature).map_err(crate::error::builder)?,
+        );
+        Ok(())
+    }
+}
+
+/// Current Unix time in seconds, or 0 if the clock is before the epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_svc::http::Method;
+
+    use super::*;
+    use crate::Url;
+
+    fn request() -> Request {
+        let url = Url::parse("http://lock.local/api/doors/1/access?source=nfc&n=2").unwrap();
+        let mut request = Request::new(Method::Post, url);
+        *request.body_mut() = Some(br#"{"serial":1}"#.to_vec().into());
+        request
+    }
+
+    #[test]
+    fn signs_the_method_path_query_timestamp_and_body() {
+        let signer = HmacSigner::new("door-1", b"secret", HmacAlgorithm::Sha256).unwrap();
+        assert_eq!(
+            signer.signature(&request(), 1_700_000_000).unwrap(),
+            "sha256=849bf672d5aa1c72843f8905a3367a446fbec6892a3345313b13684849180c54"
+        );
+
+        let signer = HmacSigner::new("door-1", b"secret", HmacAlgorithm::Sha1).unwrap();
+        assert_eq!(
+            signer.signature(&request(), 1_700_000_000).unwrap(),
+            "sha1=31dc1b71b93ab75e99fabd86ed882bbe742cfbeb"
+        );
+    }
+
+    #[test]
+    fn signs_a_request_without_body_or_query() {
+        let url = Url::parse("http://lock.local/api/doors").unwrap();
+        let signer = HmacSigner::new("door-1", b"secret", HmacAlgorithm::Sha256).unwrap();
+        assert_eq!(
+            signer
+                .signature(&Request::new(Method::Get, url), 1_700_000_000)
+                .unwrap(),
+            "sha256=8f2032062bb44602e5d41ea5a94089c599382c99ab0e1bf7d8bb107900c9d125"
+        );
+    }
+
+    #[test]
+    fn adds_the_key_id_timestamp_and_signature_headers() {
+        let signer = HmacSigner::new("door-1", b"secret", HmacAlgorithm::Sha256)
+            .unwrap()
+            .with_clock(|| 1_700_000_000);
+        let mut request = request();
+        signer.sign(&mut request).unwrap();
+
+        let headers = request.headers();
+        assert_eq!(headers[KEY_ID], "door-1");
+        assert_eq!(headers[TIMESTAMP], "1700000000");
+        assert_eq!(
+            headers[SIGNATURE],
+            "sha256=849bf672d5aa1c72843f8905a3367a446fbec6892a3345313b13684849180c54"
+        );
+    }
+
+    #[test]
+    fn adds_a_sensitive_bearer_token() {
+        let mut request = request();
+        BearerToken::new("device-token")
+            .unwrap()
+            .sign(&mut request)
+            .unwrap();
+
+        let value = &request.headers()[AUTHORIZATION];
+        assert_eq!(value, "Bearer device-token");
+        assert!(value.is_sensitive());
+
+        assert!(BearerToken::new("device\ntoken").is_err());
+    }
+}
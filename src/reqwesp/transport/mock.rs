@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use embedded_svc::http::client::Connection;
+use embedded_svc::http::{Headers, Method, Status};
+use embedded_svc::io::{ErrorType, Read, Write};
+
+use super::{find_header, known_headers, ResponseHeaders};
+
+/// A canned response returned by a `MockConnection`.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    /// Add a header to this response.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add a body to this response.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        let length = self.body.len().to_string();
+        self.header("Content-Length", &length)
+    }
+
+    /// Add a body of unknown length to this response, as sent with
+    /// `Transfer-Encoding: chunked` once the connection decoded the chunks.
+    pub fn chunked(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self.header("Transfer-Encoding", "chunked")
+    }
+
+    /// Add a json body to this response.
+    pub fn json(self, json: &impl Serialize) -> Self {
+        let body = serde_json::to_vec(json).expect("Cannot serialize mock response body");
+        self.header("Content-Type", "application/json").body(body)
+    }
+}
+
+/// A request received by a `MockConnection`.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Obtain the given header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    responses: VecDeque<io::Result<MockResponse>>,
+    requests: Vec<RecordedRequest>,
+    known_headers_only: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    New,
+    Request,
+    Response,
+}
+
+/// Status and headers of the current `MockConnection` response.
+pub struct MockHeaders {
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl Status for MockHeaders {
+    fn status(&self) -> u16 {
+        self.status
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Headers for MockHeaders {
+    fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// Body of the current `MockConnection` response.
+pub struct MockBody {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl ErrorType for MockBody {
+    type Error = io::Error;
+}
+
+impl Read for MockBody {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let remaining = &self.data[self.position..];
+        let size = remaining.len().min(buf.len());
+        buf[..size].copy_from_slice(&remaining[..size]);
+        self.position += size;
+        Ok(size)
+    }
+}
+
+/// An in-memory `Connection` answering each request with the next queued `MockResponse`.
+///
+/// Clones share the same response queue and request log, so a clone can be kept
+/// around to inspect what a `Client` sent after handing it the original.
+pub struct MockConnection {
+    shared: Rc<RefCell<Shared>>,
+    phase: Phase,
+    request: Option<RecordedRequest>,
+    head: MockHeaders,
+    body: MockBody,
+}
+
+impl Default for MockConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for MockConnection {
+    fn clone(&self) -> Self {
+        Self::with_shared(self.shared.clone())
+    }
+}
+
+impl MockConnection {
+    pub fn new() -> Self {
+        Self::with_shared(Rc::default())
+    }
+
+    fn with_shared(shared: Rc<RefCell<Shared>>) -> Self {
+        Self {
+            shared,
+            phase: Phase::New,
+            request: None,
+            head: MockHeaders {
+                status: 0,
+                headers: vec![],
+            },
+            body: MockBody {
+                data: vec![],
+                position: 0,
+            },
+        }
+    }
+
+    /// Queue a response for the next request.
+    pub fn push_response(&self, response: MockResponse) {
+        self.shared.borrow_mut().responses.push_back(Ok(response));
+    }
+
+    /// Make the next request fail with a connection error of the given kind.
+    pub fn push_error(&self, kind: io::ErrorKind) {
+        let error = io::Error::new(kind, "mock connection error");
+        self.shared.borrow_mut().responses.push_back(Err(error));
+    }
+
+    /// Make `ResponseHeaders::response_headers()` list only well-known
+    /// headers, like an `EspHttpConnection`.
+    pub fn known_headers_only(&self) {
+        self.shared.borrow_mut().known_headers_only = true;
+    }
+
+    /// All requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.shared.borrow().requests.clone()
+    }
+
+    /// The most recently received request.
+    pub fn last_request(&self) -> Option<RecordedRequest> {
+        self.shared.borrow().requests.last().cloned()
+    }
+
+    fn assert_phase(&self, phase: Phase) {
+        if self.phase != phase {
+            panic!("connection is not in {phase:?} phase");
+        }
+    }
+}
+
+impl Status for MockConnection {
+    fn status(&self) -> u16 {
+        self.assert_phase(Phase::Response);
+        self.head.status()
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        self.assert_phase(Phase::Response);
+        self.head.status_message()
+    }
+}
+
+impl Headers for MockConnection {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.assert_phase(Phase::Response);
+        self.head.header(name)
+    }
+}
+
+impl ResponseHeaders for MockConnection {
+    fn response_headers(&self) -> Vec<(&str, &str)> {
+        self.assert_phase(Phase::Response);
+        if self.shared.borrow().known_headers_only {
+            return known_headers(self);
+        }
+        self.head
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+impl ErrorType for MockConnection {
+    type Error = io::Error;
+}
+
+impl Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.assert_phase(Phase::Response);
+        self.body.read(buf)
+    }
+}
+
+impl Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.assert_phase(Phase::Request);
+        if let Some(request) = self.request.as_mut() {
+            request.body.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.assert_phase(Phase::Request);
+        Ok(())
+    }
+}
+
+impl Connection for MockConnection {
+    type Headers = MockHeaders;
+
+    type Read = MockBody;
+
+    type RawConnectionError = io::Error;
+
+    type RawConnection = Self;
+
+    fn initiate_request<'a>(
+        &'a mut self,
+        method: Method,
+        uri: &'a str,
+        headers: &'a [(&'a str, &'a str)],
+    ) -> Result<(), Self::Error> {
+        if self.phase == Phase::Request {
+            panic!("connection is not in initial phase");
+        }
+
+        self.request = Some(RecordedRequest {
+            method,
+            uri: uri.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: vec![],
+        });
+        self.phase = Phase::Request;
+        Ok(())
+    }
+
+    fn is_request_initiated(&self) -> bool {
+        self.phase == Phase::Request
+    }
+
+    fn initiate_response(&mut self) -> Result<(), Self::Error> {
+        self.assert_phase(Phase::Request);
+
+        let mut shared = self.shared.borrow_mut();
+        if let Some(request) = self.request.take() {
+            shared.requests.push(request);
+        }
+
+        let response = shared.responses.pop_front().unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "no mock response queued",
+            ))
+        });
+
+        match response {
+            Ok(response) => {
+                self.head = MockHeaders {
+                    status: response.status,
+                    headers: response.headers,
+                };
+                self.body = MockBody {
+                    data: response.body,
+                    position: 0,
+                };
+                self.phase = Phase::Response;
+                Ok(())
+            }
+            Err(e) => {
+                self.phase = Phase::New;
+                Err(e)
+            }
+        }
+    }
+
+    fn is_response_initiated(&self) -> bool {
+        self.phase == Phase::Response
+    }
+
+    fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+        self.assert_phase(Phase::Response);
+        (&self.head, &mut self.body)
+    }
+
+    fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "mock connection has no raw connection",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use embedded_svc::http::Method;
+    use serde_json::{json, Value};
+
+    use super::{MockConnection, MockResponse};
+    use crate::reqwesp::{Client, RetryPolicy};
+
+    #[test]
+    fn records_request_and_returns_queued_response() {
+        let mock = MockConnection::new();
+        mock.push_response(MockResponse::new(200).json(&json!({ "open": true })));
+
+        let mut client = Client::with_connection(mock.clone());
+        let response = client
+            .post("http://lock.local/doors/1")
+            .header("X-Door", "front")
+            .json(&json!({ "unlock": 1 }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.json::<Value>().unwrap(), json!({ "open": true }));
+
+        let request = mock.last_request().unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.uri, "http://lock.local/doors/1");
+        assert_eq!(request.header("x-door"), Some("front"));
+        assert_eq!(request.body, br#"{"unlock":1}"#);
+    }
+
+    #[test]
+    fn retries_after_a_connection_error() {
+        let mock = MockConnection::new();
+        mock.push_error(io::ErrorKind::ConnectionReset);
+        mock.push_response(MockResponse::new(204));
+
+        let mut client = Client::with_connection(mock.clone());
+        let response = client
+            .get("http://lock.local/status")
+            .retry(RetryPolicy::new(2).backoff(Duration::ZERO, Duration::ZERO))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn fails_without_a_queued_response() {
+        let mut client = Client::with_connection(MockConnection::new());
+        assert!(client.get("http://lock.local/status").send().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! `Connection`s a `Client` can use instead of the default `EspHttpConnection`.
+//!
+//! Neither of these depend on ESP-IDF, so requests can be exercised on the host:
+//! `MockConnection` answers from memory, while `TcpConnection` speaks plain
+//! HTTP/1.1 to a local server, and is the default `Connection` off the device.
+
+use hyper::header::{self, HeaderName};
+
+use embedded_svc::http::client::Connection;
+use embedded_svc::http::Method;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::http::client::EspHttpConnection;
+
+#[cfg(any(test, feature = "mock"))]
+mod mock;
+#[cfg(any(test, feature = "mock"))]
+pub use mock::{MockConnection, MockResponse, RecordedRequest};
+
+mod tcp;
+pub use tcp::TcpConnection;
+
+/// The `Connection` of a `Client` built with `ClientBuilder::build()`.
+#[cfg(target_os = "espidf")]
+pub type DefaultConnection = EspHttpConnection;
+
+/// The `Connection` of a `Client` built with `ClientBuilder::build()`.
+#[cfg(not(target_os = "espidf"))]
+pub type DefaultConnection = TcpConnection;
+
+/// Headers looked up by the default `ResponseHeaders::response_headers()`.
+const KNOWN_HEADERS: &[HeaderName] = &[
+    header::ACCEPT_RANGES,
+    header::AGE,
+    header::ALLOW,
+    header::CACHE_CONTROL,
+    header::CONNECTION,
+    header::CONTENT_DISPOSITION,
+    header::CONTENT_ENCODING,
+    header::CONTENT_LANGUAGE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_LOCATION,
+    header::CONTENT_RANGE,
+    header::CONTENT_TYPE,
+    header::DATE,
+    header::ETAG,
+    header::EXPIRES,
+    header::LAST_MODIFIED,
+    header::LINK,
+    header::LOCATION,
+    header::RETRY_AFTER,
+    header::SERVER,
+    header::SET_COOKIE,
+    header::STRICT_TRANSPORT_SECURITY,
+    header::TRANSFER_ENCODING,
+    header::VARY,
+    header::WWW_AUTHENTICATE,
+];
+
+/// A `Connection` that can list the headers of the current response.
+///
+/// `embedded_svc` only looks up response headers by name. The default
+/// implementation looks up a fixed list of well-known headers, which is all
+/// `EspHttpConnection` allows, so `Response::header()` looks any other header
+/// up by name. Connections that keep every header, repeated ones included,
+/// return all of them.
+pub trait ResponseHeaders: Connection {
+    /// All headers of the current response, in the order they were received.
+    fn response_headers(&self) -> Vec<(&str, &str)> {
+        known_headers(self)
+    }
+}
+
+/// Looks up each of the `KNOWN_HEADERS` on `connection`.
+fn known_headers<C: Connection + ?Sized>(connection: &C) -> Vec<(&str, &str)> {
+    KNOWN_HEADERS
+        .iter()
+        .filter_map(|name| Some((name.as_str(), connection.header(name.as_str())?)))
+        .collect()
+}
+
+#[cfg(target_os = "espidf")]
+impl ResponseHeaders for EspHttpConnection {}
+
+/// Finds a header by case-insensitive name.
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Returns the request line token of `method`.
+pub(crate) fn method_name(method: Method) -> &'static str {
+    match method {
+        Method::Delete => "DELETE",
+        Method::Get => "GET",
+        Method::Head => "HEAD",
+        Method::Post => "POST",
+        Method::Put => "PUT",
+        Method::Connect => "CONNECT",
+        Method::Options => "OPTIONS",
+        Method::Trace => "TRACE",
+        Method::Copy => "COPY",
+        Method::Lock => "LOCK",
+        Method::MkCol => "MKCOL",
+        Method::Move => "MOVE",
+        Method::Propfind => "PROPFIND",
+        Method::Proppatch => "PROPPATCH",
+        Method::Search => "SEARCH",
+        Method::Unlock => "UNLOCK",
+        Method::Bind => "BIND",
+        Method::Rebind => "REBIND",
+        Method::Unbind => "UNBIND",
+        Method::Acl => "ACL",
+        Method::Report => "REPORT",
+        Method::MkActivity => "MKACTIVITY",
+        Method::Checkout => "CHECKOUT",
+        Method::Merge => "MERGE",
+        Method::MSearch => "M-SEARCH",
+        Method::Notify => "NOTIFY",
+        Method::Subscribe => "SUBSCRIBE",
+        Method::Unsubscribe => "UNSUBSCRIBE",
+        Method::Patch => "PATCH",
+        Method::Purge => "PURGE",
+        Method::MkCalendar => "MKCALENDAR",
+        Method::Link => "LINK",
+        Method::Unlink => "UNLINK",
+    }
+}
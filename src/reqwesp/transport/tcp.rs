@@ -0,0 +1,542 @@
+use std::io::{self, BufRead, BufReader, Read as _, Write as _};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use url::Url;
+
+use embedded_svc::http::client::Connection;
+use embedded_svc::http::{Headers, Method, Status};
+use embedded_svc::io::{ErrorType, Read, Write};
+
+use super::{find_header, method_name, ResponseHeaders};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    New,
+    Request,
+    Response,
+}
+
+/// How the end of the response body is found.
+#[derive(Clone, Copy, Debug)]
+enum Framing {
+    /// `Content-Length` bytes remain.
+    Length(u64),
+    /// `Transfer-Encoding: chunked`, with the bytes remaining in the current chunk.
+    Chunked { remaining: u64, done: bool },
+    /// The body ends when the server closes the connection.
+    Close,
+}
+
+/// Status and headers of the current `TcpConnection` response.
+pub struct TcpHeaders {
+    status: u16,
+    message: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Status for TcpHeaders {
+    fn status(&self) -> u16 {
+        self.status
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        Some(self.message.as_str())
+    }
+}
+
+impl Headers for TcpHeaders {
+    fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// Body of the current `TcpConnection` response.
+pub struct TcpBody {
+    reader: Option<BufReader<TcpStream>>,
+    framing: Framing,
+}
+
+impl TcpBody {
+    fn reader(&mut self) -> io::Result<&mut BufReader<TcpStream>> {
+        self.reader
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "no response"))
+    }
+
+    fn read_chunk_size(&mut self) -> io::Result<u64> {
+        let line = read_line(self.reader()?)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        u64::from_str_radix(size, 16).map_err(|_| invalid_data("malformed chunk size"))
+    }
+}
+
+impl ErrorType for TcpBody {
+    type Error = io::Error;
+}
+
+impl Read for TcpBody {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.framing {
+            Framing::Length(0) => Ok(0),
+            Framing::Length(remaining) => {
+                let limit = buf.len().min(remaining as usize);
+                let size = self.reader()?.read(&mut buf[..limit])?;
+                if size == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.framing = Framing::Length(remaining - size as u64);
+                Ok(size)
+            }
+            Framing::Chunked { done: true, .. } => Ok(0),
+            Framing::Chunked { mut remaining, .. } => {
+                if remaining == 0 {
+                    remaining = self.read_chunk_size()?;
+                    if remaining == 0 {
+                        // Skip any trailers up to the final empty line
+                        while !read_line(self.reader()?)?.is_empty() {}
+                        self.framing = Framing::Chunked {
+                            remaining,
+                            done: true,
+                        };
+                        return Ok(0);
+                    }
+                }
+
+                let limit = buf.len().min(remaining as usize);
+                let size = self.reader()?.read(&mut buf[..limit])?;
+                if size == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                remaining -= size as u64;
+                if remaining == 0 {
+                    // Each chunk is followed by a CRLF
+                    read_line(self.reader()?)?;
+                }
+                self.framing = Framing::Chunked {
+                    remaining,
+                    done: false,
+                };
+                Ok(size)
+            }
+            Framing::Close => self.reader()?.read(buf),
+        }
+    }
+}
+
+/// The request being written, sent once the response is initiated.
+struct PendingRequest {
+    method: Method,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// A plain HTTP/1.1 `Connection` over a std `TcpStream`.
+///
+/// A new TCP connection is opened for every request and closed by the server once
+/// the response has been sent. The request body is buffered in memory so that a
+/// `Content-Length` can always be sent. TLS is not supported, so this is meant for
+/// talking to a local server from the host rather than for use on the device.
+pub struct TcpConnection {
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    phase: Phase,
+    request: Option<PendingRequest>,
+    head: TcpHeaders,
+    body: TcpBody,
+}
+
+impl Default for TcpConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TcpConnection {
+    /// Constructs a new `TcpConnection` without any timeouts.
+    pub fn new() -> Self {
+        Self::with_timeouts(None, None)
+    }
+
+    /// Constructs a new `TcpConnection` with the given connect and read timeouts.
+    pub fn with_timeouts(
+        connect_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            connect_timeout,
+            read_timeout,
+            phase: Phase::New,
+            request: None,
+            head: TcpHeaders {
+                status: 0,
+                message: String::new(),
+                headers: vec![],
+            },
+            body: TcpBody {
+                reader: None,
+                framing: Framing::Length(0),
+            },
+        }
+    }
+
+    fn connect(&self, url: &Url) -> io::Result<TcpStream> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL has no host"))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host did not resolve");
+        for address in (host, port).to_socket_addrs()? {
+            let stream = match self.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+                None => TcpStream::connect(address),
+            };
+            match stream {
+                Ok(stream) => {
+                    stream.set_read_timeout(self.read_timeout)?;
+                    stream.set_write_timeout(self.read_timeout)?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn send(&mut self, request: PendingRequest) -> io::Result<()> {
+        let mut stream = self.connect(&request.url)?;
+
+        let mut target = request.url.path().to_string();
+        if let Some(query) = request.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let host = match request.url.port() {
+            Some(port) => format!("{}:{port}", request.url.host_str().unwrap_or_default()),
+            None => request.url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut head = format!(
+            "{} {target} HTTP/1.1\r\nHost: {host}\r\n",
+            method_name(request.method)
+        );
+        for (name, value) in &request.headers {
+            // Framing is handled here, as the body is sent in one go
+            if ["host", "content-length", "transfer-encoding", "connection"]
+                .iter()
+                .any(|skip| name.eq_ignore_ascii_case(skip))
+            {
+                continue;
+            }
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if !request.body.is_empty()
+            || matches!(request.method, Method::Post | Method::Put | Method::Patch)
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&request.body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+
+        let status_line = read_line(&mut reader)?;
+        let mut parts = status_line.splitn(3, ' ');
+        let version = parts.next().unwrap_or_default();
+        if !version.starts_with("HTTP/1.") {
+            return Err(invalid_data("malformed status line"));
+        }
+        let status: u16 = parts
+            .next()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| invalid_data("malformed status code"))?;
+        let message = parts.next().unwrap_or_default().to_string();
+
+        let mut headers = vec![];
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid_data("malformed header"))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        let framing = if request.method == Method::Head
+            || (100..200).contains(&status)
+            || status == 204
+            || status == 304
+        {
+            Framing::Length(0)
+        } else if find_header(&headers, "Transfer-Encoding")
+            .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"))
+        {
+            Framing::Chunked {
+                remaining: 0,
+                done: false,
+            }
+        } else if let Some(length) = find_header(&headers, "Content-Length") {
+            Framing::Length(
+                length
+                    .parse()
+                    .map_err(|_| invalid_data("malformed content length"))?,
+            )
+        } else {
+            Framing::Close
+        };
+
+        self.head = TcpHeaders {
+            status,
+            message,
+            headers,
+        };
+        self.body = TcpBody {
+            reader: Some(reader),
+            framing,
+        };
+        Ok(())
+    }
+
+    fn assert_phase(&self, phase: Phase) {
+        if self.phase != phase {
+            panic!("connection is not in {phase:?} phase");
+        }
+    }
+}
+
+impl Status for TcpConnection {
+    fn status(&self) -> u16 {
+        self.assert_phase(Phase::Response);
+        self.head.status()
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        self.assert_phase(Phase::Response);
+        self.head.status_message()
+    }
+}
+
+impl Headers for TcpConnection {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.assert_phase(Phase::Response);
+        self.head.header(name)
+    }
+}
+
+impl ResponseHeaders for TcpConnection {
+    fn response_headers(&self) -> Vec<(&str, &str)> {
+        self.assert_phase(Phase::Response);
+        self.head
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+impl ErrorType for TcpConnection {
+    type Error = io::Error;
+}
+
+impl Read for TcpConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.assert_phase(Phase::Response);
+        self.body.read(buf)
+    }
+}
+
+impl Write for TcpConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.assert_phase(Phase::Request);
+        if let Some(request) = self.request.as_mut() {
+            request.body.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.assert_phase(Phase::Request);
+        Ok(())
+    }
+}
+
+impl Connection for TcpConnection {
+    type Headers = TcpHeaders;
+
+    type Read = TcpBody;
+
+    type RawConnectionError = io::Error;
+
+    type RawConnection = Self;
+
+    fn initiate_request<'a>(
+        &'a mut self,
+        method: Method,
+        uri: &'a str,
+        headers: &'a [(&'a str, &'a str)],
+    ) -> Result<(), Self::Error> {
+        if self.phase == Phase::Request {
+            panic!("connection is not in initial phase");
+        }
+
+        let url = Url::parse(uri).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if url.scheme() != "http" {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "TcpConnection only supports plain http",
+            ));
+        }
+
+        // Drop the previous response, closing its socket
+        self.body.reader = None;
+        self.request = Some(PendingRequest {
+            method,
+            url,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: vec![],
+        });
+        self.phase = Phase::Request;
+        Ok(())
+    }
+
+    fn is_request_initiated(&self) -> bool {
+        self.phase == Phase::Request
+    }
+
+    fn initiate_response(&mut self) -> Result<(), Self::Error> {
+        self.assert_phase(Phase::Request);
+
+        let request = self
+            .request
+            .take()
+            .expect("Request phase without a pending request");
+        match self.send(request) {
+            Ok(()) => {
+                self.phase = Phase::Response;
+                Ok(())
+            }
+            Err(e) => {
+                self.phase = Phase::New;
+                Err(e)
+            }
+        }
+    }
+
+    fn is_response_initiated(&self) -> bool {
+        self.phase == Phase::Response
+    }
+
+    fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+        self.assert_phase(Phase::Response);
+        (&self.head, &mut self.body)
+    }
+
+    fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TcpConnection does not expose its raw connection",
+        ))
+    }
+}
+
+/// Reads a single CRLF terminated line, without the line ending.
+fn read_line(reader: &mut BufReader<TcpStream>) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::TcpConnection;
+    use crate::reqwesp::Client;
+
+    /// Serves `response` to a single request, returning the request head.
+    fn serve_once(response: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            head
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn reads_a_response_with_content_length() {
+        let (url, server) =
+            serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Custom: yes\r\n\r\nhello");
+
+        let mut client = Client::with_connection(TcpConnection::new());
+        let response = client
+            .get(format!("{url}/status?door=1"))
+            .header("X-Door", "front")
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("x-custom").unwrap(), "yes");
+        assert_eq!(response.text().unwrap(), "hello");
+
+        let head = server.join().unwrap();
+        assert!(head.starts_with("GET /status?door=1 HTTP/1.1\r\n"));
+        assert!(head.contains("x-door: front\r\n"));
+    }
+
+    #[test]
+    fn reads_a_chunked_response() {
+        let (url, server) = serve_once(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nlock\r\n3\r\ned!\r\n0\r\n\r\n",
+        );
+
+        let mut client = Client::with_connection(TcpConnection::new());
+        let response = client.get(url.as_str()).send().unwrap();
+        assert_eq!(response.text().unwrap(), "locked!");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn reads_a_body_until_the_connection_closes() {
+        let (url, server) = serve_once("HTTP/1.1 404 Not Found\r\n\r\nno such door");
+
+        let mut client = Client::with_connection(TcpConnection::new());
+        let response = client.get(url.as_str()).send().unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.text().unwrap(), "no such door");
+        server.join().unwrap();
+    }
+}
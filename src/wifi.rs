@@ -1,37 +0,0 @@
-use anyhow::Result;
-
-use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
-use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
-
-#[toml_cfg::toml_config]
-pub(crate) struct Config {
-    #[default("")]
-    wifi_ssid: &'static str,
-    #[default("")]
-    wifi_password: &'static str,
-}
-
-pub fn connect(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
-    let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
-        ssid: CONFIG.wifi_ssid.parse().expect("Failed to parse wifi SSID"),
-        password: CONFIG
-            .wifi_password
-            .parse()
-            .expect("Failed to parse wifi password"),
-        auth_method: AuthMethod::WPA2Personal,
-        ..Default::default()
-    });
-
-    wifi.set_configuration(&wifi_configuration)?;
-
-    wifi.start()?;
-    log::info!("Wifi started");
-
-    wifi.connect()?;
-    log::info!("Wifi connected");
-
-    wifi.wait_netif_up()?;
-    log::info!("Wifi netif up");
-
-    Ok(())
-}
@@ -0,0 +1,335 @@
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use embedded_svc::wifi::{AccessPointInfo, AuthMethod, ClientConfiguration, Configuration};
+use esp_idf_svc::eventloop::{EspSubscription, EspSystemEventLoop, System};
+use esp_idf_svc::netif::IpEvent;
+use esp_idf_svc::sys::{esp, esp_wifi_sta_get_ap_info, wifi_ap_record_t};
+use esp_idf_svc::wifi::{EspWifi, ScanConfig, WifiEvent};
+
+use super::Network;
+use crate::RetryPolicy;
+
+/// How long a scan or a connection attempt may take before it is given up.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Delay before the first reconnection attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound of the delay between two reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WifiState {
+    /// `WifiManager::start()` was not called yet.
+    Stopped,
+    /// Looking for the configured networks.
+    Scanning,
+    /// Joining a network and waiting for an IP address.
+    Connecting,
+    /// Joined a network and got an IP address.
+    Connected,
+    /// Waiting before the next attempt.
+    Disconnected,
+}
+
+/// Sent to the subscribers of a `WifiManager` every time its state changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WifiStatus {
+    pub state: WifiState,
+    /// The network being joined or joined, if any.
+    pub ssid: Option<String>,
+    /// Signal strength in dBm, once connected.
+    pub rssi: Option<i8>,
+}
+
+/// What the system event callbacks hand over to the manager.
+enum Message {
+    ScanDone,
+    StaConnected,
+    StaDisconnected,
+    IpAssigned,
+}
+
+/// Keeps the lock connected to the best available network.
+///
+/// The manager never blocks: it starts scans and connections, and `poll()`
+/// must be called regularly to handle their outcome. Networks are tried in
+/// the order they were given, the first one found by a scan being joined with
+/// the authentication method its access point advertises. When none is found,
+/// as with a hidden network, one of them is joined by its SSID alone, in turn
+/// at every attempt. After a failure or a disconnection, a new scan is started
+/// after an exponential backoff.
+pub struct WifiManager {
+    wifi: EspWifi<'static>,
+    networks: Vec<Network>,
+    state: WifiState,
+    ssid: Option<String>,
+    attempt: u32,
+    backoff: RetryPolicy,
+    retry_at: Option<Instant>,
+    deadline: Option<Instant>,
+    messages: Receiver<Message>,
+    subscribers: Vec<Sender<WifiStatus>>,
+    _subscriptions: [EspSubscription<'static, System>; 2],
+}
+
+impl WifiManager {
+    /// Constructs a new `WifiManager` joining one of `networks`, in priority order.
+    pub fn new(
+        wifi: EspWifi<'static>,
+        sys_loop: &EspSystemEventLoop,
+        networks: Vec<Network>,
+    ) -> Result<Self> {
+        if networks.is_empty() {
+            return Err(anyhow!("No Wifi network configured"));
+        }
+
+        let (sender, messages) = channel();
+        let wifi_sender = sender.clone();
+        let wifi_events = sys_loop.subscribe::<WifiEvent, _>(move |event| {
+            let message = match event {
+                WifiEvent::ScanDone => Message::ScanDone,
+                WifiEvent::StaConnected => Message::StaConnected,
+                WifiEvent::StaDisconnected => Message::StaDisconnected,
+                _ => return,
+            };
+            // Only fails once the manager is dropped.
+            let _ = wifi_sender.send(message);
+        })?;
+        let ip_events = sys_loop.subscribe::<IpEvent, _>(move |event| {
+            if let IpEvent::DhcpIpAssigned(_) = event {
+                let _ = sender.send(Message::IpAssigned);
+            }
+        })?;
+
+        Ok(Self {
+            wifi,
+            networks,
+            state: WifiState::Stopped,
+            ssid: None,
+            attempt: 0,
+            backoff: RetryPolicy::new(u32::MAX).backoff(INITIAL_BACKOFF, MAX_BACKOFF),
+            retry_at: None,
+            deadline: None,
+            messages,
+            subscribers: vec![],
+            _subscriptions: [wifi_events, ip_events],
+        })
+    }
+
+    /// Set the delay before the first reconnection attempt, and the maximum delay between attempts.
+    pub fn set_backoff(&mut self, initial: Duration, max: Duration) {
+        self.backoff = RetryPolicy::new(u32::MAX).backoff(initial, max);
+    }
+
+    /// Starts the driver and the first scan.
+    pub fn start(&mut self) -> Result<()> {
+        self.wifi
+            .set_configuration(&Configuration::Client(ClientConfiguration::default()))?;
+        self.wifi.start()?;
+        log::info!("Wifi started");
+        self.scan()
+    }
+
+    /// Get the current state.
+    pub fn state(&self) -> WifiState {
+        self.state
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == WifiState::Connected
+    }
+
+    /// Number of attempts that failed since the last successful connection.
+    pub fn failures(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Get the network being joined or joined, if any.
+    pub fn ssid(&self) -> Option<&str> {
+        self.ssid.as_deref()
+    }
+
+    /// Signal strength of the joined network in dBm.
+    pub fn rssi(&self) -> Option<i8> {
+        if !self.is_connected() {
+            return None;
+        }
+        let mut record = wifi_ap_record_t::default();
+        esp!(unsafe { esp_wifi_sta_get_ap_info(&mut record) })
+            .ok()
+            .map(|_| record.rssi)
+    }
+
+    /// Get the current `WifiStatus`.
+    pub fn status(&self) -> WifiStatus {
+        WifiStatus {
+            state: self.state,
+            ssid: self.ssid.clone(),
+            rssi: self.rssi(),
+        }
+    }
+
+    /// Returns a receiver of every following `WifiStatus` change.
+    pub fn subscribe(&mut self) -> Receiver<WifiStatus> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Get a mutable reference to the underlying driver.
+    pub fn wifi(&mut self) -> &mut EspWifi<'static> {
+        &mut self.wifi
+    }
+
+    /// Stops managing the connection and gives the driver back, e.g. to
+    /// start a provisioning `Portal` on it.
+    pub fn into_wifi(self) -> EspWifi<'static> {
+        self.wifi
+    }
+
+    /// Handles the pending Wi-Fi events and the retry and timeout timers.
+    pub fn poll(&mut self) -> Result<()> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(Message::ScanDone) if self.state == WifiState::Scanning => self.join()?,
+                Ok(Message::StaConnected) => log::info!("Wifi associated, waiting for IP"),
+                Ok(Message::IpAssigned) if self.state == WifiState::Connecting => {
+                    self.attempt = 0;
+                    self.deadline = None;
+                    self.set_state(WifiState::Connected);
+                }
+                Ok(Message::StaDisconnected)
+                    if matches!(self.state, WifiState::Connecting | WifiState::Connected) =>
+                {
+                    log::warn!("Wifi disconnected from {:?}", self.ssid);
+                    self.retry_later();
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(anyhow!("Wifi event loop stopped delivering events"))
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            log::warn!("Wifi {:?} timed out", self.state);
+            // The resulting `StaDisconnected` is ignored in the `Disconnected` state.
+            let _ = self.wifi.disconnect();
+            self.retry_later();
+        } else if self.retry_at.is_some_and(|retry_at| now >= retry_at) {
+            self.scan()?;
+        }
+        Ok(())
+    }
+
+    fn scan(&mut self) -> Result<()> {
+        self.retry_at = None;
+        self.ssid = None;
+        self.wifi.start_scan(&ScanConfig::default(), false)?;
+        self.deadline = Some(Instant::now() + ATTEMPT_TIMEOUT);
+        self.set_state(WifiState::Scanning);
+        Ok(())
+    }
+
+    /// Joins the best network found by the last scan, or tries one of the
+    /// networks as a hidden one.
+    fn join(&mut self) -> Result<()> {
+        let access_points = self.wifi.get_scan_result()?;
+        let (network, configuration) = match select(&self.networks, &access_points) {
+            Some((network, access_point)) => {
+                let auth_method = access_point.auth_method.unwrap_or(AuthMethod::WPA2Personal);
+                log::info!(
+                    "Joining {} ({auth_method:?}, {} dBm)",
+                    network.ssid,
+                    access_point.signal_strength
+                );
+                let configuration = ClientConfiguration {
+                    bssid: Some(access_point.bssid),
+                    channel: Some(access_point.channel),
+                    ..client_configuration(network, auth_method)?
+                };
+                (network, configuration)
+            }
+            None => {
+                let network = &self.networks[self.attempt as usize % self.networks.len()];
+                // The access point of a hidden network only answers probes for its SSID.
+                let auth_method = if network.password.is_empty() {
+                    AuthMethod::None
+                } else {
+                    AuthMethod::WPA2Personal
+                };
+                log::warn!(
+                    "No configured Wifi network in range, joining {} as a hidden network",
+                    network.ssid
+                );
+                (network, client_configuration(network, auth_method)?)
+            }
+        };
+        self.ssid = Some(network.ssid.clone());
+        let configuration = Configuration::Client(configuration);
+
+        self.wifi.set_configuration(&configuration)?;
+        self.wifi.connect()?;
+        self.deadline = Some(Instant::now() + ATTEMPT_TIMEOUT);
+        self.set_state(WifiState::Connecting);
+        Ok(())
+    }
+
+    fn retry_later(&mut self) {
+        self.attempt += 1;
+        let delay = self.backoff.delay(self.attempt);
+        log::info!("Retrying Wifi in {delay:?}");
+        self.retry_at = Some(Instant::now() + delay);
+        self.deadline = None;
+        self.ssid = None;
+        self.set_state(WifiState::Disconnected);
+    }
+
+    fn set_state(&mut self, state: WifiState) {
+        if state == self.state {
+            return;
+        }
+        log::info!("Wifi state: {:?} -> {state:?}", self.state);
+        self.state = state;
+
+        let status = self.status();
+        self.subscribers
+            .retain(|subscriber| subscriber.send(status.clone()).is_ok());
+    }
+}
+
+/// Joins `network` by its SSID, with `auth_method`.
+fn client_configuration(network: &Network, auth_method: AuthMethod) -> Result<ClientConfiguration> {
+    Ok(ClientConfiguration {
+        ssid: network
+            .ssid
+            .parse()
+            .map_err(|_| anyhow!("Wifi SSID {} is too long", network.ssid))?,
+        auth_method,
+        password: network
+            .password
+            .parse()
+            .map_err(|_| anyhow!("Wifi password of {} is too long", network.ssid))?,
+        ..Default::default()
+    })
+}
+
+/// Picks the first of `networks` in range, through its strongest access point.
+fn select<'a, 'b>(
+    networks: &'a [Network],
+    access_points: &'b [AccessPointInfo],
+) -> Option<(&'a Network, &'b AccessPointInfo)> {
+    networks.iter().find_map(|network| {
+        access_points
+            .iter()
+            .filter(|access_point| access_point.ssid.as_str() == network.ssid)
+            .max_by_key(|access_point| access_point.signal_strength)
+            .map(|access_point| (network, access_point))
+    })
+}
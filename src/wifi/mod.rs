@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "espidf")]
+use anyhow::Result;
+#[cfg(target_os = "espidf")]
+use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+
+#[cfg(target_os = "espidf")]
+mod manager;
+#[cfg(target_os = "espidf")]
+pub use manager::{WifiManager, WifiState, WifiStatus};
+
+#[toml_cfg::toml_config]
+pub(crate) struct Config {
+    #[default("")]
+    wifi_ssid: &'static str,
+    #[default("")]
+    wifi_password: &'static str,
+    #[default("")]
+    wifi_fallback_ssid: &'static str,
+    #[default("")]
+    wifi_fallback_password: &'static str,
+}
+
+/// A network the lock may join.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Network {
+    pub ssid: String,
+    /// Empty for an open network.
+    #[serde(default)]
+    pub password: String,
+}
+
+impl Network {
+    pub fn new(ssid: &str, password: &str) -> Self {
+        Self {
+            ssid: ssid.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+/// The networks set in `cfg.toml`, in priority order.
+pub fn networks() -> Vec<Network> {
+    [
+        (CONFIG.wifi_ssid, CONFIG.wifi_password),
+        (CONFIG.wifi_fallback_ssid, CONFIG.wifi_fallback_password),
+    ]
+    .into_iter()
+    .filter(|(ssid, _)| !ssid.is_empty())
+    .map(|(ssid, password)| Network::new(ssid, password))
+    .collect()
+}
+
+/// Connects once to the first network set in `cfg.toml`, blocking until the
+/// network interface is up.
+///
+/// Use a `WifiManager` to pick between several networks and reconnect.
+#[cfg(target_os = "espidf")]
+pub fn connect(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
+    let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
+        ssid: CONFIG.wifi_ssid.parse().expect("Failed to parse wifi SSID"),
+        password: CONFIG
+            .wifi_password
+            .parse()
+            .expect("Failed to parse wifi password"),
+        auth_method: AuthMethod::WPA2Personal,
+        ..Default::default()
+    });
+
+    wifi.set_configuration(&wifi_configuration)?;
+
+    wifi.start()?;
+    log::info!("Wifi started");
+
+    wifi.connect()?;
+    log::info!("Wifi connected");
+
+    wifi.wait_netif_up()?;
+    log::info!("Wifi netif up");
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::Aes128;
+use anyhow::{anyhow, Result};
+use rand::random;
+
+use super::RESPONSE_LEN;
+use crate::hmac::{constant_time_eq, hmac_sha1};
+
+/// Length of the secret key programmed in the YubiKey slot.
+pub const SECRET_KEY_LEN: usize = 20;
+
+/// Length of the random challenges, kept from the C library this replaces.
+const CHALLENGE_LEN: usize = 57;
+
+/// AES block length, also the length of the IV.
+const BLOCK_LEN: usize = 16;
+
+/// The secret key padded with zeros to whole AES blocks.
+const PADDED_KEY_LEN: usize = (SECRET_KEY_LEN / BLOCK_LEN + 1) * BLOCK_LEN;
+
+/// Length of the record of an enrolled key: challenge, IV and encrypted secret key.
+pub const RECORD_LEN: usize = CHALLENGE_LEN + BLOCK_LEN + PADDED_KEY_LEN;
+
+/// Encrypts `secret`, the key programmed in the YubiKey, under the response to
+/// a new random challenge. Returns the record and that response.
+pub(super) fn seal(
+    secret: &[u8; SECRET_KEY_LEN],
+) -> Result<([u8; RECORD_LEN], [u8; RESPONSE_LEN])> {
+    let mut challenge = [0u8; CHALLENGE_LEN];
+    fill_random(&mut challenge);
+    let response = hmac_sha1(secret, &challenge)?;
+
+    let mut iv = [0u8; BLOCK_LEN];
+    fill_random(&mut iv);
+    let mut padded = [0u8; PADDED_KEY_LEN];
+    padded[..SECRET_KEY_LEN].copy_from_slice(secret);
+    cbc::Encryptor::<Aes128>::new_from_slices(&response[..BLOCK_LEN], &iv)
+        .map_err(|e| anyhow!("Cannot set up AES: {e}"))?
+        .encrypt_padded_mut::<NoPadding>(&mut padded, PADDED_KEY_LEN)
+        .map_err(|e| anyhow!("Cannot encrypt the secret key: {e}"))?;
+
+    let mut record = [0u8; RECORD_LEN];
+    record[..CHALLENGE_LEN].copy_from_slice(&challenge);
+    record[CHALLENGE_LEN..CHALLENGE_LEN + BLOCK_LEN].copy_from_slice(&iv);
+    record[CHALLENGE_LEN + BLOCK_LEN..].copy_from_slice(&padded);
+    Ok((record, response))
+}
+
+/// The challenge a record was sealed under.
+pub(super) fn challenge(record: &[u8; RECORD_LEN]) -> &[u8] {
+    &record[..CHALLENGE_LEN]
+}
+
+/// Decrypts the secret of `record` with `response`, the answer of a YubiKey to
+/// its challenge, and checks the secret produces that same response.
+///
+/// Returns the secret, or `None` if the YubiKey holds another one.
+pub(super) fn unseal(
+    record: &[u8; RECORD_LEN],
+    response: &[u8; RESPONSE_LEN],
+) -> Result<Option<[u8; SECRET_KEY_LEN]>> {
+    let mut record = *record;
+    let (challenge, rest) = record.split_at_mut(CHALLENGE_LEN);
+    let (iv, padded) = rest.split_at_mut(BLOCK_LEN);
+
+    cbc::Decryptor::<Aes128>::new_from_slices(&response[..BLOCK_LEN], iv)
+        .map_err(|e| anyhow!("Cannot set up AES: {e}"))?
+        .decrypt_padded_mut::<NoPadding>(padded)
+        .map_err(|e| anyhow!("Cannot decrypt the secret key: {e}"))?;
+    let secret: [u8; SECRET_KEY_LEN] = padded[..SECRET_KEY_LEN].try_into()?;
+
+    let expected = hmac_sha1(&secret, challenge)?;
+    if !constant_time_eq(response, &expected) {
+        return Ok(None);
+    }
+    Ok(Some(secret))
+}
+
+/// Decodes the hex `secret` of an `EnrollmentRequest`.
+pub(super) fn parse_secret(secret: &str) -> Result<[u8; SECRET_KEY_LEN]> {
+    let mut bytes = [0u8; SECRET_KEY_LEN];
+    if secret.len() != 2 * SECRET_KEY_LEN || !secret.is_ascii() {
+        return Err(anyhow!("Secret must be {SECRET_KEY_LEN} hex bytes"));
+    }
+    for (byte, digits) in bytes.iter_mut().zip(secret.as_bytes().chunks_exact(2)) {
+        let digits = std::str::from_utf8(digits)?;
+        *byte = u8::from_str_radix(digits, 16).map_err(|_| anyhow!("Secret is not hexadecimal"))?;
+    }
+    Ok(bytes)
+}
+
+/// A new random secret key, to program in a YubiKey.
+pub(super) fn generate_secret() -> [u8; SECRET_KEY_LEN] {
+    let mut secret = [0u8; SECRET_KEY_LEN];
+    fill_random(&mut secret);
+    secret
+}
+
+fn fill_random(bytes: &mut [u8]) {
+    for byte in bytes {
+        *byte = random();
+    }
+}
@@ -0,0 +1,63 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+/// Error returned when reading or authenticating a YubiKey through a
+/// `Transport` failing with `E`, such as a `Pn532Error`.
+///
+/// Tells a YubiKey which fails the challenge-response, such as a forged one,
+/// apart from one which could not be read properly.
+#[derive(Debug)]
+pub enum YkError<E> {
+    /// The YubiKey applet was not selected, or the card went away since.
+    NotSelected,
+    /// The YubiKey waits for a touch before answering the challenge.
+    TouchRequired,
+    /// The slot of the YubiKey is not programmed for challenge-response.
+    SlotNotConfigured,
+    /// The YubiKey refused the new configuration of a slot, which is protected
+    /// by an access code.
+    WriteProtected,
+    /// No YubiKey with this serial number is enrolled.
+    NotEnrolled(u32),
+    /// The `Transport` failed to exchange a command with the card.
+    CommError(E),
+    /// A command of this many bytes does not fit in an APDU or in the frames of
+    /// the `Transport`, or a challenge is longer than a YubiKey answers.
+    CommandTooLong(usize),
+    /// The answer of the card makes no sense, for the reason given.
+    InvalidResponse(&'static str),
+    /// The card answered with a status word the command does not expect.
+    UnexpectedStatus([u8; 2]),
+    /// The record of the enrolled YubiKey could not be read or written.
+    StorageError(anyhow::Error),
+    /// The response does not decrypt the enrolled secret: the YubiKey is not
+    /// programmed with it.
+    ResponseMismatch,
+}
+
+impl<E: fmt::Debug> fmt::Display for YkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            YkError::NotSelected => f.write_str("YubiKey applet not selected"),
+            YkError::TouchRequired => f.write_str("YubiKey requires a touch"),
+            YkError::SlotNotConfigured => f.write_str("slot not configured for challenge-response"),
+            YkError::WriteProtected => f.write_str("slot protected by an access code"),
+            YkError::NotEnrolled(serial) => write!(f, "YubiKey {serial} is not enrolled"),
+            YkError::CommError(e) => write!(f, "communication error: {e:?}"),
+            YkError::CommandTooLong(len) => write!(f, "command of {len} bytes is too long"),
+            YkError::InvalidResponse(what) => write!(f, "invalid response: {what}"),
+            YkError::UnexpectedStatus(sw) => write!(f, "unexpected status word {sw:02X?}"),
+            YkError::StorageError(e) => write!(f, "storage error: {e}"),
+            YkError::ResponseMismatch => f.write_str("response does not match the enrolled secret"),
+        }
+    }
+}
+
+impl<E: fmt::Debug> StdError for YkError<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            YkError::StorageError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
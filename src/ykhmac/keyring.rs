@@ -0,0 +1,484 @@
+use anyhow::{anyhow, Result};
+
+use super::enroll::{challenge, generate_secret, seal, unseal};
+use super::{
+    EnrollPermit, SecretStore, Slot, SlotStore, Transport, YkError, YubiKey, RECORD_LEN,
+    SECRET_KEY_LEN,
+};
+use crate::hmac::constant_time_eq;
+
+/// Number of YubiKeys a `Keyring` can hold.
+pub const MAX_KEYS: usize = 8;
+
+/// Bytes of an entry of the table: serial number, slot and record.
+const ENTRY_LEN: usize = 4 + 1 + RECORD_LEN;
+
+/// Slot byte of an unused entry, as left by an erase.
+const UNUSED: u8 = 0xFF;
+
+/// A YubiKey enrolled in a `Keyring`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEntry {
+    pub serial: u32,
+    pub slot: Slot,
+}
+
+/// The secrets of several YubiKeys, each encrypted in its own record under the
+/// response of that YubiKey to its last challenge.
+///
+/// Keys are told apart by serial number. The whole table is kept in a
+/// `SlotStore`, so enrolling, revoking or re-encrypting a key after an
+/// authentication never loses the others on a power loss.
+pub struct Keyring<S> {
+    store: SlotStore<S>,
+    entries: Vec<(KeyEntry, [u8; RECORD_LEN])>,
+}
+
+impl<S: SecretStore> Keyring<S> {
+    /// Opens the table kept in `store`, double-buffered by a `SlotStore`.
+    pub fn new(store: S) -> Result<Self> {
+        let mut store = SlotStore::new(store, MAX_KEYS * ENTRY_LEN)?;
+        let mut table = vec![0u8; MAX_KEYS * ENTRY_LEN];
+        store.read(0, &mut table)?;
+
+        let mut entries = Vec::new();
+        for entry in table.chunks_exact(ENTRY_LEN) {
+            let slot = match entry[4] {
+                1 => Slot::One,
+                2 => Slot::Two,
+                UNUSED => continue,
+                other => return Err(anyhow!("Invalid slot {other} in the keyring")),
+            };
+            let serial = u32::from_le_bytes(entry[..4].try_into()?);
+            entries.push((KeyEntry { serial, slot }, entry[5..].try_into()?));
+        }
+        log::info!("Loaded {} enrolled keys", entries.len());
+        Ok(Self { store, entries })
+    }
+
+    /// The enrolled keys.
+    pub fn keys(&self) -> Vec<KeyEntry> {
+        self.entries.iter().map(|(key, _)| *key).collect()
+    }
+
+    pub fn contains(&self, serial: u32) -> bool {
+        self.find(serial).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gives the underlying store back.
+    pub fn into_inner(self) -> S {
+        self.store.into_inner()
+    }
+
+    /// Enrolls the selected YubiKey, returning its serial number.
+    ///
+    /// Unless `permit` carries a secret, `slot` is programmed with a new
+    /// random secret, and whatever it held before is lost. The key is saved
+    /// before the YubiKey is programmed, and forgotten again if programming
+    /// fails, so a failed save never leaves a YubiKey with a secret no lock has.
+    /// With a `permit` carrying a secret, the YubiKey must already answer with
+    /// it, and is enrolled without being programmed.
+    ///
+    /// The secret never leaves this call: it is neither logged nor returned. A
+    /// YubiKey already enrolled must be revoked first, so its rolling record is
+    /// never overwritten by mistake.
+    pub fn enroll<T: Transport>(
+        &mut self,
+        yubikey: &mut YubiKey<T>,
+        slot: Slot,
+        permit: EnrollPermit,
+    ) -> Result<u32> {
+        let serial = yubikey.serial()?;
+        if self.contains(serial) {
+            return Err(anyhow!("YubiKey {serial} is already enrolled"));
+        }
+        if self.entries.len() >= MAX_KEYS {
+            return Err(anyhow!("Keyring is full, {MAX_KEYS} keys enrolled"));
+        }
+
+        match permit.secret {
+            None => self.program(yubikey, serial, slot)?,
+            Some(secret) => self.accept(yubikey, serial, slot, &secret)?,
+        }
+        log::info!("Enrolled YubiKey {serial}");
+        Ok(serial)
+    }
+
+    /// Forgets the YubiKey with `serial`, returning `false` if it was not enrolled.
+    pub fn revoke(&mut self, serial: u32) -> Result<bool> {
+        let Some(index) = self.find(serial) else {
+            return Ok(false);
+        };
+        let mut entries = self.entries.clone();
+        entries.remove(index);
+        self.save(entries)?;
+        log::info!("Revoked YubiKey {serial}");
+        Ok(true)
+    }
+
+    /// Challenges the selected YubiKey with the record enrolled for its serial
+    /// number, and checks its response decrypts the secret of that record.
+    ///
+    /// On success, the secret is enrolled again under a new challenge.
+    pub fn authenticate<T: Transport>(
+        &mut self,
+        yubikey: &mut YubiKey<T>,
+    ) -> Result<(), YkError<T::Error>> {
+        let serial = yubikey.serial()?;
+        let index = self.find(serial).ok_or(YkError::NotEnrolled(serial))?;
+        let (key, record) = self.entries[index];
+        let response = yubikey.hmac(key.slot, challenge(&record))?;
+        let secret = unseal(&record, &response)
+            .map_err(YkError::StorageError)?
+            .ok_or(YkError::ResponseMismatch)?;
+
+        let mut entries = self.entries.clone();
+        entries[index].1 = seal(&secret).map_err(YkError::StorageError)?.0;
+        self.save(entries).map_err(YkError::StorageError)
+    }
+
+    /// Saves a new random secret for `serial`, then programs it in `slot`.
+    fn program<T: Transport>(
+        &mut self,
+        yubikey: &mut YubiKey<T>,
+        serial: u32,
+        slot: Slot,
+    ) -> Result<()> {
+        let secret = generate_secret();
+        let (record, expected) = seal(&secret)?;
+        let mut entries = self.entries.clone();
+        entries.push((KeyEntry { serial, slot }, record));
+        self.save(entries)?;
+
+        let programmed = yubikey
+            .program_hmac(slot, &secret)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| check(yubikey, slot, &record, &expected));
+        if let Err(e) = programmed {
+            let mut entries = self.entries.clone();
+            entries.retain(|(key, _)| key.serial != serial);
+            if let Err(e) = self.save(entries) {
+                log::warn!("Cannot forget YubiKey {serial}: {e:?}");
+            }
+            return Err(e.context(format!("YubiKey {serial} did not take the new secret")));
+        }
+        Ok(())
+    }
+
+    /// Checks `slot` already holds `secret`, then saves it for `serial`.
+    fn accept<T: Transport>(
+        &mut self,
+        yubikey: &mut YubiKey<T>,
+        serial: u32,
+        slot: Slot,
+        secret: &[u8; SECRET_KEY_LEN],
+    ) -> Result<()> {
+        let (record, expected) = seal(secret)?;
+        check(yubikey, slot, &record, &expected)
+            .map_err(|e| e.context(format!("YubiKey {serial} does not hold the secret")))?;
+        let mut entries = self.entries.clone();
+        entries.push((KeyEntry { serial, slot }, record));
+        self.save(entries)
+    }
+
+    fn find(&self, serial: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(key, _)| key.serial == serial)
+    }
+
+    /// Writes the whole table in one commit, keeping `entries` only if it succeeds.
+    fn save(&mut self, entries: Vec<(KeyEntry, [u8; RECORD_LEN])>) -> Result<()> {
+        let mut table = vec![UNUSED; MAX_KEYS * ENTRY_LEN];
+        for ((key, record), entry) in entries.iter().zip(table.chunks_exact_mut(ENTRY_LEN)) {
+            entry[..4].copy_from_slice(&key.serial.to_le_bytes());
+            entry[4] = match key.slot {
+                Slot::One => 1,
+                Slot::Two => 2,
+            };
+            entry[5..].copy_from_slice(record);
+        }
+        self.store.write(0, &table)?;
+        self.entries = entries;
+        Ok(())
+    }
+}
+
+/// Checks `slot` of the YubiKey answers the challenge of `record` with `expected`.
+fn check<T: Transport>(
+    yubikey: &mut YubiKey<T>,
+    slot: Slot,
+    record: &[u8; RECORD_LEN],
+    expected: &[u8],
+) -> Result<()> {
+    let response = yubikey.hmac(slot, challenge(record))?;
+    if !constant_time_eq(&response, expected) {
+        return Err(anyhow!("Response does not match the secret"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::command::{EnrollmentRequest, SignedEnrollment};
+    use crate::config::mock::MemoryStorage;
+    use crate::hmac::hex;
+    use crate::lock::mock::MockClock;
+    use crate::ykhmac::mock::{RamSecretStore, SimulatedYubiKey};
+    use crate::ykhmac::EnrollmentMode;
+
+    const ERASE_SIZE: usize = 0x1000;
+
+    fn keyring() -> Keyring<RamSecretStore> {
+        Keyring::new(RamSecretStore::new(2 * ERASE_SIZE).with_erase_size(ERASE_SIZE)).unwrap()
+    }
+
+    fn present(serial: u32) -> YubiKey<SimulatedYubiKey> {
+        let mut yubikey = YubiKey::new(SimulatedYubiKey::new(serial));
+        assert!(yubikey.select().unwrap());
+        yubikey
+    }
+
+    /// A permit to program a new secret.
+    fn permit() -> EnrollPermit {
+        EnrollPermit { secret: None }
+    }
+
+    /// A permit to accept `secret`, from a request signed by the backend.
+    fn accept(secret: [u8; SECRET_KEY_LEN]) -> EnrollPermit {
+        let key = b"secret";
+        let request = EnrollmentRequest {
+            door_id: 1,
+            expires_at: 100,
+            secret: Some(hex(&secret)),
+        };
+        let mut mode = EnrollmentMode::new(
+            MockClock::new(),
+            Duration::from_secs(60),
+            MemoryStorage::new(),
+        )
+        .unwrap();
+        mode.authorize(
+            &SignedEnrollment::sign(&request, key).unwrap(),
+            key,
+            1,
+            Some(0),
+        )
+        .unwrap();
+        mode.take().unwrap()
+    }
+
+    #[test]
+    fn enrolls_and_authenticates_a_yubikey() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        assert_eq!(
+            keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap(),
+            1234
+        );
+        assert_eq!(
+            keyring.keys(),
+            vec![KeyEntry {
+                serial: 1234,
+                slot: Slot::Two
+            }]
+        );
+        assert!(yubikey.transport().secret(Slot::Two).is_some());
+        assert!(yubikey.transport().secret(Slot::One).is_none());
+
+        keyring.authenticate(&mut yubikey).unwrap();
+        keyring.authenticate(&mut yubikey).unwrap();
+    }
+
+    #[test]
+    fn authentication_seals_the_secret_under_a_new_challenge() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+        let before = keyring.entries[0].1;
+
+        keyring.authenticate(&mut yubikey).unwrap();
+        let after = keyring.entries[0].1;
+        assert_ne!(challenge(&after), challenge(&before));
+
+        // The new record is the one stored.
+        let keyring = Keyring::new(keyring.into_inner()).unwrap();
+        assert_eq!(keyring.entries[0].1, after);
+    }
+
+    #[test]
+    fn keeps_the_keys_across_restarts() {
+        let mut keyring = keyring();
+        let mut first = present(1);
+        let mut second = present(2);
+        keyring.enroll(&mut first, Slot::Two, permit()).unwrap();
+        keyring.enroll(&mut second, Slot::One, permit()).unwrap();
+        keyring.authenticate(&mut first).unwrap();
+
+        let mut keyring = Keyring::new(keyring.into_inner()).unwrap();
+        assert_eq!(keyring.keys().len(), 2);
+        keyring.authenticate(&mut first).unwrap();
+        keyring.authenticate(&mut second).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_yubikey_with_another_secret() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+
+        // Same serial number, but not the secret the lock programmed.
+        let mut forged = present(1234);
+        forged.transport().set_secret(Slot::Two, Some([0x42; 20]));
+        assert!(matches!(
+            keyring.authenticate(&mut forged),
+            Err(YkError::ResponseMismatch)
+        ));
+        assert!(matches!(
+            keyring.authenticate(&mut present(99)),
+            Err(YkError::NotEnrolled(99))
+        ));
+    }
+
+    #[test]
+    fn refuses_a_yubikey_waiting_for_a_touch() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+        yubikey.transport().require_touch(true);
+        assert!(matches!(
+            keyring.authenticate(&mut yubikey),
+            Err(YkError::TouchRequired)
+        ));
+    }
+
+    #[test]
+    fn refuses_a_yubikey_twice_until_revoked() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+        assert!(keyring.enroll(&mut yubikey, Slot::Two, permit()).is_err());
+
+        assert!(keyring.revoke(1234).unwrap());
+        assert!(!keyring.revoke(1234).unwrap());
+        assert!(keyring.authenticate(&mut yubikey).is_err());
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+        keyring.authenticate(&mut yubikey).unwrap();
+    }
+
+    #[test]
+    fn holds_at_most_max_keys() {
+        let mut keyring = keyring();
+        let mut yubikeys: Vec<_> = (0..MAX_KEYS as u32).map(present).collect();
+        for yubikey in &mut yubikeys {
+            keyring.enroll(yubikey, Slot::Two, permit()).unwrap();
+        }
+        assert!(keyring
+            .enroll(&mut present(MAX_KEYS as u32), Slot::Two, permit())
+            .is_err());
+
+        let mut keyring = Keyring::new(keyring.into_inner()).unwrap();
+        assert_eq!(keyring.keys().len(), MAX_KEYS);
+        for yubikey in &mut yubikeys {
+            keyring.authenticate(yubikey).unwrap();
+        }
+    }
+
+    #[test]
+    fn does_not_enroll_a_write_protected_yubikey() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        yubikey.transport().set_write_protected(true);
+        assert!(keyring.enroll(&mut yubikey, Slot::Two, permit()).is_err());
+        assert!(keyring.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_previous_record_when_it_cannot_be_saved() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        keyring.enroll(&mut yubikey, Slot::Two, permit()).unwrap();
+
+        let mut store = keyring.into_inner();
+        store.fail_writes(true);
+        let mut keyring = Keyring::new(store).unwrap();
+        assert!(matches!(
+            keyring.authenticate(&mut yubikey),
+            Err(YkError::StorageError(_))
+        ));
+
+        let mut store = keyring.into_inner();
+        store.fail_writes(false);
+        let mut keyring = Keyring::new(store).unwrap();
+        keyring.authenticate(&mut yubikey).unwrap();
+    }
+
+    #[test]
+    fn saves_the_key_before_programming_the_yubikey() {
+        let mut store = RamSecretStore::new(2 * ERASE_SIZE).with_erase_size(ERASE_SIZE);
+        store.fail_writes(true);
+        let mut keyring = Keyring::new(store).unwrap();
+        let mut yubikey = present(1234);
+        yubikey
+            .transport()
+            .set_secret(Slot::Two, Some([0x42; SECRET_KEY_LEN]));
+
+        assert!(keyring.enroll(&mut yubikey, Slot::Two, permit()).is_err());
+        assert!(keyring.is_empty());
+        assert_eq!(
+            yubikey.transport().secret(Slot::Two),
+            Some([0x42; SECRET_KEY_LEN])
+        );
+    }
+
+    #[test]
+    fn forgets_the_key_when_programming_fails() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        yubikey.transport().set_write_protected(true);
+        assert!(keyring.enroll(&mut yubikey, Slot::Two, permit()).is_err());
+
+        let keyring = Keyring::new(keyring.into_inner()).unwrap();
+        assert!(keyring.is_empty());
+    }
+
+    #[test]
+    fn accepts_the_secret_a_yubikey_already_holds() {
+        let secret = [0x42; SECRET_KEY_LEN];
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        yubikey.transport().set_secret(Slot::Two, Some(secret));
+        yubikey.transport().set_write_protected(true);
+
+        keyring
+            .enroll(&mut yubikey, Slot::Two, accept(secret))
+            .unwrap();
+        assert_eq!(yubikey.transport().secret(Slot::Two), Some(secret));
+        keyring.authenticate(&mut yubikey).unwrap();
+    }
+
+    #[test]
+    fn does_not_accept_a_secret_the_yubikey_does_not_hold() {
+        let mut keyring = keyring();
+        let mut yubikey = present(1234);
+        yubikey
+            .transport()
+            .set_secret(Slot::Two, Some([0x42; SECRET_KEY_LEN]));
+
+        assert!(keyring
+            .enroll(&mut yubikey, Slot::Two, accept([0x24; SECRET_KEY_LEN]))
+            .is_err());
+        assert!(keyring.is_empty());
+        assert_eq!(
+            yubikey.transport().secret(Slot::Two),
+            Some([0x42; SECRET_KEY_LEN])
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Host-side stand-ins for the persistent memory and the YubiKey, to exercise
+//! a `Keyring` without hardware.
+
+use anyhow::{anyhow, Result};
+
+use super::yubikey::{
+    crc16, ACC_CODE_LEN, CFGFLAG_CHAL_HMAC, CMD_CONFIG_1, CMD_CONFIG_2, CMD_GET_SERIAL, CMD_HMAC_1,
+    CMD_HMAC_2, CONFIG_CFG_FLAGS, CONFIG_KEY, CONFIG_LEN, CONFIG_TKT_FLAGS, CONFIG_UID,
+    INS_API_REQ, INS_SELECT, INS_STATUS, SEL_APP_AID, SW_NOT_FOUND, SW_OK, SW_PRECONDITION,
+    TKTFLAG_CHAL_RESP,
+};
+use super::{SecretStore, Slot, Transport, Version, SECRET_KEY_LEN, YUBIKEY_AID};
+use crate::hmac::hmac_sha1;
+
+/// Residue of the CRC over a configuration followed by its complemented CRC.
+const CRC_OK_RESIDUAL: u16 = 0xF0B8;
+
+const SW_WRONG_LENGTH: [u8; 2] = [0x67, 0x00];
+const SW_INS_NOT_SUPPORTED: [u8; 2] = [0x6D, 0x00];
+
+/// A `SecretStore` backed by a `Vec`, starting erased (all `0xFF`) like flash.
+#[derive(Clone, Debug)]
+pub struct RamSecretStore {
+    bytes: Vec<u8>,
+    erase_size: usize,
+    writes: usize,
+    interrupt_after: Option<usize>,
+    fail_writes: bool,
+}
+
+impl RamSecretStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bytes: vec![0xFF; capacity],
+            erase_size: 1,
+            writes: 0,
+            interrupt_after: None,
+            fail_writes: false,
+        }
+    }
+
+    /// Makes writes erase every block of `erase_size` bytes they touch, like flash.
+    pub fn with_erase_size(mut self, erase_size: usize) -> Self {
+        self.erase_size = erase_size;
+        self
+    }
+
+    /// Simulates a power loss after `writes` more successful writes: the next
+    /// one erases its blocks, writes the first half of its bytes and fails, and
+    /// so do the ones after it.
+    pub fn interrupt_after(&mut self, writes: Option<usize>) {
+        self.interrupt_after = writes.map(|writes| self.writes + writes);
+    }
+
+    /// Number of successful writes.
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+
+    /// Makes every write fail, simulating a worn out flash.
+    pub fn fail_writes(&mut self, fail: bool) {
+        self.fail_writes = fail;
+    }
+
+    /// Raw content, to inspect or corrupt it.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    fn range(&self, offset: usize, len: usize) -> Result<std::ops::Range<usize>> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.bytes.len() => Ok(offset..end),
+            _ => Err(anyhow!("{len} bytes at offset {offset} are out of bounds")),
+        }
+    }
+}
+
+impl SecretStore for RamSecretStore {
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()> {
+        let range = self.range(offset, bytes.len())?;
+        bytes.copy_from_slice(&self.bytes[range]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        if self.fail_writes {
+            return Err(anyhow!("Write failed"));
+        }
+        let range = self.range(offset, bytes.len())?;
+
+        let erased = range.start / self.erase_size * self.erase_size
+            ..range.end.div_ceil(self.erase_size) * self.erase_size;
+        let mut blocks = self.bytes[erased.clone()].to_vec();
+        blocks[range.start - erased.start..range.end - erased.start].copy_from_slice(bytes);
+
+        if self
+            .interrupt_after
+            .is_some_and(|writes| self.writes >= writes)
+        {
+            self.bytes[erased].fill(0xFF);
+            let torn = range.start..range.start + bytes.len() / 2;
+            self.bytes[torn.clone()].copy_from_slice(&bytes[..torn.len()]);
+            return Err(anyhow!("Power lost during write"));
+        }
+        self.bytes[erased].copy_from_slice(&blocks);
+        self.writes += 1;
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn erase_size(&self) -> usize {
+        self.erase_size
+    }
+}
+
+/// A `Transport` answering like a YubiKey with challenge-response configured.
+///
+/// It answers SELECT of `YUBIKEY_AID`, `INS_STATUS`, `CMD_GET_SERIAL`,
+/// `CMD_HMAC_1`/`CMD_HMAC_2` and `CMD_CONFIG_1`/`CMD_CONFIG_2`, and stands in for the `Pn532` anywhere a
+/// `Transport` is taken. Status words follow the real applet: `SW_PRECONDITION`
+/// when a touch is required or nothing was selected, `SW_NOT_FOUND` for a
+/// missing applet or an unprogrammed slot.
+#[derive(Clone, Debug)]
+pub struct SimulatedYubiKey {
+    serial: u32,
+    version: Version,
+    secrets: [Option<[u8; SECRET_KEY_LEN]>; 2],
+    max_command_len: usize,
+    requires_touch: bool,
+    write_protected: bool,
+    /// Programming sequence, moving on with every configuration written.
+    sequence: u8,
+    has_applet: bool,
+    present: bool,
+    selected: bool,
+}
+
+impl SimulatedYubiKey {
+    pub fn new(serial: u32) -> Self {
+        Self {
+            serial,
+            version: Version {
+                major: 5,
+                minor: 4,
+                patch: 3,
+            },
+            secrets: [None, None],
+            max_command_len: 64,
+            requires_touch: false,
+            write_protected: false,
+            sequence: 1,
+            has_applet: true,
+            present: true,
+            selected: false,
+        }
+    }
+
+    /// Programs `slot` with `secret`, or clears it.
+    pub fn set_secret(&mut self, slot: Slot, secret: Option<[u8; SECRET_KEY_LEN]>) {
+        self.secrets[slot_index(slot)] = secret;
+    }
+
+    /// Sets the firmware version reported by `INS_STATUS`.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    /// Set the length of the longest command accepted.
+    pub fn set_max_command_len(&mut self, len: usize) {
+        self.max_command_len = len;
+    }
+
+    /// Makes challenge-response fail as if the YubiKey waited for a touch.
+    pub fn require_touch(&mut self, requires_touch: bool) {
+        self.requires_touch = requires_touch;
+    }
+
+    /// Makes the YubiKey refuse new configurations, as if its slots were
+    /// protected by an access code.
+    pub fn set_write_protected(&mut self, write_protected: bool) {
+        self.write_protected = write_protected;
+    }
+
+    /// Secret programmed in `slot`, if any.
+    pub fn secret(&self, slot: Slot) -> Option<[u8; SECRET_KEY_LEN]> {
+        self.secrets[slot_index(slot)]
+    }
+
+    /// Removes the OTP applet, as on a card which is not a YubiKey.
+    pub fn set_applet(&mut self, has_applet: bool) {
+        self.has_applet = has_applet;
+        self.selected = false;
+    }
+
+    /// Takes the YubiKey away from the reader, failing every exchange.
+    pub fn set_present(&mut self, present: bool) {
+        self.present = present;
+        self.selected = false;
+    }
+
+    /// Firmware version, programming sequence and touch level.
+    fn status(&self) -> Vec<u8> {
+        vec![
+            self.version.major,
+            self.version.minor,
+            self.version.patch,
+            self.sequence,
+            0,
+            0,
+        ]
+    }
+
+    /// Writes the configuration of `slot`, unless it is protected or corrupted.
+    fn configure(&mut self, slot: Slot, data: &[u8]) {
+        if self.write_protected
+            || data.len() != CONFIG_LEN + ACC_CODE_LEN
+            || crc16(&data[..CONFIG_LEN]) != CRC_OK_RESIDUAL
+        {
+            return;
+        }
+        let hmac = data[CONFIG_TKT_FLAGS] & TKTFLAG_CHAL_RESP != 0
+            && data[CONFIG_CFG_FLAGS] & CFGFLAG_CHAL_HMAC == CFGFLAG_CHAL_HMAC;
+        let mut secret = [0u8; SECRET_KEY_LEN];
+        secret[..16].copy_from_slice(&data[CONFIG_KEY..CONFIG_KEY + 16]);
+        secret[16..].copy_from_slice(&data[CONFIG_UID..CONFIG_UID + SECRET_KEY_LEN - 16]);
+        self.secrets[slot_index(slot)] = hmac.then_some(secret);
+        self.sequence = self.sequence.wrapping_add(1);
+    }
+
+    /// Handles a command, returning the response data and status word.
+    fn handle(&mut self, command: &[u8]) -> Vec<u8> {
+        let [_, ins, p1, _, len, rest @ ..] = command else {
+            return SW_WRONG_LENGTH.to_vec();
+        };
+        // Commands without data carry the expected response length instead.
+        let data = if *ins == INS_STATUS || (*ins == INS_API_REQ && *p1 == CMD_GET_SERIAL) {
+            &[][..]
+        } else if rest.len() == usize::from(*len) {
+            rest
+        } else {
+            return SW_WRONG_LENGTH.to_vec();
+        };
+
+        let mut response = match (*ins, *p1) {
+            (INS_SELECT, SEL_APP_AID) if self.has_applet && data == YUBIKEY_AID => {
+                self.selected = true;
+                vec![]
+            }
+            (INS_SELECT, _) => {
+                self.selected = false;
+                return SW_NOT_FOUND.to_vec();
+            }
+            _ if !self.selected => return SW_PRECONDITION.to_vec(),
+            (INS_STATUS, _) => self.status(),
+            (INS_API_REQ, CMD_GET_SERIAL) => self.serial.to_be_bytes().to_vec(),
+            (INS_API_REQ, CMD_HMAC_1 | CMD_HMAC_2) => {
+                let slot = if *p1 == Slot::One.command() {
+                    Slot::One
+                } else {
+                    Slot::Two
+                };
+                match self.secrets[slot_index(slot)] {
+                    Some(_) if self.requires_touch => return SW_PRECONDITION.to_vec(),
+                    Some(secret) => hmac_sha1(&secret, data)
+                        .expect("HMAC takes keys of any length")
+                        .to_vec(),
+                    None => return SW_NOT_FOUND.to_vec(),
+                }
+            }
+            (INS_API_REQ, CMD_CONFIG_1 | CMD_CONFIG_2) => {
+                let slot = if *p1 == Slot::One.config_command() {
+                    Slot::One
+                } else {
+                    Slot::Two
+                };
+                self.configure(slot, data);
+                self.status()
+            }
+            _ => return SW_INS_NOT_SUPPORTED.to_vec(),
+        };
+        response.extend_from_slice(&SW_OK);
+        response
+    }
+}
+
+/// Error of a failed exchange with a `SimulatedYubiKey`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulatedError {
+    /// The YubiKey was taken away from the reader.
+    NotPresent,
+    /// The command is longer than `set_max_command_len` allows.
+    CommandTooLong(usize),
+}
+
+impl Transport for SimulatedYubiKey {
+    type Error = SimulatedError;
+
+    fn exchange(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, SimulatedError> {
+        if !self.present {
+            return Err(SimulatedError::NotPresent);
+        }
+        if command.len() > self.max_command_len {
+            return Err(SimulatedError::CommandTooLong(command.len()));
+        }
+        let answer = self.handle(command);
+        let len = answer.len().min(response.len());
+        response[..len].copy_from_slice(&answer[..len]);
+        Ok(len)
+    }
+
+    fn max_command_len(&self) -> usize {
+        self.max_command_len
+    }
+}
+
+fn slot_index(slot: Slot) -> usize {
+    match slot {
+        Slot::One => 0,
+        Slot::Two => 1,
+    }
+}
@@ -0,0 +1,115 @@
+//! YubiKey HMAC-SHA1 challenge-response, to unlock with a YubiKey held to the reader.
+//!
+//! A `YubiKey` speaks the APDU protocol of the YubiKey applet over any
+//! `Transport`, such as the `Pn532` reader. A `Keyring` keeps the secret key of
+//! each enrolled YubiKey encrypted with the response of that YubiKey to a
+//! random challenge: only the YubiKey programmed with the same secret can
+//! decrypt it again. Every successful authentication re-encrypts the secret
+//! with a new challenge, so a recorded response cannot be replayed.
+//!
+//! Secrets are generated on the lock and programmed in the YubiKey while an
+//! `EnrollmentMode` is open, or accepted once from a request signed by the
+//! backend for a YubiKey already programmed, such as for another door. They
+//! are never logged.
+
+use std::fmt;
+
+use anyhow::Result;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+mod enroll;
+pub use enroll::{RECORD_LEN, SECRET_KEY_LEN};
+
+mod error;
+pub use error::YkError;
+
+mod keyring;
+pub use keyring::{KeyEntry, Keyring, MAX_KEYS};
+
+mod mode;
+pub use mode::{EnrollPermit, EnrollmentMode, BUTTON_HOLD};
+
+#[cfg(target_os = "espidf")]
+mod nvs;
+#[cfg(target_os = "espidf")]
+pub use nvs::NvsSecretStore;
+
+#[cfg(target_os = "espidf")]
+mod partition;
+#[cfg(target_os = "espidf")]
+pub use partition::PartitionSecretStore;
+
+#[cfg(target_os = "espidf")]
+mod pn532;
+#[cfg(target_os = "espidf")]
+pub use pn532::{initialize_pn532, YubiKeyReader, PN532_BUF_SIZE};
+
+mod slots;
+pub use slots::SlotStore;
+
+mod yubikey;
+pub use yubikey::{Slot, Version, YubiKey, RESPONSE_LEN, YUBIKEY_AID};
+
+/// Label of the data partition holding the enrolled secret in `partitions.csv`.
+pub const PARTITION_LABEL: &str = "ykhmac";
+
+/// NVS namespace of the `EnrollmentMode`.
+pub const NVS_NAMESPACE: &str = "lynx_enroll";
+
+/// Sends APDUs to a smart card, such as a YubiKey in front of an NFC reader.
+pub trait Transport {
+    /// Error of a failed exchange, held by `YkError::CommError`.
+    type Error: fmt::Debug + Send + Sync + 'static;
+
+    /// Sends `command` and reads the response, status word included, into
+    /// `response`. Returns the length of the response.
+    fn exchange(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Length of the longest command the transport can send.
+    fn max_command_len(&self) -> usize;
+}
+
+/// Persistent memory of a `Keyring`, holding the encrypted secret keys.
+///
+/// Offsets are relative to the start of the store.
+pub trait SecretStore {
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()>;
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()>;
+
+    /// Size of the store in bytes.
+    fn capacity(&self) -> usize;
+
+    /// Size of the blocks a write may erase as a whole, 1 if writes never erase
+    /// more than they write.
+    fn erase_size(&self) -> usize {
+        1
+    }
+}
+
+impl<T: SecretStore + ?Sized> SecretStore for Box<T> {
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()> {
+        (**self).read(offset, bytes)
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        (**self).write(offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        (**self).capacity()
+    }
+
+    fn erase_size(&self) -> usize {
+        (**self).erase_size()
+    }
+}
+
+/// Outcome of waiting for a credential on the reader.
+pub enum YubiKeyResult {
+    IsYubiKey,
+    NotYubiKey,
+    Error(anyhow::Error),
+}
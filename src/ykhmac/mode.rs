@@ -0,0 +1,297 @@
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use super::enroll::parse_secret;
+use super::SECRET_KEY_LEN;
+use crate::command::SignedEnrollment;
+use crate::config::ConfigStorage;
+use crate::lock::Clock;
+
+/// How long the enrollment button must be held to open the `EnrollmentMode`.
+pub const BUTTON_HOLD: Duration = Duration::from_secs(5);
+
+/// Key of the `expires_at` of the last accepted request in the `ConfigStorage`
+/// of an `EnrollmentMode`.
+const LAST_REQUEST_KEY: &str = "last_request";
+
+/// Proof that enrolling one YubiKey was authorized, used up by `Keyring::enroll`.
+///
+/// Only an open `EnrollmentMode` hands one out.
+pub struct EnrollPermit {
+    /// Secret the YubiKey already holds, to accept instead of programming a new one.
+    pub(super) secret: Option<[u8; SECRET_KEY_LEN]>,
+}
+
+impl fmt::Debug for EnrollPermit {
+    /// Never shows the secret.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EnrollPermit")
+            .field("accept", &self.secret.is_some())
+            .finish()
+    }
+}
+
+/// Window during which the next YubiKey presented may be enrolled.
+///
+/// It opens when the enrollment button is held for `BUTTON_HOLD`, or on an
+/// `EnrollmentRequest` signed by the backend. It closes once its permit is
+/// taken, or after `window` if no YubiKey was presented.
+///
+/// A hold only counts once the button was seen released, so a button stuck
+/// down, or held through a power cycle, never opens it. The last request
+/// accepted is kept in a `ConfigStorage`, such as an NVS namespace, so a
+/// recorded request cannot be replayed after a restart either.
+pub struct EnrollmentMode<C, S> {
+    clock: C,
+    window: Duration,
+    storage: S,
+    open_until: Option<Duration>,
+    /// Secret of the request which opened the mode, if it had one.
+    secret: Option<[u8; SECRET_KEY_LEN]>,
+    /// Whether the button was seen released since the mode was created.
+    released: bool,
+    /// When the button was pressed, and whether that hold already opened the mode.
+    pressed_since: Option<(Duration, bool)>,
+    /// `expires_at` of the last request accepted, so it cannot be replayed.
+    last_request: Option<u64>,
+}
+
+impl<C: Clock, S: ConfigStorage> EnrollmentMode<C, S> {
+    pub fn new(clock: C, window: Duration, mut storage: S) -> Result<Self> {
+        let last_request = match storage.read(LAST_REQUEST_KEY)? {
+            Some(expires_at) => Some(expires_at.parse()?),
+            None => None,
+        };
+        Ok(Self {
+            clock,
+            window,
+            storage,
+            open_until: None,
+            secret: None,
+            released: false,
+            pressed_since: None,
+            last_request,
+        })
+    }
+
+    /// Gives the storage back.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open_until
+            .is_some_and(|until| self.clock.now() < until)
+    }
+
+    /// Feeds the state of the enrollment button, opening the mode once per hold
+    /// of `BUTTON_HOLD`. Returns whether it just opened.
+    pub fn button(&mut self, pressed: bool) -> bool {
+        let now = self.clock.now();
+        if !pressed {
+            self.released = true;
+            self.pressed_since = None;
+            return false;
+        }
+        if !self.released {
+            return false;
+        }
+        match self.pressed_since {
+            Some((since, false)) if now - since >= BUTTON_HOLD => {
+                self.pressed_since = Some((since, true));
+                log::info!("Enrollment authorized by a button hold");
+                self.open(None);
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.pressed_since = Some((now, false));
+                false
+            }
+        }
+    }
+
+    /// Checks `request` against `key`, the key shared with the backend, and
+    /// opens the mode if it is meant for `door_id` and still valid at Unix time
+    /// `now`. A request is only accepted once.
+    ///
+    /// `now` is `None` while the clock is not synchronized, in which case no
+    /// request can be accepted. An empty `key` is refused, as anyone could sign
+    /// requests then.
+    pub fn authorize(
+        &mut self,
+        request: &SignedEnrollment,
+        key: &[u8],
+        door_id: u32,
+        now: Option<u64>,
+    ) -> Result<()> {
+        if key.is_empty() {
+            return Err(anyhow!("No key to verify enrollment requests with"));
+        }
+        let request = request.verify(key)?;
+        if request.door_id != door_id {
+            return Err(anyhow!(
+                "Enrollment request is for door {}",
+                request.door_id
+            ));
+        }
+        let now = now.ok_or_else(|| anyhow!("Time is not synchronized"))?;
+        if now >= request.expires_at {
+            return Err(anyhow!("Enrollment request expired"));
+        }
+        if self
+            .last_request
+            .is_some_and(|last| request.expires_at <= last)
+        {
+            return Err(anyhow!("Enrollment request was already used"));
+        }
+        let secret = request.secret.as_deref().map(parse_secret).transpose()?;
+
+        // Stored before the mode opens, so it never opens twice for one request.
+        self.storage
+            .write(LAST_REQUEST_KEY, &request.expires_at.to_string())?;
+        self.last_request = Some(request.expires_at);
+        log::info!("Enrollment authorized by the backend");
+        self.open(secret);
+        Ok(())
+    }
+
+    /// Closes the mode without enrolling anything.
+    pub fn cancel(&mut self) {
+        self.open_until = None;
+        self.secret = None;
+    }
+
+    /// Takes the permit to enroll one YubiKey, closing the mode.
+    pub fn take(&mut self) -> Option<EnrollPermit> {
+        let open = self.is_open();
+        let secret = self.secret.take();
+        self.open_until = None;
+        open.then_some(EnrollPermit { secret })
+    }
+
+    fn open(&mut self, secret: Option<[u8; SECRET_KEY_LEN]>) {
+        self.open_until = Some(self.clock.now() + self.window);
+        self.secret = secret;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::EnrollmentRequest;
+    use crate::config::mock::MemoryStorage;
+    use crate::lock::mock::MockClock;
+
+    const KEY: &[u8] = b"secret";
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn mode(clock: &MockClock) -> EnrollmentMode<MockClock, MemoryStorage> {
+        EnrollmentMode::new(clock.clone(), WINDOW, MemoryStorage::new()).unwrap()
+    }
+
+    fn request(door_id: u32, expires_at: u64) -> SignedEnrollment {
+        let request = EnrollmentRequest {
+            door_id,
+            expires_at,
+            secret: None,
+        };
+        SignedEnrollment::sign(&request, KEY).unwrap()
+    }
+
+    #[test]
+    fn opens_once_per_button_hold() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        assert!(!mode.button(false));
+        assert!(!mode.button(true));
+        clock.advance(BUTTON_HOLD);
+        assert!(mode.button(true));
+        assert!(!mode.button(true));
+        assert!(mode.is_open());
+
+        let permit = mode.take().unwrap();
+        assert!(permit.secret.is_none());
+        assert!(!mode.is_open());
+        assert!(mode.take().is_none());
+    }
+
+    #[test]
+    fn ignores_a_button_held_since_boot() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        mode.button(true);
+        clock.advance(BUTTON_HOLD * 2);
+        assert!(!mode.button(true));
+        assert!(!mode.is_open());
+
+        mode.button(false);
+        mode.button(true);
+        clock.advance(BUTTON_HOLD);
+        assert!(mode.button(true));
+    }
+
+    #[test]
+    fn closes_after_the_window() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        mode.authorize(&request(1, 100), KEY, 1, Some(0)).unwrap();
+        clock.advance(WINDOW);
+        assert!(mode.take().is_none());
+    }
+
+    #[test]
+    fn refuses_a_request_for_another_door_or_an_expired_one() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        assert!(mode.authorize(&request(2, 100), KEY, 1, Some(0)).is_err());
+        assert!(mode.authorize(&request(1, 100), KEY, 1, Some(100)).is_err());
+        assert!(mode.authorize(&request(1, 100), KEY, 1, None).is_err());
+        assert!(mode.authorize(&request(1, 100), b"", 1, Some(0)).is_err());
+        assert!(mode
+            .authorize(&request(1, 100), b"other", 1, Some(0))
+            .is_err());
+        assert!(!mode.is_open());
+    }
+
+    #[test]
+    fn refuses_a_replayed_request_after_a_restart() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        mode.authorize(&request(1, 100), KEY, 1, Some(0)).unwrap();
+        assert!(mode.authorize(&request(1, 100), KEY, 1, Some(0)).is_err());
+
+        let mut mode = EnrollmentMode::new(clock, WINDOW, mode.into_storage()).unwrap();
+        assert!(mode.authorize(&request(1, 100), KEY, 1, Some(0)).is_err());
+        mode.authorize(&request(1, 101), KEY, 1, Some(0)).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_request_it_cannot_remember() {
+        let clock = MockClock::new();
+        let mut storage = MemoryStorage::new();
+        storage.fail_writes(true);
+        let mut mode = EnrollmentMode::new(clock, WINDOW, storage).unwrap();
+        assert!(mode.authorize(&request(1, 100), KEY, 1, Some(0)).is_err());
+        assert!(!mode.is_open());
+    }
+
+    #[test]
+    fn hands_over_the_secret_to_accept() {
+        let clock = MockClock::new();
+        let mut mode = mode(&clock);
+        let request = EnrollmentRequest {
+            door_id: 1,
+            expires_at: 100,
+            secret: Some("42".repeat(SECRET_KEY_LEN)),
+        };
+        let signed = SignedEnrollment::sign(&request, KEY).unwrap();
+        mode.authorize(&signed, KEY, 1, Some(0)).unwrap();
+
+        let permit = mode.take().unwrap();
+        assert_eq!(permit.secret, Some([0x42; SECRET_KEY_LEN]));
+        assert_eq!(format!("{permit:?}"), "EnrollPermit { accept: true }");
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Result};
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+use super::SecretStore;
+
+/// NVS key of the persistent memory image. At most 15 characters.
+const KEY: &str = "ykhmac";
+
+/// Size of the image, with room for both slots of a `SlotStore`.
+const CAPACITY: usize = 2048;
+
+/// `SecretStore` keeping the whole persistent memory as one blob in an NVS namespace.
+///
+/// Unlike a raw partition, NVS spreads its writes over the partition and
+/// survives a power loss in the middle of one.
+pub struct NvsSecretStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl NvsSecretStore {
+    pub fn new(partition: EspDefaultNvsPartition, namespace: &str) -> Result<Self> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, namespace, true)?,
+        })
+    }
+
+    /// Reads the stored image, erased (all `0xFF`) if it was never written.
+    fn image(&self) -> Result<[u8; CAPACITY]> {
+        let mut image = [0xFF; CAPACITY];
+        self.nvs.get_raw(KEY, &mut image)?;
+        Ok(image)
+    }
+}
+
+impl SecretStore for NvsSecretStore {
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()> {
+        let image = self.image()?;
+        let end = offset + bytes.len();
+        bytes.copy_from_slice(
+            image
+                .get(offset..end)
+                .ok_or_else(|| anyhow!("Read past the end of the stored secret"))?,
+        );
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let mut image = self.image()?;
+        let end = offset + bytes.len();
+        image
+            .get_mut(offset..end)
+            .ok_or_else(|| anyhow!("Write past the end of the stored secret"))?
+            .copy_from_slice(bytes);
+        self.nvs.set_raw(KEY, &image)?;
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        CAPACITY
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Result};
+use embedded_storage::{ReadStorage, Storage};
+
+use super::SecretStore;
+use crate::partition::{Partition, SECTOR_SIZE};
+
+/// `SecretStore` writing directly to a data `Partition`, found by its label.
+///
+/// A write erases and rewrites the whole sectors it touches, keeping the rest
+/// of their content.
+pub struct PartitionSecretStore {
+    partition: Partition,
+}
+
+impl PartitionSecretStore {
+    /// Looks up the data partition named `label` in the partition table.
+    pub fn find(label: &str) -> Result<Self> {
+        Ok(Self {
+            partition: Partition::find(label)?,
+        })
+    }
+}
+
+fn flash_offset(offset: usize) -> Result<u32> {
+    u32::try_from(offset).map_err(|_| anyhow!("Offset {offset} exceeds the partition"))
+}
+
+impl SecretStore for PartitionSecretStore {
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()> {
+        let len = bytes.len();
+        ReadStorage::read(&mut self.partition, flash_offset(offset)?, bytes)
+            .map_err(|e| anyhow!("Cannot read {len} bytes at offset {offset}: {e:?}"))
+    }
+
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let len = bytes.len();
+        Storage::write(&mut self.partition, flash_offset(offset)?, bytes)
+            .map_err(|e| anyhow!("Cannot write {len} bytes at offset {offset}: {e:?}"))
+    }
+
+    fn capacity(&self) -> usize {
+        ReadStorage::capacity(&self.partition)
+    }
+
+    fn erase_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+}
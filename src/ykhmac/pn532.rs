@@ -0,0 +1,82 @@
+use core::borrow::Borrow;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use esp_idf_svc::hal::spi::SpiDriver;
+
+use super::{Transport, YubiKey, YubiKeyResult};
+use crate::{Pn532, Pn532Error};
+
+/// PN532 frame buffer size. Must be big enough to hold any expected responses.
+pub const PN532_BUF_SIZE: usize = 128;
+
+/// Bytes of a PN532 frame around the data of an `InDataExchange`: preamble,
+/// length, checksums, command and target number.
+const FRAME_OVERHEAD: usize = 10;
+
+/// A YubiKey in front of a PN532 reader.
+pub type YubiKeyReader<'d> = YubiKey<Pn532<'d, SpiDriver<'d>, PN532_BUF_SIZE>>;
+
+/// Configures the PN532 to look for ISO14443A cards.
+pub fn initialize_pn532<'d, S, const N: usize>(
+    pn532: &mut Pn532<'d, S, N>,
+) -> Result<(), Pn532Error>
+where
+    S: Borrow<SpiDriver<'d>> + 'd,
+{
+    if let Err(e) = pn532.print_firmware_version() {
+        log::error!("Cannot get firmware version! {e:?}");
+        return Err(e);
+    };
+    if let Err(e) = pn532.sam_config() {
+        log::error!("Cannot set SAM config! {e:?}");
+        return Err(e);
+    };
+    if let Err(e) = pn532.set_passive_activation_retries(0xFF) {
+        log::error!("Cannot set retries! {e:?}");
+        return Err(e);
+    };
+    log::info!("Initialized PN532");
+    Ok(())
+}
+
+impl<'d, S, const N: usize> Transport for Pn532<'d, S, N>
+where
+    S: Borrow<SpiDriver<'d>> + 'd,
+{
+    type Error = Pn532Error;
+
+    fn exchange(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Pn532Error> {
+        let len = self.in_data_exchange(command, response)?;
+        Ok(len.into())
+    }
+
+    fn max_command_len(&self) -> usize {
+        N - FRAME_OVERHEAD
+    }
+}
+
+impl<'d, S, const N: usize> YubiKey<Pn532<'d, S, N>>
+where
+    S: Borrow<SpiDriver<'d>> + 'd,
+{
+    /// Waits for an NFC target and checks whether it is a YubiKey.
+    pub fn wait_for_target(&mut self, timeout: Duration) -> YubiKeyResult {
+        if let Err(e) = self.transport().inlist_passive_target(timeout) {
+            return YubiKeyResult::Error(anyhow!("No NFC target: {e:?}"));
+        }
+        match self.select() {
+            Ok(true) => {
+                log::info!("Select OK");
+                YubiKeyResult::IsYubiKey
+            }
+            Ok(false) => YubiKeyResult::NotYubiKey,
+            Err(e) => {
+                // Cards without the applet may not answer at all.
+                log::debug!("Cannot select the YubiKey applet: {e:?}");
+                YubiKeyResult::NotYubiKey
+            }
+        }
+    }
+}
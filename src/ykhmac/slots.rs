@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Result};
+
+use super::SecretStore;
+use crate::audit::crc32;
+
+/// Bytes of the generation counter before the record, and of the CRC after it.
+const HEADER_LEN: usize = 4;
+const CRC_LEN: usize = 4;
+
+/// Makes the record of the ykhmac library survive a power loss while it is rewritten.
+///
+/// The library rewrites its record in several calls, front to back, after every
+/// successful authentication. Those writes are staged in RAM, and only the one
+/// reaching the end of the record commits it, to the slot not holding the
+/// current record, with the next generation and a CRC. On boot, the valid slot
+/// with the highest generation wins, so an interrupted commit leaves the
+/// previous record in place.
+///
+/// Slots start on separate erase units of the underlying store, so writing one
+/// never erases the other.
+pub struct SlotStore<S> {
+    store: S,
+    len: usize,
+    stride: usize,
+    /// Slot of the committed record and its generation, if any.
+    active: Option<(usize, u32)>,
+    /// The committed record, with the staged writes applied.
+    staged: Vec<u8>,
+}
+
+impl<S: SecretStore> SlotStore<S> {
+    /// Opens the two slots of `len` bytes records kept in `store`.
+    pub fn new(store: S, len: usize) -> Result<Self> {
+        let erase_size = store.erase_size().max(1);
+        let stride = (HEADER_LEN + len + CRC_LEN).div_ceil(erase_size) * erase_size;
+        if 2 * stride > store.capacity() {
+            return Err(anyhow!(
+                "Secret store of {} bytes cannot hold two slots of {stride} bytes",
+                store.capacity()
+            ));
+        }
+
+        let mut slots = Self {
+            store,
+            len,
+            stride,
+            active: None,
+            staged: vec![0xFF; len],
+        };
+        for slot in 0..2 {
+            let Some((generation, record)) = slots.read_slot(slot)? else {
+                continue;
+            };
+            if slots.active.map_or(true, |(_, active)| generation > active) {
+                slots.active = Some((slot, generation));
+                slots.staged = record;
+            }
+        }
+        match slots.active {
+            Some((slot, generation)) => {
+                log::info!("Loaded secret record generation {generation} from slot {slot}")
+            }
+            None => log::warn!("No valid secret record, the key must be enrolled"),
+        }
+        Ok(slots)
+    }
+
+    /// Generation of the committed record, if any.
+    pub fn generation(&self) -> Option<u32> {
+        self.active.map(|(_, generation)| generation)
+    }
+
+    /// Gives the underlying store back.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Writes the staged record to the inactive slot, making it the committed one.
+    fn commit(&mut self) -> Result<()> {
+        let (slot, generation) = match self.active {
+            Some((slot, generation)) => (1 - slot, generation.wrapping_add(1)),
+            None => (0, 0),
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.len + CRC_LEN);
+        bytes.extend_from_slice(&generation.to_le_bytes());
+        bytes.extend_from_slice(&self.staged);
+        bytes.extend_from_slice(&crc32(&bytes).to_le_bytes());
+        self.store.write(slot * self.stride, &bytes)?;
+
+        // Only trust the new slot once it reads back intact.
+        match self.read_slot(slot)? {
+            Some((found, record)) if found == generation && record == self.staged => {}
+            _ => return Err(anyhow!("Secret record in slot {slot} did not read back")),
+        }
+        self.active = Some((slot, generation));
+        log::info!("Committed secret record generation {generation} to slot {slot}");
+        Ok(())
+    }
+
+    /// Reads the record in `slot`, returning `None` if it is erased or torn.
+    fn read_slot(&mut self, slot: usize) -> Result<Option<(u32, Vec<u8>)>> {
+        let mut bytes = vec![0u8; HEADER_LEN + self.len + CRC_LEN];
+        self.store.read(slot * self.stride, &mut bytes)?;
+
+        let (data, crc) = bytes.split_at(HEADER_LEN + self.len);
+        if crc32(data).to_le_bytes() != crc {
+            return Ok(None);
+        }
+        let (generation, record) = data.split_at(HEADER_LEN);
+        let generation = u32::from_le_bytes(generation.try_into()?);
+        Ok(Some((generation, record.to_vec())))
+    }
+
+    fn range(&self, offset: usize, len: usize) -> Result<std::ops::Range<usize>> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(offset..end),
+            _ => Err(anyhow!(
+                "{len} bytes at offset {offset} exceed the {} bytes record",
+                self.len
+            )),
+        }
+    }
+}
+
+impl<S: SecretStore> SecretStore for SlotStore<S> {
+    /// Reads the committed record, with the writes staged since.
+    fn read(&mut self, offset: usize, bytes: &mut [u8]) -> Result<()> {
+        let range = self.range(offset, bytes.len())?;
+        bytes.copy_from_slice(&self.staged[range]);
+        Ok(())
+    }
+
+    /// Stages `bytes`, committing the record if they reach its end.
+    ///
+    /// A failed commit drops the staged writes, leaving the previous record.
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let range = self.range(offset, bytes.len())?;
+        let end = range.end;
+        self.staged[range].copy_from_slice(bytes);
+        if end < self.len {
+            return Ok(());
+        }
+
+        let result = self.commit();
+        if result.is_err() {
+            self.staged = match self.active {
+                Some((slot, _)) => self.read_slot(slot)?.map(|(_, record)| record),
+                None => None,
+            }
+            .unwrap_or_else(|| vec![0xFF; self.len]);
+        }
+        result
+    }
+
+    fn capacity(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ykhmac::mock::RamSecretStore;
+
+    const LEN: usize = 40;
+    const ERASE_SIZE: usize = 64;
+
+    /// A store with generations 0 to `generations - 1` committed, the last one
+    /// holding `record(generations - 1)`.
+    fn committed(generations: u8) -> SlotStore<RamSecretStore> {
+        let store = RamSecretStore::new(4 * ERASE_SIZE).with_erase_size(ERASE_SIZE);
+        let mut slots = SlotStore::new(store, LEN).unwrap();
+        for generation in 0..generations {
+            update(&mut slots, generation).unwrap();
+        }
+        slots
+    }
+
+    fn record(fill: u8) -> Vec<u8> {
+        vec![fill; LEN]
+    }
+
+    /// Rewrites the record front to back in several writes, like the library.
+    fn update(slots: &mut SlotStore<RamSecretStore>, fill: u8) -> Result<()> {
+        let record = record(fill);
+        for (index, chunk) in record.chunks(16).enumerate() {
+            slots.write(index * 16, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn read(slots: &mut SlotStore<RamSecretStore>) -> Vec<u8> {
+        let mut bytes = vec![0u8; LEN];
+        slots.read(0, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn commits_only_the_write_reaching_the_end() {
+        let mut slots = committed(1);
+        let writes = slots.store.writes();
+        slots.write(0, &[7; 16]).unwrap();
+        assert_eq!(slots.store.writes(), writes);
+        assert_eq!(read(&mut slots)[..16], [7; 16]);
+
+        slots.write(16, &[7; LEN - 16]).unwrap();
+        assert_eq!(slots.store.writes(), writes + 1);
+        assert_eq!(slots.generation(), Some(1));
+    }
+
+    #[test]
+    fn reopens_the_newest_generation() {
+        let slots = committed(3);
+        let mut slots = SlotStore::new(slots.into_inner(), LEN).unwrap();
+        assert_eq!(slots.generation(), Some(2));
+        assert_eq!(read(&mut slots), record(2));
+    }
+
+    #[test]
+    fn keeps_the_previous_generation_when_a_commit_is_interrupted() {
+        for generations in 1..=2 {
+            let writes = {
+                let mut slots = committed(generations);
+                let before = slots.store.writes();
+                update(&mut slots, 0xAA).unwrap();
+                slots.store.writes() - before
+            };
+            for interrupted in 0..writes {
+                let mut slots = committed(generations);
+                slots.store.interrupt_after(Some(interrupted));
+                assert!(update(&mut slots, 0xAA).is_err());
+                // The staged writes are dropped, the record is the previous one.
+                assert_eq!(read(&mut slots), record(generations - 1));
+
+                let mut store = slots.into_inner();
+                store.interrupt_after(None);
+                let mut slots = SlotStore::new(store, LEN).unwrap();
+                assert_eq!(slots.generation(), Some(u32::from(generations) - 1));
+                assert_eq!(read(&mut slots), record(generations - 1));
+
+                // The next commit goes through.
+                update(&mut slots, 0xBB).unwrap();
+                assert_eq!(slots.generation(), Some(u32::from(generations)));
+            }
+        }
+    }
+
+    #[test]
+    fn loses_nothing_when_the_first_commit_is_interrupted() {
+        let store = RamSecretStore::new(4 * ERASE_SIZE).with_erase_size(ERASE_SIZE);
+        let mut slots = SlotStore::new(store, LEN).unwrap();
+        slots.store.interrupt_after(Some(0));
+        assert!(update(&mut slots, 0xAA).is_err());
+
+        let slots = SlotStore::new(slots.into_inner(), LEN).unwrap();
+        assert_eq!(slots.generation(), None);
+    }
+
+    #[test]
+    fn ignores_a_corrupted_slot() {
+        let slots = committed(2);
+        let mut store = slots.into_inner();
+        // Generation 1 went to the second slot.
+        store.bytes_mut()[ERASE_SIZE + HEADER_LEN] ^= 0xFF;
+
+        let mut slots = SlotStore::new(store, LEN).unwrap();
+        assert_eq!(slots.generation(), Some(0));
+        assert_eq!(read(&mut slots), record(0));
+    }
+
+    #[test]
+    fn refuses_a_store_too_small_for_two_slots() {
+        let store = RamSecretStore::new(ERASE_SIZE).with_erase_size(ERASE_SIZE);
+        assert!(SlotStore::new(store, LEN).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use super::{Transport, YkError, SECRET_KEY_LEN};
+
+/// AID of the Yubico OTP applet, which also serves challenge-response.
+pub const YUBIKEY_AID: [u8; 7] = [0xA0, 0x00, 0x00, 0x05, 0x27, 0x20, 0x01];
+
+/// Length of a HMAC-SHA1 response.
+pub const RESPONSE_LEN: usize = 20;
+
+/// Longest challenge a YubiKey answers.
+const MAX_CHALLENGE_LEN: usize = 64;
+
+pub(super) const CLA_ISO: u8 = 0x00;
+pub(super) const INS_SELECT: u8 = 0xA4;
+pub(super) const INS_STATUS: u8 = 0x03;
+pub(super) const INS_API_REQ: u8 = 0x01;
+pub(super) const SEL_APP_AID: u8 = 0x04;
+pub(super) const CMD_CONFIG_1: u8 = 0x01;
+pub(super) const CMD_CONFIG_2: u8 = 0x03;
+pub(super) const CMD_GET_SERIAL: u8 = 0x10;
+pub(super) const CMD_HMAC_1: u8 = 0x30;
+pub(super) const CMD_HMAC_2: u8 = 0x38;
+
+/// Length of a slot configuration, CRC included, and of the access code sent after it.
+pub(super) const CONFIG_LEN: usize = 52;
+pub(super) const ACC_CODE_LEN: usize = 6;
+
+/// Offsets in a slot configuration. An HMAC secret is split between the 16
+/// bytes of the key field and the start of the uid field.
+pub(super) const CONFIG_UID: usize = 16;
+pub(super) const CONFIG_KEY: usize = 22;
+pub(super) const CONFIG_EXT_FLAGS: usize = 45;
+pub(super) const CONFIG_TKT_FLAGS: usize = 46;
+pub(super) const CONFIG_CFG_FLAGS: usize = 47;
+pub(super) const CONFIG_CRC: usize = 50;
+
+const EXTFLAG_SERIAL_API_VISIBLE: u8 = 0x04;
+pub(super) const TKTFLAG_CHAL_RESP: u8 = 0x40;
+pub(super) const CFGFLAG_CHAL_HMAC: u8 = 0x22;
+const CFGFLAG_HMAC_LT64: u8 = 0x04;
+
+pub(super) const SW_OK: [u8; 2] = [0x90, 0x00];
+pub(super) const SW_PRECONDITION: [u8; 2] = [0x69, 0x85];
+pub(super) const SW_NOT_FOUND: [u8; 2] = [0x6A, 0x82];
+
+/// Configuration slot of a YubiKey, each with its own HMAC secret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    One,
+    Two,
+}
+
+impl Slot {
+    pub(super) fn command(self) -> u8 {
+        match self {
+            Slot::One => CMD_HMAC_1,
+            Slot::Two => CMD_HMAC_2,
+        }
+    }
+
+    pub(super) fn config_command(self) -> u8 {
+        match self {
+            Slot::One => CMD_CONFIG_1,
+            Slot::Two => CMD_CONFIG_2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl Version {
+    pub fn as_string(&self) -> String {
+        format!("{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(f, "{}", self.as_string())
+    }
+}
+
+/// A YubiKey reached through a `Transport`.
+pub struct YubiKey<T> {
+    transport: T,
+    /// Whether the applet answered the last SELECT, with no failed exchange since.
+    selected: bool,
+}
+
+impl<T: Transport> YubiKey<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            selected: false,
+        }
+    }
+
+    /// Get a mutable reference to the underlying transport.
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Selects the YubiKey applet, returning `false` if the card does not have it.
+    pub fn select(&mut self) -> Result<bool, YkError<T::Error>> {
+        self.selected = false;
+        let mut response = [0u8; 12];
+        let len = self.send(INS_SELECT, SEL_APP_AID, &YUBIKEY_AID, &mut response)?;
+        match status(Command::Select, &response[..len]) {
+            Ok(_) => {
+                self.selected = true;
+                Ok(true)
+            }
+            Err(YkError::NotSelected) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the serial number of the selected YubiKey.
+    pub fn serial(&mut self) -> Result<u32, YkError<T::Error>> {
+        let mut response = [0u8; 6];
+        let len = self.transmit(&[CLA_ISO, INS_API_REQ, CMD_GET_SERIAL, 0, 6], &mut response)?;
+        let data = status(Command::Serial, &response[..len])?;
+        let serial = data
+            .get(..4)
+            .ok_or(YkError::InvalidResponse("serial number too short"))?;
+        Ok(u32::from_be_bytes([
+            serial[0], serial[1], serial[2], serial[3],
+        ]))
+    }
+
+    /// Reads the firmware version of the selected YubiKey.
+    pub fn version(&mut self) -> Result<Version, YkError<T::Error>> {
+        let [major, minor, patch, ..] = self.read_status()?;
+        Ok(Version {
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    /// Programs `slot` for HMAC-SHA1 challenge-response with `secret`, erasing
+    /// whatever the slot held before. No touch is required to answer.
+    ///
+    /// Fails with `YkError::WriteProtected` if the slot is protected by an
+    /// access code.
+    pub fn program_hmac(
+        &mut self,
+        slot: Slot,
+        secret: &[u8; SECRET_KEY_LEN],
+    ) -> Result<(), YkError<T::Error>> {
+        let mut config = [0u8; CONFIG_LEN + ACC_CODE_LEN];
+        let (key, uid) = secret.split_at(16);
+        config[CONFIG_KEY..CONFIG_KEY + key.len()].copy_from_slice(key);
+        config[CONFIG_UID..CONFIG_UID + uid.len()].copy_from_slice(uid);
+        config[CONFIG_EXT_FLAGS] = EXTFLAG_SERIAL_API_VISIBLE;
+        config[CONFIG_TKT_FLAGS] = TKTFLAG_CHAL_RESP;
+        config[CONFIG_CFG_FLAGS] = CFGFLAG_CHAL_HMAC | CFGFLAG_HMAC_LT64;
+        let crc = !crc16(&config[..CONFIG_CRC]);
+        config[CONFIG_CRC..CONFIG_LEN].copy_from_slice(&crc.to_le_bytes());
+
+        // The YubiKey answers with its status, where the programming sequence
+        // only moves on if the configuration was written.
+        let sequence = self.read_status()?[3];
+        let mut response = [0u8; 8];
+        let len = self.send(INS_API_REQ, slot.config_command(), &config, &mut response)?;
+        match status(Command::Config, &response[..len])? {
+            [_, _, _, after, ..] if *after != sequence => Ok(()),
+            [_, _, _, _, ..] => Err(YkError::WriteProtected),
+            _ => Err(YkError::InvalidResponse("status too short")),
+        }
+    }
+
+    /// Asks the YubiKey for the HMAC-SHA1 of `challenge` with the secret of `slot`.
+    pub fn hmac(
+        &mut self,
+        slot: Slot,
+        challenge: &[u8],
+    ) -> Result<[u8; RESPONSE_LEN], YkError<T::Error>> {
+        if challenge.len() > MAX_CHALLENGE_LEN {
+            return Err(YkError::CommandTooLong(challenge.len()));
+        }
+        let mut response = [0u8; RESPONSE_LEN + 2];
+        let len = self.send(INS_API_REQ, slot.command(), challenge, &mut response)?;
+        let data = status(Command::Hmac, &response[..len])?;
+        data.get(..RESPONSE_LEN)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(YkError::InvalidResponse("HMAC too short"))
+    }
+
+    /// Returns the slots configured for challenge-response.
+    pub fn find_slots(&mut self) -> Vec<Slot> {
+        let challenge = [0x42; 8];
+        [Slot::One, Slot::Two]
+            .into_iter()
+            .filter(|slot| self.hmac(*slot, &challenge).is_ok())
+            .collect()
+    }
+
+    /// Reads the status of the selected YubiKey: firmware version, programming
+    /// sequence and touch level.
+    fn read_status(&mut self) -> Result<[u8; 6], YkError<T::Error>> {
+        let mut response = [0u8; 8];
+        let len = self.transmit(&[CLA_ISO, INS_STATUS, 0, 0, 6], &mut response)?;
+        status(Command::Status, &response[..len])?
+            .get(..6)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(YkError::InvalidResponse("status too short"))
+    }
+
+    /// Sends the command `ins` with parameter `p1` and `data`, returning the response length.
+    fn send(
+        &mut self,
+        ins: u8,
+        p1: u8,
+        data: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, YkError<T::Error>> {
+        let len = u8::try_from(data.len()).map_err(|_| YkError::CommandTooLong(data.len()))?;
+        let mut command = vec![CLA_ISO, ins, p1, 0, len];
+        command.extend_from_slice(data);
+        self.transmit(&command, response)
+    }
+
+    /// Sends `command`, which must be SELECT unless the applet is selected.
+    fn transmit(
+        &mut self,
+        command: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, YkError<T::Error>> {
+        if !self.selected && command[1] != INS_SELECT {
+            return Err(YkError::NotSelected);
+        }
+        if command.len() > self.transport.max_command_len() {
+            return Err(YkError::CommandTooLong(command.len()));
+        }
+        match self.transport.exchange(command, response) {
+            Ok(len) => Ok(len.min(response.len())),
+            Err(e) => {
+                // The card may have left the field, dropping the selection.
+                self.selected = false;
+                Err(YkError::CommError(e))
+            }
+        }
+    }
+}
+
+/// Commands whose status words mean different things.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Select,
+    Status,
+    Serial,
+    Hmac,
+    Config,
+}
+
+/// Checks the status word ending the `response` to `command`, returning the data before it.
+///
+/// `SW_NOT_FOUND` is a missing applet for SELECT, turned into
+/// `YkError::NotSelected`, but an unprogrammed slot for HMAC. `SW_PRECONDITION`
+/// is a touch awaited for HMAC, and a protected slot for CONFIG.
+fn status<E>(command: Command, response: &[u8]) -> Result<&[u8], YkError<E>> {
+    let Some(split) = response.len().checked_sub(2) else {
+        return Err(YkError::InvalidResponse("no status word"));
+    };
+    let (data, sw) = response.split_at(split);
+    match (command, [sw[0], sw[1]]) {
+        (_, SW_OK) => Ok(data),
+        (Command::Select, SW_NOT_FOUND) => Err(YkError::NotSelected),
+        (Command::Hmac, SW_PRECONDITION) => Err(YkError::TouchRequired),
+        (Command::Hmac, SW_NOT_FOUND) => Err(YkError::SlotNotConfigured),
+        (Command::Config, SW_PRECONDITION) => Err(YkError::WriteProtected),
+        (_, sw) => Err(YkError::UnexpectedStatus(sw)),
+    }
+}
+
+/// CRC-16 of slot configurations (ISO 13239).
+pub(super) fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ u16::from(*byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ykhmac::mock::{SimulatedError, SimulatedYubiKey};
+
+    fn selected() -> YubiKey<SimulatedYubiKey> {
+        let mut yubikey = YubiKey::new(SimulatedYubiKey::new(1234));
+        assert!(yubikey.select().unwrap());
+        yubikey
+    }
+
+    /// Length of the data of a response made of the status word `sw` alone.
+    fn check(command: Command, sw: [u8; 2]) -> Result<usize, YkError<()>> {
+        status(command, &sw).map(|data| data.len())
+    }
+
+    #[test]
+    fn classifies_status_words_per_command() {
+        assert!(matches!(
+            check(Command::Select, SW_NOT_FOUND),
+            Err(YkError::NotSelected)
+        ));
+        assert!(matches!(
+            check(Command::Hmac, SW_NOT_FOUND),
+            Err(YkError::SlotNotConfigured)
+        ));
+        assert!(matches!(
+            check(Command::Hmac, SW_PRECONDITION),
+            Err(YkError::TouchRequired)
+        ));
+        assert!(matches!(
+            check(Command::Config, SW_PRECONDITION),
+            Err(YkError::WriteProtected)
+        ));
+        assert!(matches!(
+            check(Command::Select, SW_PRECONDITION),
+            Err(YkError::UnexpectedStatus(SW_PRECONDITION))
+        ));
+        assert!(matches!(
+            check(Command::Serial, SW_NOT_FOUND),
+            Err(YkError::UnexpectedStatus(SW_NOT_FOUND))
+        ));
+        assert!(matches!(check(Command::Status, SW_OK), Ok(0)));
+        assert!(matches!(
+            status::<()>(Command::Status, &[0x90]),
+            Err(YkError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn tells_a_card_without_the_applet() {
+        let mut yubikey = YubiKey::new(SimulatedYubiKey::new(1234));
+        yubikey.transport().set_applet(false);
+        assert!(!yubikey.select().unwrap());
+        assert!(matches!(yubikey.serial(), Err(YkError::NotSelected)));
+    }
+
+    #[test]
+    fn hands_over_the_error_of_the_transport() {
+        let mut yubikey = selected();
+        yubikey.transport().set_present(false);
+        assert!(matches!(
+            yubikey.serial(),
+            Err(YkError::CommError(SimulatedError::NotPresent))
+        ));
+        // The selection is dropped with the card.
+        yubikey.transport().set_present(true);
+        assert!(matches!(yubikey.serial(), Err(YkError::NotSelected)));
+    }
+
+    #[test]
+    fn refuses_a_command_too_long_for_the_transport() {
+        let mut yubikey = selected();
+        yubikey.transport().set_max_command_len(16);
+        assert!(matches!(
+            yubikey.hmac(Slot::Two, &[0; 16]),
+            Err(YkError::CommandTooLong(21))
+        ));
+        assert!(matches!(
+            yubikey.hmac(Slot::Two, &[0; MAX_CHALLENGE_LEN + 1]),
+            Err(YkError::CommandTooLong(65))
+        ));
+    }
+
+    #[test]
+    fn reports_a_write_protected_slot() {
+        let mut yubikey = selected();
+        yubikey.transport().set_write_protected(true);
+        assert!(matches!(
+            yubikey.program_hmac(Slot::Two, &[0x42; SECRET_KEY_LEN]),
+            Err(YkError::WriteProtected)
+        ));
+        assert!(matches!(
+            yubikey.hmac(Slot::Two, &[0; 8]),
+            Err(YkError::SlotNotConfigured)
+        ));
+    }
+}